@@ -0,0 +1,19 @@
+-- This migration adds question of the day schedules and the guild timezone they run in.
+ALTER TABLE guild_settings ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+
+CREATE TABLE IF NOT EXISTS qotd_schedules (
+    guild_id INTEGER PRIMARY KEY,
+    channel_id INTEGER NOT NULL,
+    post_time TEXT NOT NULL,
+    question_type TEXT CHECK(question_type IN ('TRUTH', 'DARE')) NOT NULL,
+    rating TEXT CHECK(rating IN ('PG', 'PG-13', 'ALL')) NOT NULL,
+    last_posted TEXT DEFAULT NULL
+);
+
+-- Questions that have been posted, used to avoid repeating recent prompts
+CREATE TABLE IF NOT EXISTS qotd_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    question_uid TEXT NOT NULL,
+    posted_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,5 @@
+-- This migration adds a language setting to guilds and a language to questions.
+-- A NULL guild language means each member's Discord language is used.
+ALTER TABLE guild_settings ADD COLUMN language TEXT DEFAULT NULL;
+
+ALTER TABLE questions ADD COLUMN language TEXT NOT NULL DEFAULT 'en';
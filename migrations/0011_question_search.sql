@@ -0,0 +1,23 @@
+-- This migration adds a full-text search index over question prompts.
+-- The index reads from the questions table and is kept in sync with triggers.
+CREATE VIRTUAL TABLE IF NOT EXISTS questions_fts USING fts5(
+    prompt,
+    content = 'questions',
+    content_rowid = 'id'
+);
+
+-- Index the questions that already exist
+INSERT INTO questions_fts (questions_fts) VALUES ('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS questions_fts_insert AFTER INSERT ON questions BEGIN
+    INSERT INTO questions_fts (rowid, prompt) VALUES (new.id, new.prompt);
+END;
+
+CREATE TRIGGER IF NOT EXISTS questions_fts_delete AFTER DELETE ON questions BEGIN
+    INSERT INTO questions_fts (questions_fts, rowid, prompt) VALUES ('delete', old.id, old.prompt);
+END;
+
+CREATE TRIGGER IF NOT EXISTS questions_fts_update AFTER UPDATE OF prompt ON questions BEGIN
+    INSERT INTO questions_fts (questions_fts, rowid, prompt) VALUES ('delete', old.id, old.prompt);
+    INSERT INTO questions_fts (rowid, prompt) VALUES (new.id, new.prompt);
+END;
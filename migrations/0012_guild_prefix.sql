@@ -0,0 +1,3 @@
+-- This migration adds a text command prefix setting to guilds.
+-- A NULL prefix means the prefix from the config is used.
+ALTER TABLE guild_settings ADD COLUMN prefix TEXT DEFAULT NULL;
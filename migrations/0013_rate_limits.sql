@@ -0,0 +1,5 @@
+-- This migration adds rate limit overrides to guilds, written as requests/seconds like 5/10.
+-- A NULL limit means the limit from the config is used.
+ALTER TABLE guild_settings ADD COLUMN rate_limit_user TEXT DEFAULT NULL;
+ALTER TABLE guild_settings ADD COLUMN rate_limit_channel TEXT DEFAULT NULL;
+ALTER TABLE guild_settings ADD COLUMN rate_limit_guild TEXT DEFAULT NULL;
@@ -0,0 +1,9 @@
+-- This migration adds a review status to questions and a blocklist for each guild.
+-- Questions with the pending status are held for a moderator and aren't drawn.
+ALTER TABLE questions ADD COLUMN status TEXT NOT NULL DEFAULT 'approved';
+
+CREATE TABLE IF NOT EXISTS guild_blocklist (
+    guild_id INTEGER NOT NULL,
+    word TEXT NOT NULL,
+    PRIMARY KEY (guild_id, word)
+);
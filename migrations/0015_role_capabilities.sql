@@ -0,0 +1,8 @@
+-- This migration lets guilds grant capabilities, like submitting or moderating questions, to roles.
+-- A capability no role was granted falls back to its default.
+CREATE TABLE IF NOT EXISTS guild_role_capabilities (
+    guild_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    capability TEXT NOT NULL,
+    PRIMARY KEY (guild_id, role_id, capability)
+);
@@ -0,0 +1,4 @@
+-- This migration adds back the admin column of guild_settings.
+-- 0008 rebuilt the table without the column 0006 added, so the setting was never saved since.
+-- The old values were dropped with the table, every guild starts with anyone allowed to add questions.
+ALTER TABLE guild_settings ADD COLUMN admin BOOLEAN NOT NULL DEFAULT FALSE;
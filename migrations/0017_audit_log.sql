@@ -0,0 +1,17 @@
+-- This migration adds the audit log of moderation and settings changes, and the channel each guild mirrors it to.
+-- Entries are mirrored by a background task, which marks them once they were sent.
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    actor_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT DEFAULT NULL,
+    old_value TEXT DEFAULT NULL,
+    new_value TEXT DEFAULT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    mirrored BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_guild ON audit_log (guild_id, id);
+
+ALTER TABLE guild_settings ADD COLUMN audit_channel INTEGER DEFAULT NULL;
@@ -0,0 +1,6 @@
+-- This migration records who added each question and when.
+-- Questions from before have no author or creation time, so only moderators can change them.
+ALTER TABLE questions ADD COLUMN author_id INTEGER DEFAULT NULL;
+ALTER TABLE questions ADD COLUMN created_at TEXT DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS questions_author ON questions (guild_id, author_id);
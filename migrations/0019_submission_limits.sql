@@ -0,0 +1,13 @@
+-- This migration adds the members banned from adding questions in each guild, and the guild's daily quota.
+-- A ban without an expiry lasts until a moderator lifts it, expired bans are ignored.
+CREATE TABLE IF NOT EXISTS submission_bans (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    moderator_id INTEGER NOT NULL,
+    reason TEXT DEFAULT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TEXT DEFAULT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+ALTER TABLE guild_settings ADD COLUMN submission_quota INTEGER DEFAULT NULL;
@@ -0,0 +1,6 @@
+-- This migration lets questions be moved to a trash instead of being deleted right away.
+-- Trashed questions are hidden everywhere until they are restored, and purged after the retention period.
+ALTER TABLE questions ADD COLUMN deleted_at TEXT DEFAULT NULL;
+ALTER TABLE questions ADD COLUMN deleted_by INTEGER DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS questions_trash ON questions (guild_id, deleted_at);
@@ -0,0 +1,16 @@
+-- This migration adds a tag to questions, so they can be grouped and changed in bulk,
+-- and the bulk operations waiting for the member who previewed them to confirm.
+ALTER TABLE questions ADD COLUMN tag TEXT DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS questions_tag ON questions (guild_id, tag);
+
+-- The uids of the previewed questions are kept, one per line, so confirming changes exactly those
+CREATE TABLE IF NOT EXISTS bulk_operations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    value TEXT DEFAULT NULL,
+    uids TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,15 @@
+-- This migration adds the questions written in the question form, kept while the member who wrote them
+-- looks at the preview. New questions are given their uid here, so the preview can already show it.
+CREATE TABLE IF NOT EXISTS question_drafts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    question_uid TEXT NOT NULL,
+    editing INTEGER NOT NULL DEFAULT 0,
+    prompt TEXT NOT NULL,
+    question_type TEXT NOT NULL,
+    rating TEXT NOT NULL,
+    language TEXT NOT NULL,
+    tag TEXT DEFAULT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
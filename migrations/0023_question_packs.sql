@@ -0,0 +1,22 @@
+-- This migration adds question packs: a guild publishes the questions it gave a tag under a share code,
+-- and other guilds subscribe to the pack with that code to draw its questions too.
+-- The questions stay in the publishing guild, so only it can change them.
+CREATE TABLE IF NOT EXISTS question_packs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    share_code TEXT NOT NULL UNIQUE,
+    published_by INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (guild_id, tag)
+);
+
+CREATE TABLE IF NOT EXISTS pack_subscriptions (
+    guild_id INTEGER NOT NULL,
+    pack_id INTEGER NOT NULL,
+    subscribed_by INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, pack_id)
+);
+
+CREATE INDEX IF NOT EXISTS pack_subscriptions_pack ON pack_subscriptions (pack_id);
@@ -0,0 +1,534 @@
+//! Audit module for the bot
+//! Records who changed questions or settings in a guild and when, with the value before and after,
+//! so moderators can look back through `/audit`. Guilds can also have every entry mirrored to a
+//! log channel, which a background task does so a slow channel doesn't hold up commands.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serenity::all::{
+    ChannelId, ChannelType, CommandOptionType, CreateAllowedMentions, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateMessage, GuildId, Http, UserId,
+};
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use tracing::{error, info, warn};
+
+use crate::{
+    bot::Bot,
+    embed::{fit_lines, page_components, MAX_EMBED_DESCRIPTION},
+    error::{Error, Result},
+    i18n::{t, tf, CommandLocalizer},
+    invocation::{Invocation, Reply},
+    other_impl::{EmbedMaker, MessageMaker},
+    pagination::PageStateError,
+    permissions::{Capability, Member},
+};
+
+/// Prefix of the ids of audit log page buttons, like `audit.n.3`
+pub const AUDIT_PREFIX: &str = "audit.";
+
+/// How often the mirror checks for new entries
+const MIRROR_TICK: Duration = Duration::from_secs(10);
+
+/// Most entries mirrored in one tick, the rest wait for the next one
+const MIRROR_BATCH: i64 = 50;
+
+/// Longest value shown for an entry, prompts can be much longer
+const MAX_VALUE_LENGTH: usize = 120;
+
+/// Something that was changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    AddQuestion,
+    EditQuestion,
+    RemoveQuestion,
+    RestoreQuestion,
+    ApproveQuestion,
+    RejectQuestion,
+    SetRating,
+    SetQuestionPermissions,
+    SetLanguage,
+    SetPrefix,
+    SetRateLimit,
+    BlockWord,
+    UnblockWord,
+    GrantCapability,
+    RevokeCapability,
+    SetQotd,
+    ClearQotd,
+    SetAuditChannel,
+    BanSubmitter,
+    UnbanSubmitter,
+    SetSubmissionQuota,
+    BulkRemove,
+    BulkRetag,
+    BulkRerate,
+    PublishPack,
+    UnpublishPack,
+    SubscribePack,
+    UnsubscribePack,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 28] = [
+        AuditAction::AddQuestion,
+        AuditAction::EditQuestion,
+        AuditAction::RemoveQuestion,
+        AuditAction::RestoreQuestion,
+        AuditAction::ApproveQuestion,
+        AuditAction::RejectQuestion,
+        AuditAction::SetRating,
+        AuditAction::SetQuestionPermissions,
+        AuditAction::SetLanguage,
+        AuditAction::SetPrefix,
+        AuditAction::SetRateLimit,
+        AuditAction::BlockWord,
+        AuditAction::UnblockWord,
+        AuditAction::GrantCapability,
+        AuditAction::RevokeCapability,
+        AuditAction::SetQotd,
+        AuditAction::ClearQotd,
+        AuditAction::SetAuditChannel,
+        AuditAction::BanSubmitter,
+        AuditAction::UnbanSubmitter,
+        AuditAction::SetSubmissionQuota,
+        AuditAction::BulkRemove,
+        AuditAction::BulkRetag,
+        AuditAction::BulkRerate,
+        AuditAction::PublishPack,
+        AuditAction::UnpublishPack,
+        AuditAction::SubscribePack,
+        AuditAction::UnsubscribePack,
+    ];
+
+    /// Name used in the database and the locale files
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuditAction::AddQuestion => "add_question",
+            AuditAction::EditQuestion => "edit_question",
+            AuditAction::RemoveQuestion => "remove_question",
+            AuditAction::RestoreQuestion => "restore_question",
+            AuditAction::ApproveQuestion => "approve_question",
+            AuditAction::RejectQuestion => "reject_question",
+            AuditAction::SetRating => "set_rating",
+            AuditAction::SetQuestionPermissions => "set_question_permissions",
+            AuditAction::SetLanguage => "set_language",
+            AuditAction::SetPrefix => "set_prefix",
+            AuditAction::SetRateLimit => "set_rate_limit",
+            AuditAction::BlockWord => "block_word",
+            AuditAction::UnblockWord => "unblock_word",
+            AuditAction::GrantCapability => "grant_capability",
+            AuditAction::RevokeCapability => "revoke_capability",
+            AuditAction::SetQotd => "set_qotd",
+            AuditAction::ClearQotd => "clear_qotd",
+            AuditAction::SetAuditChannel => "set_audit_channel",
+            AuditAction::BanSubmitter => "ban_submitter",
+            AuditAction::UnbanSubmitter => "unban_submitter",
+            AuditAction::SetSubmissionQuota => "set_submission_quota",
+            AuditAction::BulkRemove => "bulk_remove",
+            AuditAction::BulkRetag => "bulk_retag",
+            AuditAction::BulkRerate => "bulk_rerate",
+            AuditAction::PublishPack => "publish_pack",
+            AuditAction::UnpublishPack => "unpublish_pack",
+            AuditAction::SubscribePack => "subscribe_pack",
+            AuditAction::UnsubscribePack => "unsubscribe_pack",
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        AuditAction::ALL.into_iter().find(|action| action.name() == s).ok_or(())
+    }
+}
+
+/// An entry of the audit log
+/// `id` and `created_at` are set by the database, entries made with `new` have them at 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub guild_id: GuildId,
+    /// Member who made the change
+    pub actor_id: UserId,
+    pub action: AuditAction,
+    /// What was changed, like the uid of a question, `None` for settings
+    pub target: Option<String>,
+    /// Value before the change, `None` if there wasn't one
+    pub before: Option<String>,
+    /// Value after the change, `None` if it was removed
+    pub after: Option<String>,
+    /// Unix timestamp of the change
+    pub created_at: i64,
+}
+
+impl AuditEntry {
+    /// Makes an entry for a change a member made
+    pub fn new(guild_id: GuildId, actor_id: UserId, action: AuditAction) -> AuditEntry {
+        AuditEntry {
+            id: 0,
+            guild_id,
+            actor_id,
+            action,
+            target: None,
+            before: None,
+            after: None,
+            created_at: 0,
+        }
+    }
+
+    /// Makes an entry for a change made by the member who ran a command
+    pub fn from_invocation(invocation: &Invocation, action: AuditAction) -> Option<AuditEntry> {
+        Some(AuditEntry::new(invocation.guild_id?, invocation.user_id, action))
+    }
+
+    pub fn target(mut self, target: impl ToString) -> AuditEntry {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    pub fn before(mut self, before: Option<impl ToString>) -> AuditEntry {
+        self.before = before.map(|before| before.to_string());
+        self
+    }
+
+    pub fn after(mut self, after: Option<impl ToString>) -> AuditEntry {
+        self.after = after.map(|after| after.to_string());
+        self
+    }
+
+    /// Describes the entry on one line, like `<t:1700000000:f> @member set the rating: PG → PG-13`
+    pub fn describe(&self, language: &str) -> String {
+        let mut line = format!(
+            "<t:{}:f> <@{}> {}",
+            self.created_at,
+            self.actor_id,
+            t(language, &format!("audit.action.{}", self.action.name()))
+        );
+
+        if let Some(target) = &self.target {
+            line.push_str(&format!(" `{target}`"));
+        }
+
+        let value = |value: &Option<String>| match value {
+            Some(value) => shorten(value),
+            None => t(language, "audit.none"),
+        };
+
+        match (&self.before, &self.after) {
+            (None, None) => {}
+            (None, after) => line.push_str(&format!(": {}", value(after))),
+            (before, after) => line.push_str(&format!(": {} → {}", value(before), value(after))),
+        }
+
+        line
+    }
+}
+
+/// Shortens long values, cutting on a character boundary
+fn shorten(value: &str) -> String {
+    match value.char_indices().nth(MAX_VALUE_LENGTH) {
+        Some((end, _)) => format!("{}…", &value[..end]),
+        None => value.to_string(),
+    }
+}
+
+impl FromRow<'_, SqliteRow> for AuditEntry {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let action: String = row.try_get("action")?;
+
+        Ok(AuditEntry {
+            id: row.try_get("id")?,
+            guild_id: GuildId::new(row.try_get::<i64, _>("guild_id")? as u64),
+            actor_id: UserId::new(row.try_get::<i64, _>("actor_id")? as u64),
+            action: AuditAction::from_str(&action).map_err(|_| sqlx::Error::ColumnDecode {
+                index: "action".to_string(),
+                source: format!("unknown audit action {action:?}").into(),
+            })?,
+            target: row.try_get("target")?,
+            before: row.try_get("old_value")?,
+            after: row.try_get("new_value")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// Columns of an entry, with the timestamp as unix seconds
+const ENTRY_COLUMNS: &str = r#"
+    a.id, a.guild_id, a.actor_id, a.action, a.target, a.old_value, a.new_value,
+    CAST(strftime('%s', a.created_at) AS INTEGER) AS created_at
+"#;
+
+impl Bot {
+    /// Records a change in the audit log
+    /// The change was already made, so a failure is logged instead of failing the command
+    pub async fn record_audit(&self, entry: AuditEntry) {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO audit_log (guild_id, actor_id, action, target, old_value, new_value)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(entry.guild_id.get() as i64)
+        .bind(entry.actor_id.get() as i64)
+        .bind(entry.action.name())
+        .bind(&entry.target)
+        .bind(&entry.before)
+        .bind(&entry.after)
+        .execute(&self.database)
+        .await;
+
+        match result {
+            Ok(_) => info!(action = entry.action.name(), actor = %entry.actor_id, "audit entry recorded"),
+            Err(e) => error!(action = entry.action.name(), error = %e, "failed to record audit entry"),
+        }
+    }
+
+    /// Counts the entries in the audit log of a guild
+    pub async fn count_audit_entries(&self, guild_id: GuildId) -> Result<i64> {
+        let total = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM audit_log WHERE guild_id = ?")
+            .bind(guild_id.get() as i64)
+            .fetch_one(&self.database)
+            .await?;
+
+        Ok(total)
+    }
+
+    /// Gets a page of the audit log of a guild, newest first
+    pub async fn get_audit_page(&self, guild_id: GuildId, page: usize) -> Result<Vec<AuditEntry>> {
+        let entries = sqlx::query_as::<_, AuditEntry>(&format!(
+            r#"
+            SELECT {ENTRY_COLUMNS} FROM audit_log a
+            WHERE a.guild_id = ?
+            ORDER BY a.id DESC
+            LIMIT ? OFFSET ?
+            "#
+        ))
+        .bind(guild_id.get() as i64)
+        .bind(self.config.page_size)
+        .bind((page.saturating_sub(1) as i64) * self.config.page_size)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Sets the channel the audit log of a guild is mirrored to, `None` stops mirroring
+    /// Entries from before are marked as mirrored so the channel doesn't get the whole history at once
+    pub async fn set_audit_channel(&self, guild_id: GuildId, channel_id: Option<ChannelId>) -> Result<()> {
+        sqlx::query("UPDATE audit_log SET mirrored = TRUE WHERE guild_id = ? AND mirrored = FALSE")
+            .bind(guild_id.get() as i64)
+            .execute(&self.database)
+            .await?;
+
+        self.update_guild_settings(guild_id, |settings| settings.audit_channel = channel_id).await?;
+
+        Ok(())
+    }
+
+    /// Gets entries waiting to be mirrored, with the channel to mirror each one to
+    async fn get_unmirrored_audit(&self) -> Result<Vec<(AuditEntry, ChannelId)>> {
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT {ENTRY_COLUMNS}, g.audit_channel FROM audit_log a
+            JOIN guild_settings g ON g.guild_id = a.guild_id
+            WHERE a.mirrored = FALSE AND g.audit_channel IS NOT NULL
+            ORDER BY a.id
+            LIMIT ?
+            "#
+        ))
+        .bind(MIRROR_BATCH)
+        .fetch_all(&self.database)
+        .await?;
+
+        let entries = rows
+            .iter()
+            .map(|row| {
+                let channel_id = ChannelId::new(row.try_get::<i64, _>("audit_channel")? as u64);
+                Ok((AuditEntry::from_row(row)?, channel_id))
+            })
+            .collect::<sqlx::Result<_>>()?;
+
+        Ok(entries)
+    }
+
+    async fn mark_mirrored(&self, id: i64) -> Result<()> {
+        sqlx::query("UPDATE audit_log SET mirrored = TRUE WHERE id = ?")
+            .bind(id)
+            .execute(&self.database)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Runs forever, posting new audit entries to the log channel of their guild
+///
+/// # Parameters
+/// * `bot: Bot` - Bot instance for database interaction
+/// * `http: Arc<Http>` - Http client used to post to channels
+pub async fn run_audit_mirror(bot: Bot, http: Arc<Http>) {
+    let mut interval = tokio::time::interval(MIRROR_TICK);
+
+    loop {
+        interval.tick().await;
+
+        let entries = match bot.get_unmirrored_audit().await {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!(error = %e, "failed to load audit entries to mirror");
+                continue;
+            }
+        };
+
+        for (entry, channel_id) in entries {
+            // Marked first so a channel that can't be posted to doesn't retry every tick
+            if let Err(e) = bot.mark_mirrored(entry.id).await {
+                error!(error = %e, "failed to mark audit entry as mirrored");
+                continue;
+            }
+
+            let language = bot
+                .get_language(Some(entry.guild_id), "")
+                .await
+                .unwrap_or("en");
+            let message = CreateMessage::new()
+                .content(entry.describe(language))
+                .allowed_mentions(CreateAllowedMentions::new());
+
+            if let Err(e) = channel_id.send_message(&http, message).await {
+                warn!(guild = %entry.guild_id, channel = %channel_id, error = %e, "failed to mirror audit entry");
+            }
+        }
+    }
+}
+
+/// Command to look through the audit log
+pub fn audit_command() -> CreateCommand {
+    CreateCommand::new("audit")
+        .localized("audit")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "show", "Show who changed what in this server")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "page", "The page to show")
+                        .min_int_value(1),
+                ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "channel",
+                "Post every change to a channel",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Channel,
+                    "channel",
+                    "The channel to post to, leave empty to stop posting",
+                )
+                .channel_types(vec![ChannelType::Text]),
+            ),
+        )
+}
+
+pub async fn audit(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+
+    match invocation.subcommand.as_deref() {
+        Some("show") => {
+            let page = invocation.int_option("page").unwrap_or(1).max(1) as usize;
+
+            Ok(audit_page(bot, guild_id, page, language).await?.ephemeral(true))
+        }
+        Some("channel") => {
+            // Looking through the log is moderation, changing where it goes is a setting
+            bot.require(Member::from(invocation), Capability::Settings).await?;
+
+            let channel_id = invocation.channel_option("channel");
+            let before = bot.get_guild_settings(guild_id).await?.audit_channel;
+
+            bot.set_audit_channel(guild_id, channel_id).await?;
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::SetAuditChannel) {
+                let mention = |channel: ChannelId| format!("<#{channel}>");
+                bot.record_audit(entry.before(before.map(mention)).after(channel_id.map(mention))).await;
+            }
+
+            Ok(Reply::text(match channel_id {
+                Some(channel_id) => tf(language, "audit.channel_set", &[("channel", &channel_id)]),
+                None => t(language, "audit.channel_cleared"),
+            }))
+        }
+        _ => Err(Error::InvalidInput(t(language, "common.unknown"))),
+    }
+}
+
+/// Checks if a component id belongs to the audit log
+pub fn is_audit_id(id: &str) -> bool {
+    id.starts_with(AUDIT_PREFIX)
+}
+
+/// Updates the audit log to the page a button or select menu points to
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to access the database
+/// * `interaction: &str` - The id of the component the client used, like `audit.n.3`
+/// * `selected_page: Option<&str>` - The page picked in the page select menu, if it was used
+/// * `guild_id: Option<GuildId>` - Guild the interaction came from
+/// * `language: &str` - Language to display the page in
+pub async fn change_audit_page(
+    bot: &Bot,
+    interaction: &str,
+    selected_page: Option<&str>,
+    guild_id: Option<GuildId>,
+    language: &str,
+) -> Result<CreateInteractionResponse> {
+    let guild_id = guild_id.ok_or(Error::GuildOnly)?;
+
+    let page = selected_page
+        .or_else(|| interaction.rsplit('.').next())
+        .and_then(|page| page.parse::<usize>().ok());
+
+    match page {
+        Some(page) => Ok(audit_page(bot, guild_id, page, language).await?.to_update()),
+        None => Ok(Reply::text(t(language, "list.expired")).ephemeral(true).to_interaction_message()),
+    }
+}
+
+/// Makes a page of the audit log
+async fn audit_page(bot: &Bot, guild_id: GuildId, page: usize, language: &str) -> Result<Reply> {
+    let total = bot.count_audit_entries(guild_id).await?;
+
+    if total == 0 {
+        return Ok(Reply::text(t(language, "audit.empty")));
+    }
+
+    // The log may have grown since the button was made, so keep the page in range
+    let pages = (total as usize).div_ceil(bot.config.page_size as usize);
+    let page = page.clamp(1, pages);
+
+    let entries = bot.get_audit_page(guild_id, page).await?;
+
+    let encode = |page, slot| Ok::<_, PageStateError>(format!("{AUDIT_PREFIX}{slot}.{page}"));
+    let components = page_components(page, pages, language, encode).unwrap_or_default();
+
+    // Values come last, so long lines are cut at the end to fit the page in the embed
+    let lines: Vec<String> = entries.iter().map(|entry| entry.describe(language)).collect();
+    let description = fit_lines(&lines, MAX_EMBED_DESCRIPTION, |line| line.as_str(), |_, line| line.to_string());
+    let title = tf(language, "audit.title", &[("page", &page), ("pages", &pages)]);
+
+    Ok(Reply::embed(description.to_embed(title, t(language, "audit.footer"))).components(components))
+}
@@ -0,0 +1,168 @@
+//! Autocomplete module for the bot
+//! Suggests questions while a `question_uid` option is typed, so members can pick a question by its text
+//! instead of copying its uid out of a list. Any command can opt in by building its option with
+//! `question_uid_option` and naming where its questions come from in `QuestionScope::for_command`.
+
+use serenity::all::{
+    AutocompleteChoice, CommandInteraction, CommandOptionType, CreateAutocompleteResponse, CreateCommandOption,
+    GuildId, UserId,
+};
+
+use crate::{
+    bot::Bot,
+    content_policy::PENDING,
+    error::Result,
+    invocation::Invocation,
+    permissions::{Capability, Member},
+    questions::Question,
+};
+
+/// Most choices Discord shows for an option
+const MAX_CHOICES: i64 = 25;
+
+/// Longest name Discord accepts for a choice
+const MAX_CHOICE_NAME: usize = 100;
+
+/// Which of a guild's questions a command works on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuestionScope {
+    /// Questions outside of the trash
+    Active,
+    /// Questions in the trash
+    Trash,
+    /// Questions waiting for review
+    Pending,
+}
+
+impl QuestionScope {
+    /// Gets where the questions a command takes come from, and the capability needed to pick any of them
+    /// Members without the capability are only offered their own questions
+    ///
+    /// # Returns
+    /// * `Option<(QuestionScope, Capability)>` - `None` if the command has no `question_uid` option
+    pub fn for_command(name: &str) -> Option<(QuestionScope, Capability)> {
+        match name {
+            "edit_question" | "question_form" => Some((QuestionScope::Active, Capability::Edit)),
+            "remove_question" => Some((QuestionScope::Active, Capability::Remove)),
+            "restore_question" => Some((QuestionScope::Trash, Capability::Remove)),
+            "review" => Some((QuestionScope::Pending, Capability::Moderate)),
+            _ => None,
+        }
+    }
+
+    /// Condition a question has to meet to be in the scope
+    fn condition(&self) -> &'static str {
+        match self {
+            QuestionScope::Active => "deleted_at IS NULL",
+            QuestionScope::Trash => "deleted_at IS NOT NULL",
+            QuestionScope::Pending => "deleted_at IS NULL AND status = ?4",
+        }
+    }
+}
+
+/// Makes a required `question_uid` option that suggests questions as it is typed
+///
+/// # Parameters
+/// * `description: &str` - Description shown under the option
+pub fn question_uid_option(description: &str) -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::String, "question_uid", description)
+        .required(true)
+        .set_autocomplete(true)
+}
+
+impl Bot {
+    /// Finds the questions of a guild whose text or uid contains what was typed, newest first
+    /// Uids starting with the text come before everything else, so pasting a uid finds it right away
+    ///
+    /// # Parameters
+    /// * `guild_id: GuildId` - Guild the questions were added to
+    /// * `scope: QuestionScope` - Which of the guild's questions to look through
+    /// * `text: &str` - What was typed so far, everything matches when it's empty
+    /// * `author: Option<UserId>` - Only finds questions by this member, if set
+    pub async fn find_question_choices(
+        &self,
+        guild_id: GuildId,
+        scope: QuestionScope,
+        text: &str,
+        author: Option<UserId>,
+    ) -> Result<Vec<Question>> {
+        let query = format!(
+            r#"
+            SELECT * FROM questions
+            WHERE guild_id = ?1 AND {}
+            AND (prompt LIKE ?2 ESCAPE '\' OR uid LIKE ?2 ESCAPE '\')
+            AND (?5 IS NULL OR author_id = ?5)
+            ORDER BY uid LIKE ?3 ESCAPE '\' DESC, id DESC
+            LIMIT ?6
+            "#,
+            scope.condition()
+        );
+
+        let text = escape_like(text.trim());
+
+        let questions = sqlx::query_as::<_, Question>(&query)
+            .bind(guild_id.get() as i64)
+            .bind(format!("%{text}%"))
+            .bind(format!("{text}%"))
+            .bind(PENDING)
+            .bind(author.map(|author| author.get() as i64))
+            .bind(MAX_CHOICES)
+            .fetch_all(&self.database)
+            .await?;
+
+        Ok(questions)
+    }
+}
+
+/// Escapes the wildcards of a `LIKE` pattern, so they match themselves
+fn escape_like(text: &str) -> String {
+    text.replace('\\', r"\\").replace('%', r"\%").replace('_', r"\_")
+}
+
+/// Names a choice after its question, like `a1b2c3 · Have you ever...`, cut to fit
+fn choice_name(question: &Question) -> String {
+    let name = format!("{} · {}", question.uid, question.prompt.replace('\n', " "));
+
+    match name.char_indices().nth(MAX_CHOICE_NAME - 1) {
+        Some((end, _)) => format!("{}…", &name[..end]),
+        None => name,
+    }
+}
+
+/// Suggests questions for the `question_uid` option being typed
+/// Outside of guilds, and for options that aren't question uids, nothing is suggested
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to access the database
+/// * `command: &CommandInteraction` - The autocomplete interaction, with the focused option
+pub async fn question_choices(bot: &Bot, command: &CommandInteraction) -> Result<CreateAutocompleteResponse> {
+    let response = CreateAutocompleteResponse::new();
+
+    let (Some(guild_id), Some(focused)) = (command.guild_id, command.data.autocomplete()) else {
+        return Ok(response);
+    };
+
+    let Some((scope, capability)) = QuestionScope::for_command(&command.data.name) else {
+        return Ok(response);
+    };
+
+    if focused.name != "question_uid" {
+        return Ok(response);
+    }
+
+    // Members who can't act on every question only see the ones they added
+    let invocation = Invocation::from_command(command);
+    let author = match bot.require(Member::from(&invocation), capability).await {
+        Ok(()) => None,
+        Err(_) => Some(invocation.user_id),
+    };
+
+    let choices = bot
+        .find_question_choices(guild_id, scope, focused.value, author)
+        .await?
+        .iter()
+        .map(|question| AutocompleteChoice::new(choice_name(question), question.uid.clone()))
+        .collect();
+
+    Ok(response.set_choices(choices))
+}
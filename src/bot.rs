@@ -1,287 +1,701 @@
+use std::sync::Arc;
+use std::time::Instant;
+
 use serenity::all::{
-    Command, Context, CreateActionRow, CreateMessage, EventHandler, GuildId, Interaction, Message, Ready
+    CommandInteraction, ComponentInteraction, CreateAutocompleteResponse, ComponentInteractionDataKind, Context, CreateInteractionResponse, CreateMessage, EventHandler, GuildId, Interaction, Message, ModalInteraction, Permissions, Ready, ResumedEvent, ShardStageUpdateEvent, UserId
 };
+use serenity::gateway::ConnectionStage;
 use serenity::async_trait;
+use tracing::{error, info, info_span, warn, Instrument};
 
+use crate::audit::{audit, change_audit_page, is_audit_id};
+use crate::autocomplete::question_choices;
+use crate::bulk::{bulk, bulk_button, is_bulk_id};
 use crate::commands::{
-    add_question, create_commands, dare, list_custom_questions, list_questions, remove_question, set_question_permissions, set_rating, truth
+    add_question, edit_question, my_questions, create_commands, dare, list_custom_questions, list_questions, remove_question, set_language, set_prefix, set_question_permissions, set_rating, truth
 };
-use crate::embed::{dare_button, embed_text, truth_button};
-use crate::interactions::{next_page, previous_page, truth_or_dare};
+use crate::config::Config;
+use crate::content_policy::{blocklist, review};
+use crate::health::Health;
+use crate::error::{error_response, report_error, Error, Result};
+use crate::i18n::{fallback_language, t, LANGUAGES};
+use crate::interactions::{change_page, truth_or_dare};
+use crate::invocation::{Invocation, Reply};
+use crate::metrics::METRICS;
 use crate::other_impl::MessageMaker;
+use crate::pagination::is_page_id;
+use crate::packs::packs;
+use crate::permissions::{permissions, Capability, Member};
+use crate::qotd::qotd;
+use crate::question_form::{form_button, is_form_id, question_form, submit_form};
+use crate::rate_limit::{set_rate_limit, RateLimiter};
+use crate::registration::sync_commands;
+use crate::search::search_questions;
+use crate::submission_limits::submissions;
+use crate::trash::{is_undo_id, restore_question, trash, undo_removal};
+use crate::text_commands::{find_command, CommandSpec};
 use crate::questions::{Question, QuestionType};
 
+#[derive(Clone)]
 pub struct Bot {
     pub database: sqlx::SqlitePool,
+    /// Key used to sign data sent to Discord, like pagination button ids
+    pub signing_key: Vec<u8>,
+    /// Settings loaded at startup
+    pub config: Arc<Config>,
+    /// Connection state reported by the health endpoint
+    pub health: Arc<Health>,
+    /// Recent uses of commands and buttons, to turn away spam
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Definitions of the commands, used to parse text commands
+    pub command_specs: Arc<Vec<CommandSpec>>,
 }
 
 #[async_trait]
 impl EventHandler for Bot {
     async fn message(&self, ctx: Context, msg: Message) {
-        let question_type: QuestionType = match msg.content.as_str().trim() {
-            "!truth" => QuestionType::TRUTH,
-            "!dare" => QuestionType::DARE,
-            _ => return,
+        if !self.config.features.text_commands || msg.author.bot {
+            return;
+        }
+
+        let prefix = match self.get_guild_prefix(msg.guild_id).await {
+            Ok(prefix) => prefix,
+            Err(e) => {
+                error!(error = %e, "failed to get prefix");
+                return;
+            }
         };
 
-        let embed = embed_text(
-            &self,
-            question_type,
-            self.get_guild_rating(msg.guild_id).await,
-            msg.guild_id,
-        )
-        .await;
+        let Some(text) = msg.content.trim().strip_prefix(prefix.as_str()) else {
+            return;
+        };
 
-        let row = CreateActionRow::Buttons(vec![truth_button(), dare_button()]);
+        // Messages that only start with the prefix are ignored
+        let Some((spec, arguments)) = find_command(text, &self.command_specs) else {
+            return;
+        };
 
-        let builder = CreateMessage::new().embed(embed).components(vec![row]);
+        let span = info_span!(
+            "text_command",
+            command = spec.name.as_str(),
+            guild = msg.guild_id.map(|id| id.get()),
+            channel = msg.channel_id.get(),
+            user = msg.author.id.get(),
+        );
 
-        let msg = msg.channel_id.send_message(&ctx.http, builder).await;
+        self.handle_message(&ctx, &msg, spec, arguments, &prefix).instrument(span).await
+    }
 
-        if let Err(why) = msg {
-            eprintln!("Error sending message: {why:?}");
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::Component(component_interaction) => {
+                let span = info_span!(
+                    "component",
+                    custom_id = component_interaction.data.custom_id.as_str(),
+                    guild = component_interaction.guild_id.map(|id| id.get()),
+                    channel = component_interaction.channel_id.get(),
+                    user = component_interaction.user.id.get(),
+                );
+
+                self.handle_component(&ctx, &component_interaction).instrument(span).await
+            }
+            Interaction::Command(command) => {
+                let span = info_span!(
+                    "command",
+                    command = command.data.name.as_str(),
+                    guild = command.guild_id.map(|id| id.get()),
+                    channel = command.channel_id.get(),
+                    user = command.user.id.get(),
+                );
+
+                self.handle_command(&ctx, &command).instrument(span).await
+            }
+            Interaction::Autocomplete(autocomplete) => {
+                let span = info_span!(
+                    "autocomplete",
+                    command = autocomplete.data.name.as_str(),
+                    guild = autocomplete.guild_id.map(|id| id.get()),
+                    user = autocomplete.user.id.get(),
+                );
+
+                self.handle_autocomplete(&ctx, &autocomplete).instrument(span).await
+            }
+            Interaction::Modal(modal_interaction) => {
+                let span = info_span!(
+                    "modal",
+                    custom_id = modal_interaction.data.custom_id.as_str(),
+                    guild = modal_interaction.guild_id.map(|id| id.get()),
+                    channel = modal_interaction.channel_id.get(),
+                    user = modal_interaction.user.id.get(),
+                );
+
+                self.handle_modal(&ctx, &modal_interaction).instrument(span).await
+            }
+            _ => {}
         }
     }
 
-    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        if let Some(component_interaction) = interaction.clone().message_component() {
-            let response = match component_interaction.data.custom_id.as_str() {
-                "truth" | "dare" => {
-                    truth_or_dare(
-                        self,
-                        &component_interaction.data.custom_id,
-                        component_interaction.guild_id,
-                    )
-                    .await
-                }
-                // Next Page of Question List
-                interaction if interaction.contains("next_page-") => {
-                    // Delete the original message
-                    if let Err(e) = component_interaction.message.delete(&ctx.http).await {
-                        eprintln!("Failed to delete message: {e:?}");
-                    }
-                    next_page(&self, interaction, component_interaction.guild_id).await
-                }
-                // Previous Page of Question List
-                interaction if interaction.contains("previous_page-") => {
-                    // Delete the original message
-                    if let Err(e) = component_interaction.message.delete(&ctx.http).await {
-                        eprintln!("Failed to delete message: {e:?}");
-                    }
-                    previous_page(&self, interaction, component_interaction.guild_id).await
+    /// Runs when the bot is connected to Discord
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!(user = %ready.user.name, guilds = ready.guilds.len(), "connected to Discord");
+        self.health.set_gateway_connected(true);
+
+        // Only commands that changed since the last start are sent to Discord
+        if let Err(why) = sync_commands(
+            &ctx.http,
+            self.config.command_scope(),
+            create_commands(&self.config),
+            self.config.purge_stale_commands,
+        )
+        .await
+        {
+            error!(error = %why, "failed to register commands");
+        }
+    }
+
+    /// Runs when the gateway connection is resumed after dropping
+    async fn resume(&self, _ctx: Context, _event: ResumedEvent) {
+        info!("resumed connection to Discord");
+        self.health.set_gateway_connected(true);
+    }
+
+    /// Runs when the gateway connection changes, like when it drops and reconnects
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        let connected = event.new == ConnectionStage::Connected;
+
+        if !connected {
+            warn!(stage = %event.new, "gateway is not connected");
+        }
+
+        self.health.set_gateway_connected(connected);
+    }
+}
+
+impl Bot {
+    /// Checks if a user is one of the owners set in the config
+    pub fn is_owner(&self, user_id: UserId) -> bool {
+        self.config.is_owner(user_id)
+    }
+
+    /// Answers a text command
+    ///
+    /// # Parameters
+    /// * `spec: &CommandSpec` - The command the message is running
+    /// * `arguments: &str` - Text after the command name
+    /// * `prefix: &str` - Prefix of the guild, shown in usage errors
+    async fn handle_message(&self, ctx: &Context, msg: &Message, spec: &CommandSpec, arguments: &str, prefix: &str) {
+        let started = Instant::now();
+        let name = spec.name.as_str();
+
+        if let Some(limited) = self.rate_limit(msg.author.id, msg.channel_id, msg.guild_id).await {
+            info!(scope = limited.scope.name(), "rate limited");
+
+            // Only the first message turned away is answered so the bot doesn't add to the flood
+            if limited.first {
+                let language = self.get_language(msg.guild_id, "").await.unwrap_or_else(|_| fallback_language(""));
+
+                if let Err(why) = msg.channel_id.send_message(&ctx.http, limited.reply(language).to_message()).await {
+                    METRICS.response_failures.with_label_values(&["text_command"]).inc();
+                    error!(error = %why, "failed to send message");
                 }
-                _ => "Uh, you shouldn't have seen this...".to_interaction_message(),
-            };
+            }
+            return;
+        }
 
-            if let Err(why) = component_interaction
-                .create_response(&ctx.http, response)
+        METRICS.commands.with_label_values(&[name]).inc();
+
+        // Text commands don't have a locale, so only the guild setting applies
+        let (language, result) = match self.get_language(msg.guild_id, "").await {
+            Ok(language) => (language, self.run_text_command(ctx, msg, spec, arguments, prefix, language).await),
+            Err(e) => (fallback_language(""), Err(e)),
+        };
+
+        let builder = match result {
+            Ok(Some(reply)) => reply.to_message(),
+            Ok(None) => {
+                warn!("unknown command");
+                return;
+            }
+            Err(e) => CreateMessage::new().embed(report_error(&e, language, self.is_owner(msg.author.id))),
+        };
+
+        match msg.channel_id.send_message(&ctx.http, builder).await {
+            Ok(_) => info!(elapsed_ms = started.elapsed().as_millis() as u64, "text command handled"),
+            Err(why) => {
+                METRICS.response_failures.with_label_values(&["text_command"]).inc();
+                error!(error = %why, "failed to send message");
+            }
+        }
+
+        METRICS
+            .latency
+            .with_label_values(&["text_command", name])
+            .observe(started.elapsed().as_secs_f64());
+    }
+
+    /// Responds to a button press or select menu choice
+    async fn handle_component(&self, ctx: &Context, component_interaction: &ComponentInteraction) {
+        let started = Instant::now();
+
+        // Ids carry state like the list page or a uid, so they are grouped to keep the labels bounded
+        let name = match component_interaction.data.custom_id.as_str() {
+            "truth" => "truth",
+            "dare" => "dare",
+            id if is_undo_id(id) => "undo",
+            id if is_form_id(id) => "form",
+            id if is_bulk_id(id) => "bulk",
+            id if is_audit_id(id) => "audit",
+            id if is_page_id(id) => "page",
+            _ => "unknown",
+        };
+
+        if let Some(limited) = self
+            .rate_limit(component_interaction.user.id, component_interaction.channel_id, component_interaction.guild_id)
+            .await
+        {
+            info!(scope = limited.scope.name(), "rate limited");
+            let language = self
+                .get_language(component_interaction.guild_id, &component_interaction.locale)
                 .await
-            {
-                eprintln!("Failed to respond to interaction : {why:?}")
+                .unwrap_or_else(|_| fallback_language(&component_interaction.locale));
+
+            let response = limited.reply(language).to_interaction_message();
+            if let Err(why) = component_interaction.create_response(&ctx.http, response).await {
+                METRICS.response_failures.with_label_values(&["component"]).inc();
+                error!(error = %why, "failed to respond to interaction");
             }
+            return;
         }
 
-        // Command interactions
-        if let Some(command) = interaction.command() {
-            match command.data.name.as_str() {
-                "set_rating" => {
-                    command
-                        .create_response(&ctx.http, set_rating(self, &command).await)
-                        .await
-                        .ok();
-                }
-                "add_question" => {
-                    command
-                        .create_response(&ctx.http, add_question(self, &command).await)
-                        .await
-                        .ok();
-                }
-                "list_questions" => {
-                    command
-                        .create_response(&ctx.http, list_questions(self, command.guild_id).await)
-                        .await
-                        .ok();
-                }
-                "list_custom_questions" => {
-                    command
-                        .create_response(
-                            &ctx.http,
-                            list_custom_questions(&self, command.guild_id).await,
-                        )
-                        .await
-                        .ok();
-                }
-                "set_question_permissions" => {
-                    command
-                        .create_response(&ctx.http, set_question_permissions(self, &command).await)
-                        .await
-                        .ok();
-                }
-                "remove_question" => {
-                    command.create_response(&ctx.http, remove_question(self, &command).await)
-                    .await
-                    .ok();
-                }
-                "truth" => {
-                    command.create_response(&ctx.http, truth(self, &command).await)
-                    .await
-                    .ok();
-                }
-                "dare" => {
-                    command.create_response(&ctx.http, dare(self, &command).await)
-                    .await
-                    .ok();
-                }
-                _ => {}
+        METRICS.components.with_label_values(&[name]).inc();
+
+        let (language, result) = match self
+            .get_language(component_interaction.guild_id, &component_interaction.locale)
+            .await
+        {
+            Ok(language) => (language, self.run_component(component_interaction, language).await),
+            Err(e) => (fallback_language(&component_interaction.locale), Err(e)),
+        };
+
+        let response = result.unwrap_or_else(|e| {
+            error_response(&e, language, self.is_owner(component_interaction.user.id))
+        });
+
+        match component_interaction.create_response(&ctx.http, response).await {
+            Ok(_) => info!(elapsed_ms = started.elapsed().as_millis() as u64, "component handled"),
+            Err(why) => {
+                METRICS.response_failures.with_label_values(&["component"]).inc();
+                error!(error = %why, "failed to respond to interaction");
             }
         }
+
+        METRICS
+            .latency
+            .with_label_values(&["component", name])
+            .observe(started.elapsed().as_secs_f64());
     }
 
-    /// Runs when the bot is connected to Discord
-    async fn ready(&self, ctx: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
+    /// Responds to a slash command
+    async fn handle_command(&self, ctx: &Context, command: &CommandInteraction) {
+        let started = Instant::now();
+        let name = command.data.name.as_str();
 
-        // Lists all the commands and their ids
-        // for command in Command::get_global_commands(&ctx.http).await.unwrap() {
-        //     println!("{}-{}", command.name, command.id);
-        // }
+        if let Some(limited) = self.rate_limit(command.user.id, command.channel_id, command.guild_id).await {
+            info!(scope = limited.scope.name(), "rate limited");
+            let language = self
+                .get_language(command.guild_id, &command.locale)
+                .await
+                .unwrap_or_else(|_| fallback_language(&command.locale));
 
-        for command in create_commands() {
-            Command::create_global_command(&ctx.http, command).await.unwrap();
+            let response = limited.reply(language).to_interaction_message();
+            if let Err(why) = command.create_response(&ctx.http, response).await {
+                METRICS.response_failures.with_label_values(&["command"]).inc();
+                error!(error = %why, "failed to respond to command");
+            }
+            return;
+        }
+
+        METRICS.commands.with_label_values(&[name]).inc();
+
+        let invocation = Invocation::from_command(command);
+
+        let (language, result) = match self.get_language(command.guild_id, &command.locale).await {
+            Ok(language) => (language, self.run_command(&invocation, language).await),
+            Err(e) => (fallback_language(&command.locale), Err(e)),
+        };
+
+        let response = match result {
+            Ok(Some(reply)) => reply.to_interaction_message(),
+            Ok(None) => {
+                warn!("unknown command");
+                return;
+            }
+            Err(e) => error_response(&e, language, self.is_owner(command.user.id)),
+        };
+
+        match command.create_response(&ctx.http, response).await {
+            Ok(_) => info!(elapsed_ms = started.elapsed().as_millis() as u64, "command handled"),
+            Err(why) => {
+                METRICS.response_failures.with_label_values(&["command"]).inc();
+                error!(error = %why, "failed to respond to command");
+            }
+        }
+
+        METRICS
+            .latency
+            .with_label_values(&["command", name])
+            .observe(started.elapsed().as_secs_f64());
+    }
+
+    /// Responds to a submitted form
+    async fn handle_modal(&self, ctx: &Context, modal_interaction: &ModalInteraction) {
+        let started = Instant::now();
+
+        let language = match self.get_language(modal_interaction.guild_id, &modal_interaction.locale).await {
+            Ok(language) => language,
+            Err(e) => {
+                error!(error = %e, "failed to get language");
+                fallback_language(&modal_interaction.locale)
+            }
+        };
+
+        if let Some(limited) = self
+            .rate_limit(modal_interaction.user.id, modal_interaction.channel_id, modal_interaction.guild_id)
+            .await
+        {
+            info!(scope = limited.scope.name(), "rate limited");
+
+            let response = limited.reply(language).to_interaction_message();
+            if let Err(why) = modal_interaction.create_response(&ctx.http, response).await {
+                METRICS.response_failures.with_label_values(&["modal"]).inc();
+                error!(error = %why, "failed to respond to interaction");
+            }
+            return;
+        }
+
+        METRICS.components.with_label_values(&["form"]).inc();
+
+        let member = modal_interaction.member.as_ref();
+        let roles = member.map(|member| member.roles.clone()).unwrap_or_default();
+        let member = Member {
+            guild_id: modal_interaction.guild_id,
+            user_id: modal_interaction.user.id,
+            roles: &roles,
+            is_admin: member
+                .and_then(|member| member.permissions)
+                .is_some_and(|permissions| permissions.administrator()),
+        };
+
+        // The question form is the only form, and it checks who can add or edit questions itself
+        let result = match is_form_id(&modal_interaction.data.custom_id) {
+            true => submit_form(self, &modal_interaction.data, member, language).await,
+            false => Err(Error::InvalidInput(t(language, "common.unknown"))),
+        };
+
+        let response = result.unwrap_or_else(|e| error_response(&e, language, self.is_owner(modal_interaction.user.id)));
+
+        match modal_interaction.create_response(&ctx.http, response).await {
+            Ok(_) => info!(elapsed_ms = started.elapsed().as_millis() as u64, "modal handled"),
+            Err(why) => {
+                METRICS.response_failures.with_label_values(&["modal"]).inc();
+                error!(error = %why, "failed to respond to interaction");
+            }
+        }
+
+        METRICS
+            .latency
+            .with_label_values(&["modal", "form"])
+            .observe(started.elapsed().as_secs_f64());
+    }
+
+    /// Suggests values for the option being typed
+    /// Suggestions are sent on every keystroke, so they aren't rate limited or counted as commands
+    async fn handle_autocomplete(&self, ctx: &Context, autocomplete: &CommandInteraction) {
+        let started = Instant::now();
+
+        // A failed search shows no suggestions, the command can still be run with a typed uid
+        let response = question_choices(self, autocomplete).await.unwrap_or_else(|e| {
+            error!(error = %e, "failed to find suggestions");
+            CreateAutocompleteResponse::new()
+        });
+
+        if let Err(why) = autocomplete
+            .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+            .await
+        {
+            METRICS.response_failures.with_label_values(&["autocomplete"]).inc();
+            error!(error = %why, "failed to send suggestions");
+        }
+
+        METRICS
+            .latency
+            .with_label_values(&["autocomplete", autocomplete.data.name.as_str()])
+            .observe(started.elapsed().as_secs_f64());
+    }
+
+    /// Runs a slash or text command
+    ///
+    /// # Returns
+    /// * `Option<Reply>` - The reply, `None` if the command is unknown
+    async fn run_command(
+        &self,
+        invocation: &Invocation,
+        language: &'static str,
+    ) -> Result<Option<Reply>> {
+        if let Some(capability) = Capability::for_command(&invocation.name) {
+            self.require(Member::from(invocation), capability).await?;
+        }
+
+        let reply = match invocation.name.as_str() {
+            "set_rating" => set_rating(self, invocation, language).await?,
+            "add_question" => add_question(self, invocation, language).await?,
+            "edit_question" => edit_question(self, invocation, language).await?,
+            "question_form" => question_form(self, invocation, language).await?,
+            "my_questions" => my_questions(self, invocation, language).await?,
+            "list_questions" => list_questions(self, invocation, language).await?,
+            "list_custom_questions" => list_custom_questions(self, invocation, language).await?,
+            "set_question_permissions" => set_question_permissions(self, invocation, language).await?,
+            "set_language" => set_language(self, invocation, language).await?,
+            "set_prefix" => set_prefix(self, invocation, language).await?,
+            "set_rate_limit" => set_rate_limit(self, invocation, language).await?,
+            "blocklist" => blocklist(self, invocation, language).await?,
+            "review" => review(self, invocation, language).await?,
+            "search_questions" => search_questions(self, invocation, language).await?,
+            "remove_question" => remove_question(self, invocation, language).await?,
+            "restore_question" => restore_question(self, invocation, language).await?,
+            "trash" => trash(self, invocation, language).await?,
+            "bulk" => bulk(self, invocation, language).await?,
+            "truth" => truth(self, invocation, language).await?,
+            "dare" => dare(self, invocation, language).await?,
+            "qotd" => qotd(self, invocation, language).await?,
+            "permissions" => permissions(self, invocation, language).await?,
+            "audit" => audit(self, invocation, language).await?,
+            "submissions" => submissions(self, invocation, language).await?,
+            "packs" => packs(self, invocation, language).await?,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(reply))
+    }
+
+    /// Parses a text command and runs it like the slash command of the same name
+    /// Discord checks the permissions of slash commands, so text commands check them here
+    async fn run_text_command(
+        &self,
+        ctx: &Context,
+        msg: &Message,
+        spec: &CommandSpec,
+        arguments: &str,
+        prefix: &str,
+        language: &'static str,
+    ) -> Result<Option<Reply>> {
+        let arguments = spec.parse(arguments, prefix, language)?;
+        let permissions = self.member_permissions(ctx, msg).await?;
+
+        if let (Some(required), Some(permissions)) = (spec.required_permissions(), permissions)
+            && !permissions.contains(required)
+            && !permissions.administrator()
+        {
+            return Err(Error::PermissionDenied);
+        }
+
+        let invocation = Invocation {
+            name: spec.name.clone(),
+            subcommand: arguments.subcommand,
+            options: arguments.options,
+            guild_id: msg.guild_id,
+            channel_id: msg.channel_id,
+            user_id: msg.author.id,
+            locale: String::new(),
+            permissions,
+            roles: msg.member.as_ref().map(|member| member.roles.clone()).unwrap_or_default(),
+        };
+
+        self.run_command(&invocation, language).await
+    }
+
+    /// Gets the permissions of the member who sent a message, `None` outside of guilds
+    /// Messages don't carry permissions, so they come from the roles of the cached guild
+    async fn member_permissions(&self, ctx: &Context, msg: &Message) -> Result<Option<Permissions>> {
+        let (Some(guild_id), Some(member)) = (msg.guild_id, msg.member.as_deref()) else {
+            return Ok(None);
+        };
+
+        if let Some(permissions) = member.permissions {
+            return Ok(Some(permissions));
+        }
+
+        let cached = ctx
+            .cache
+            .guild(guild_id)
+            .map(|guild| guild.partial_member_permissions(msg.author.id, member));
+
+        // The guild is only fetched if it isn't cached yet, like right after the bot starts
+        match cached {
+            Some(permissions) => Ok(Some(permissions)),
+            None => {
+                let guild = guild_id.to_partial_guild(ctx).await?;
+                Ok(Some(guild.partial_member_permissions(msg.author.id, member)))
+            }
+        }
+    }
+
+    /// Handles a button press or select menu choice
+    async fn run_component(
+        &self,
+        component_interaction: &ComponentInteraction,
+        language: &'static str,
+    ) -> Result<CreateInteractionResponse> {
+        let member = component_interaction.member.as_ref();
+        let roles = member.map(|member| member.roles.clone()).unwrap_or_default();
+        let is_admin = member
+            .and_then(|member| member.permissions)
+            .is_some_and(|permissions| permissions.administrator());
+
+        // The audit log is for moderators, every other button draws or browses questions
+        // Undo and bulk confirm buttons also check the capability they need when they are pressed
+        let capability = match is_audit_id(&component_interaction.data.custom_id) {
+            true => Capability::Moderate,
+            false => Capability::Play,
+        };
+
+        let member = Member {
+            guild_id: component_interaction.guild_id,
+            user_id: component_interaction.user.id,
+            roles: &roles,
+            is_admin,
+        };
+
+        self.require(member, capability).await?;
+
+        match component_interaction.data.custom_id.as_str() {
+            "truth" | "dare" => Ok(truth_or_dare(
+                self,
+                &component_interaction.data.custom_id,
+                component_interaction.guild_id,
+                language,
+            )
+            .await?
+            .to_interaction_message()),
+            // Undoing a removal, checked against who can remove the question
+            interaction if is_undo_id(interaction) => undo_removal(self, interaction, member, language).await,
+            // Opening the question form, or saving or cancelling its preview
+            interaction if is_form_id(interaction) => form_button(self, interaction, member, language).await,
+            // Confirming or cancelling a bulk operation
+            interaction if is_bulk_id(interaction) => bulk_button(self, interaction, member, language).await,
+            // Another page of the audit log, updated in place
+            interaction if is_audit_id(interaction) => {
+                let selected_page = match &component_interaction.data.kind {
+                    ComponentInteractionDataKind::StringSelect { values } => values.first().map(|v| v.as_str()),
+                    _ => None,
+                };
+
+                change_audit_page(self, interaction, selected_page, component_interaction.guild_id, language).await
+            }
+            // Another Page of a Question List, updated in place
+            interaction if is_page_id(interaction) => {
+                let selected_page = match &component_interaction.data.kind {
+                    ComponentInteractionDataKind::StringSelect { values } => values.first().map(|v| v.as_str()),
+                    _ => None,
+                };
+
+                // Moderators also see who added each question
+                let show_authors = self.require(member, Capability::Moderate).await.is_ok();
+
+                change_page(
+                    self,
+                    interaction,
+                    selected_page,
+                    component_interaction.guild_id,
+                    component_interaction.user.id,
+                    show_authors,
+                    language,
+                )
+                .await
+            }
+            _ => Err(Error::InvalidInput(t(language, "common.unknown"))),
         }
     }
-}
 
-impl Bot {
     /// Retrieves a random question from the database based on the specified question type and rating.
+    /// Questions in the provided language are preferred when there are any.
+    /// Questions with a uid in `excluded_uids` are never drawn.
     /// Returns `None` if no question is found.
     pub async fn get_random_question(
         &self,
         question_type: QuestionType,
         question_rating: &str,
         guild_id: Option<GuildId>,
-    ) -> Result<Option<Question>, sqlx::Error> {
+        language: &str,
+        excluded_uids: &[String],
+    ) -> Result<Option<Question>> {
+        // Pack questions using words the guild blocked are left out
+        let blocked = match guild_id {
+            Some(guild_id) => self.get_blocked_pack_questions(guild_id, question_type, question_rating).await?,
+            None => Vec::new(),
+        };
+
         let guild_id = match guild_id {
             Some(v) => v.get() as i64,
             None => 0
         };
 
-        // !TODO - guild specific
+        // Packs the guild subscribed to are drawn from like its own questions
         let query = r#"
             SELECT * FROM questions
-            WHERE question_type = ?1 AND rating = ?2 AND (guild_id = ?3 OR guild_id IS NULL)
-            ORDER BY RANDOM()
+            WHERE question_type = ?1 AND rating = ?2
+                AND (
+                    guild_id = ?3 OR guild_id IS NULL
+                    OR EXISTS (
+                        SELECT 1 FROM pack_subscriptions
+                        JOIN question_packs ON question_packs.id = pack_subscriptions.pack_id
+                        WHERE pack_subscriptions.guild_id = ?3
+                            AND question_packs.guild_id = questions.guild_id AND question_packs.tag = questions.tag
+                    )
+                )
+                AND status = 'approved' AND deleted_at IS NULL
+                AND id NOT IN (SELECT value FROM json_each(?5))
+                AND uid NOT IN (SELECT value FROM json_each(?6))
+            ORDER BY language = ?4 DESC, RANDOM()
             LIMIT 1
         "#;
 
         let question = sqlx::query_as::<sqlx::Sqlite, Question>(query)
-            .bind(&question_type.to_string())
+            .bind(question_type.to_string())
             .bind(question_rating)
-            .bind(guild_id as i64)
+            .bind(guild_id)
+            .bind(language)
+            .bind(serde_json::to_string(&blocked).expect("a list of ids serializes"))
+            .bind(serde_json::to_string(excluded_uids).expect("a list of uids serializes"))
             .fetch_optional(&self.database)
             .await?;
-        
-        Ok(question)
-    }
-
-    pub async fn set_guild_rating(&self, guild_id: i64, rating: &str) -> Result<(), sqlx::Error> {
-        println!("{}", rating);
-
-        sqlx::query(
-            r#"
-            INSERT INTO guild_settings (guild_id, rating)
-            VALUES (?, ?)
-            ON CONFLICT(guild_id) DO UPDATE SET rating = excluded.rating
-            "#,
-        )
-        .bind(guild_id)
-        .bind(rating)
-        .execute(&self.database)
-        .await?;
-
-        Ok(())
-    }
 
-    pub async fn get_guild_rating(&self, guild_id: Option<GuildId>) -> String {
-        // Default the guild_rating to PG
-        if guild_id.is_none() {
-            "PG".to_string()
-        } else {
-            // Query the rating for the guild_id
-            let result = sqlx::query_scalar::<_, String>(
-                r#"
-            SELECT rating FROM guild_settings
-            WHERE guild_id = ?
-            "#,
-            )
-            .bind(guild_id.unwrap().get() as i64)
-            .fetch_optional(&self.database)
-            .await;
-
-            match result.unwrap() {
-                Some(rating) => rating,
-                _ => "PG".to_string(),
-            }
+        if let Some(question) = &question {
+            METRICS
+                .draws
+                .with_label_values(&[&question.question_type.to_string(), &question.rating])
+                .inc();
         }
+
+        Ok(question)
     }
 
-    /// Sets the question permissions for a guild.
+    /// Gets the language to respond in
+    /// The guild's language setting is used if it has one, otherwise the Discord locale
     ///
     /// # Parameters
-    /// * `guild_id: Option<GuildId>` - The guild to set permissions for
-    /// * `can_add_questions: bool` - Whether the guild can add questions
-    pub async fn set_guild_question_permissions(
-        &self,
-        guild_id: Option<GuildId>,
-        admin: bool,
-    ) -> Result<(), sqlx::Error> {
-        let rating = self.get_guild_rating(guild_id).await;
-
+    /// * `guild_id: Option<GuildId>` - The guild to check the setting of
+    /// * `locale: &str` - Discord locale of the member, empty if unknown
+    pub async fn get_language(&self, guild_id: Option<GuildId>, locale: &str) -> Result<&'static str> {
         if let Some(guild_id) = guild_id {
-            match sqlx::query(
-                r#"
-                INSERT INTO guild_settings (guild_id, rating, admin)
-                VALUES (?, ?, ?)
-                ON CONFLICT(guild_id) DO UPDATE SET admin = excluded.admin
-                "#,
-            )
-            .bind(guild_id.get() as i64)
-            .bind(rating)
-            .bind(admin)
-            .execute(&self.database)
-            .await {
-                Err(e) => eprint!("{e}"),
-                _ => ()
-            }
-        }
-        Ok(())
-    }
+            let language = self.get_guild_settings(guild_id).await?.language;
 
-    pub async fn get_guild_question_permissions(&self, guild_id: Option<GuildId>) -> bool {
-        // Default the guild_rating to PG
-        if guild_id.is_none() {
-            false
-        } else {
-            // Query the rating for the guild_id
-            let result = sqlx::query_scalar::<_, i64>(
-                r#"
-            SELECT admin FROM guild_settings
-            WHERE guild_id = ?
-            "#,
-            )
-            .bind(guild_id.unwrap().get() as i64)
-            .fetch_optional(&self.database)
-            .await;
-
-            match result {
-                Ok(val) => match val {
-                    
-                    Some(admin) => admin == 1,
-                    _ => false,
-                },
-                Err(e) => {eprintln!("{e}"); false}
+            if let Some((code, _)) = LANGUAGES.iter().find(|(code, _)| Some(*code) == language.as_deref()) {
+                return Ok(code);
             }
         }
+
+        Ok(fallback_language(locale))
     }
 
     /// Gets all questions in the provided guild and in the default questions
@@ -291,22 +705,21 @@ impl Bot {
     ///
     /// # Returns
     /// * `Vec<Questions>` - A list of all the questions
-    pub async fn get_questions(&self, guild_id: Option<GuildId>) -> Vec<Question> {
-        if guild_id.is_none() {
-            Vec::new()
-        } else {
-            let query = r#"
-                SELECT * FROM questions WHERE guild_id = ?1 OR guild_id IS NULL
-                "#;
-
-            let questions = sqlx::query_as::<_, Question>(query)
-                .bind(guild_id.unwrap().get() as i64)
-                .fetch_all(&self.database)
-                .await
-                .ok();
+    pub async fn get_questions(&self, guild_id: Option<GuildId>) -> Result<Vec<Question>> {
+        let Some(guild_id) = guild_id else {
+            return Ok(Vec::new());
+        };
 
-            questions.unwrap()
-        }
+        let query = r#"
+            SELECT * FROM questions WHERE (guild_id = ?1 OR guild_id IS NULL) AND deleted_at IS NULL
+            "#;
+
+        let questions = sqlx::query_as::<_, Question>(query)
+            .bind(guild_id.get() as i64)
+            .fetch_all(&self.database)
+            .await?;
+
+        Ok(questions)
     }
 
     /// Gets all questions in the provided guild
@@ -316,42 +729,44 @@ impl Bot {
     ///
     /// # Returns
     /// * `Vec<Questions>` - A list of all the questions
-    pub async fn get_custom_questions(&self, guild_id: Option<GuildId>) -> Vec<Question> {
-        if guild_id.is_none() {
-            Vec::new()
-        } else {
-            let query = r#"
-                SELECT * FROM questions WHERE guild_id = ?1
-                "#;
-
-            let questions = sqlx::query_as::<_, Question>(query)
-                .bind(guild_id.unwrap().get() as i64)
-                .fetch_all(&self.database)
-                .await
-                .ok();
+    pub async fn get_custom_questions(&self, guild_id: Option<GuildId>) -> Result<Vec<Question>> {
+        let Some(guild_id) = guild_id else {
+            return Ok(Vec::new());
+        };
 
-            questions.unwrap()
-        }
+        let query = r#"
+            SELECT * FROM questions WHERE guild_id = ?1 AND deleted_at IS NULL
+            "#;
+
+        let questions = sqlx::query_as::<_, Question>(query)
+            .bind(guild_id.get() as i64)
+            .fetch_all(&self.database)
+            .await?;
+
+        Ok(questions)
     }
 
-    pub async fn check_question_guild(&self, guild_id: Option<GuildId>, question_uid: &String) -> bool {
-        if guild_id.is_none() {
-            return false;
-        } else {
-            let query = r#"
-                SELECT * FROM questions WHERE guild_id = ?1 AND uid = ?2 LIMIT 1
-                "#;
-
-            let question = sqlx::query_as::<_, Question>(query)
-                .bind(guild_id.unwrap().get() as i64)
-                .bind(question_uid)
-                .fetch_optional(&self.database)
-                .await;
-
-            match question.unwrap() {
-                Some(val) => {println!("Prompt: {}", val.prompt); true},
-                None => false
-            }
-        }
+    /// Gets a question added to the provided guild by its uid, questions in the trash aren't found
+    pub async fn get_guild_question(&self, guild_id: GuildId, question_uid: &str) -> Result<Option<Question>> {
+        let query = r#"
+            SELECT * FROM questions WHERE guild_id = ?1 AND uid = ?2 AND deleted_at IS NULL LIMIT 1
+            "#;
+
+        let question = sqlx::query_as::<_, Question>(query)
+            .bind(guild_id.get() as i64)
+            .bind(question_uid)
+            .fetch_optional(&self.database)
+            .await?;
+
+        Ok(question)
+    }
+
+    /// Checks if a question belongs to the provided guild
+    pub async fn check_question_guild(&self, guild_id: Option<GuildId>, question_uid: &str) -> Result<bool> {
+        let Some(guild_id) = guild_id else {
+            return Ok(false);
+        };
+
+        Ok(self.get_guild_question(guild_id, question_uid).await?.is_some())
     }
 }
@@ -0,0 +1,500 @@
+//! Bulk module for the bot
+//! Removes, retags or re-rates every question of a guild that matches a filter at once. The command only
+//! previews the questions it would change and saves them with the operation, so pressing confirm changes
+//! exactly what was previewed, in one transaction. Operations not confirmed in time are dropped.
+
+use std::str::FromStr;
+
+use serenity::all::{
+    ButtonStyle, CommandOptionType, CreateActionRow, CreateButton, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, GuildId, UserId,
+};
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    bot::Bot,
+    error::{Error, Result},
+    i18n::{t, tf, CommandLocalizer},
+    invocation::{Invocation, Reply},
+    other_impl::EmbedMaker,
+    permissions::{Capability, Member},
+    questions::{normalize_tag, Question, QuestionType, MAX_TAG_LENGTH},
+};
+
+/// Prefix of the ids of confirm and cancel buttons, like `bulk.confirm.12`
+pub const BULK_PREFIX: &str = "bulk.";
+
+/// Minutes a preview can be confirmed for
+const CONFIRM_MINUTES: i64 = 15;
+
+/// Most questions one operation can change, more should be narrowed down with the filters
+const MAX_BULK: usize = 500;
+
+/// Questions listed in a preview, the rest are only counted
+const PREVIEW_LINES: usize = 10;
+
+/// Oldest age the filter can ask for, in days
+const MAX_AGE_DAYS: u64 = 3650;
+
+/// What a bulk operation does to the questions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkAction {
+    /// Moves them to the trash
+    Remove,
+    /// Gives them a tag, or removes their tags
+    Retag,
+    /// Gives them a rating
+    Rerate,
+}
+
+impl BulkAction {
+    /// Name used in the command, the database and the locale files
+    pub fn name(&self) -> &'static str {
+        match self {
+            BulkAction::Remove => "remove",
+            BulkAction::Retag => "retag",
+            BulkAction::Rerate => "rerate",
+        }
+    }
+
+    /// Capability needed to run the operation
+    pub fn capability(&self) -> Capability {
+        match self {
+            BulkAction::Remove => Capability::Remove,
+            BulkAction::Retag | BulkAction::Rerate => Capability::Edit,
+        }
+    }
+
+    fn audit_action(&self) -> AuditAction {
+        match self {
+            BulkAction::Remove => AuditAction::BulkRemove,
+            BulkAction::Retag => AuditAction::BulkRetag,
+            BulkAction::Rerate => AuditAction::BulkRerate,
+        }
+    }
+}
+
+impl FromStr for BulkAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        [BulkAction::Remove, BulkAction::Retag, BulkAction::Rerate]
+            .into_iter()
+            .find(|action| action.name() == s)
+            .ok_or(())
+    }
+}
+
+/// Which questions of a guild a bulk operation changes, every field that is set has to match
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BulkFilter {
+    pub question_type: Option<QuestionType>,
+    pub rating: Option<String>,
+    pub tag: Option<String>,
+    pub author_id: Option<UserId>,
+    /// Only questions added more than this many days ago, questions without a creation time always match
+    pub older_than_days: Option<i64>,
+}
+
+impl BulkFilter {
+    /// Reads the filter options of a command
+    pub fn from_invocation(invocation: &Invocation) -> BulkFilter {
+        BulkFilter {
+            question_type: invocation
+                .str_option("question_type")
+                .and_then(|s| QuestionType::from_str(s).ok())
+                .filter(|question_type| *question_type != QuestionType::NONE),
+            rating: invocation.str_option("rating").map(str::to_string),
+            tag: invocation.str_option("tag").and_then(normalize_tag),
+            author_id: invocation.user_option("author"),
+            older_than_days: invocation.int_option("older_than").map(|days| days.clamp(1, MAX_AGE_DAYS as i64)),
+        }
+    }
+
+    /// Checks if nothing is filtered, which would match every question of the guild
+    pub fn is_empty(&self) -> bool {
+        *self == BulkFilter::default()
+    }
+}
+
+/// A previewed operation waiting to be confirmed
+#[derive(Debug, Clone)]
+pub struct BulkOperation {
+    pub id: i64,
+    pub guild_id: GuildId,
+    /// Member who previewed it, the only one who can confirm it
+    pub user_id: UserId,
+    pub action: BulkAction,
+    /// New tag or rating, `None` when removing questions or their tags
+    pub value: Option<String>,
+    /// Questions shown in the preview
+    pub uids: Vec<String>,
+}
+
+impl FromRow<'_, SqliteRow> for BulkOperation {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let action: String = row.try_get("action")?;
+        let uids: String = row.try_get("uids")?;
+
+        Ok(BulkOperation {
+            id: row.try_get("id")?,
+            guild_id: GuildId::new(row.try_get::<i64, _>("guild_id")? as u64),
+            user_id: UserId::new(row.try_get::<i64, _>("user_id")? as u64),
+            action: BulkAction::from_str(&action).map_err(|_| sqlx::Error::ColumnDecode {
+                index: "action".to_string(),
+                source: format!("unknown bulk action {action:?}").into(),
+            })?,
+            value: row.try_get("value")?,
+            uids: uids.lines().map(str::to_string).collect(),
+        })
+    }
+}
+
+impl Bot {
+    /// Finds the questions of a guild a bulk operation would change, up to one more than `MAX_BULK`
+    /// Only questions added in the guild are changed, questions in the trash are left alone
+    pub async fn find_bulk_questions(&self, guild_id: GuildId, filter: &BulkFilter) -> Result<Vec<Question>> {
+        let questions = sqlx::query_as::<_, Question>(
+            r#"
+            SELECT * FROM questions
+            WHERE guild_id = ?1 AND deleted_at IS NULL
+                AND (?2 IS NULL OR question_type = ?2)
+                AND (?3 IS NULL OR rating = ?3)
+                AND (?4 IS NULL OR tag = ?4)
+                AND (?5 IS NULL OR author_id = ?5)
+                AND (?6 IS NULL OR created_at IS NULL OR created_at < datetime('now', ?6))
+            ORDER BY id
+            LIMIT ?7
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(filter.question_type.map(|question_type| question_type.to_string()))
+        .bind(&filter.rating)
+        .bind(&filter.tag)
+        .bind(filter.author_id.map(|author_id| author_id.get() as i64))
+        .bind(filter.older_than_days.map(|days| format!("-{days} days")))
+        .bind(MAX_BULK as i64 + 1)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(questions)
+    }
+
+    /// Saves a previewed operation until it is confirmed, dropping the ones that weren't in time
+    ///
+    /// # Returns
+    /// * `i64` - The id of the operation, used in the ids of its buttons
+    pub async fn save_bulk_operation(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        action: BulkAction,
+        value: Option<&str>,
+        uids: &[&str],
+    ) -> Result<i64> {
+        sqlx::query("DELETE FROM bulk_operations WHERE created_at < datetime('now', ?)")
+            .bind(format!("-{CONFIRM_MINUTES} minutes"))
+            .execute(&self.database)
+            .await?;
+
+        let id = sqlx::query_scalar::<_, i64>(
+            r#"
+            INSERT INTO bulk_operations (guild_id, user_id, action, value, uids)
+            VALUES (?, ?, ?, ?, ?)
+            RETURNING id
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .bind(action.name())
+        .bind(value)
+        .bind(uids.join("\n"))
+        .fetch_one(&self.database)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Gets an operation of a guild that can still be confirmed
+    pub async fn get_bulk_operation(&self, guild_id: GuildId, id: i64) -> Result<Option<BulkOperation>> {
+        let operation = sqlx::query_as::<_, BulkOperation>(
+            r#"
+            SELECT * FROM bulk_operations
+            WHERE id = ? AND guild_id = ? AND created_at >= datetime('now', ?)
+            "#,
+        )
+        .bind(id)
+        .bind(guild_id.get() as i64)
+        .bind(format!("-{CONFIRM_MINUTES} minutes"))
+        .fetch_optional(&self.database)
+        .await?;
+
+        Ok(operation)
+    }
+
+    /// Drops an operation, returning false if it was already gone so it can't run twice
+    pub async fn delete_bulk_operation(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM bulk_operations WHERE id = ?")
+            .bind(id)
+            .execute(&self.database)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Changes every question of an operation and drops the operation, all in one transaction
+    /// so a failure keeps the preview around to confirm again
+    /// Questions removed or moved to the trash since the preview are skipped
+    ///
+    /// # Parameters
+    /// * `operation: &BulkOperation` - The operation to run
+    /// * `user_id: UserId` - Member who confirmed it, recorded as who removed the questions
+    ///
+    /// # Returns
+    /// * `Option<u64>` - How many questions were changed, `None` if the operation already ran
+    pub async fn run_bulk_operation(&self, operation: &BulkOperation, user_id: UserId) -> Result<Option<u64>> {
+        let query = match operation.action {
+            BulkAction::Remove => "UPDATE questions SET deleted_at = CURRENT_TIMESTAMP, deleted_by = ?1",
+            BulkAction::Retag => "UPDATE questions SET tag = ?2",
+            BulkAction::Rerate => "UPDATE questions SET rating = ?2",
+        };
+        let query = format!("{query} WHERE guild_id = ?3 AND uid = ?4 AND deleted_at IS NULL");
+
+        let mut transaction = self.database.begin().await?;
+
+        // Dropped first, so a second confirm waits for this one and then finds nothing to run
+        let deleted = sqlx::query("DELETE FROM bulk_operations WHERE id = ?")
+            .bind(operation.id)
+            .execute(&mut *transaction)
+            .await?;
+
+        if deleted.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let mut changed = 0;
+
+        for uid in &operation.uids {
+            changed += sqlx::query(&query)
+                .bind(user_id.get() as i64)
+                .bind(&operation.value)
+                .bind(operation.guild_id.get() as i64)
+                .bind(uid)
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
+        }
+
+        transaction.commit().await?;
+
+        Ok(Some(changed))
+    }
+}
+
+/// Command to change many questions at once
+pub fn bulk_command() -> CreateCommand {
+    let filters = || {
+        vec![
+            CreateCommandOption::new(CommandOptionType::String, "question_type", "Only questions of this type")
+                .add_string_choice("Truth", "TRUTH")
+                .add_string_choice("Dare", "DARE"),
+            CreateCommandOption::new(CommandOptionType::String, "rating", "Only questions with this rating")
+                .add_string_choice("PG", "PG")
+                .add_string_choice("PG-13", "PG-13"),
+            CreateCommandOption::new(CommandOptionType::String, "tag", "Only questions with this tag")
+                .max_length(MAX_TAG_LENGTH),
+            CreateCommandOption::new(CommandOptionType::User, "author", "Only questions added by this member"),
+            CreateCommandOption::new(CommandOptionType::Integer, "older_than", "Only questions added more than this many days ago")
+                .min_int_value(1)
+                .max_int_value(MAX_AGE_DAYS),
+        ]
+    };
+    let subcommand = |name, description, options: Vec<CreateCommandOption>| {
+        options
+            .into_iter()
+            .chain(filters())
+            .fold(CreateCommandOption::new(CommandOptionType::SubCommand, name, description), |subcommand, option| {
+                subcommand.add_sub_option(option)
+            })
+    };
+
+    CreateCommand::new("bulk")
+        .localized("bulk")
+        .add_option(subcommand("remove", "Move every matching question to the trash", vec![]))
+        .add_option(subcommand(
+            "retag",
+            "Give every matching question a tag",
+            vec![CreateCommandOption::new(
+                CommandOptionType::String,
+                "new_tag",
+                "The tag to give them, leave empty to remove their tags",
+            )
+            .max_length(MAX_TAG_LENGTH)],
+        ))
+        .add_option(subcommand(
+            "rerate",
+            "Give every matching question a rating",
+            vec![CreateCommandOption::new(CommandOptionType::String, "new_rating", "The rating to give them")
+                .required(true)
+                .add_string_choice("PG", "PG")
+                .add_string_choice("PG-13", "PG-13")],
+        ))
+}
+
+pub async fn bulk(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+
+    let Some(action) = invocation.subcommand.as_deref().and_then(|name| BulkAction::from_str(name).ok()) else {
+        return Err(Error::InvalidInput(t(language, "common.unknown")));
+    };
+
+    bot.require(Member::from(invocation), action.capability()).await?;
+
+    let filter = BulkFilter::from_invocation(invocation);
+
+    // A filter is required so one command can't change every question by mistake
+    if filter.is_empty() {
+        return Err(Error::InvalidInput(t(language, "bulk.no_filter")));
+    }
+
+    let value = match action {
+        BulkAction::Remove => None,
+        BulkAction::Retag => invocation.str_option("new_tag").and_then(normalize_tag),
+        BulkAction::Rerate => Some(invocation.str_option("new_rating").unwrap_or("PG").to_string()),
+    };
+
+    let questions = bot.find_bulk_questions(guild_id, &filter).await?;
+
+    if questions.is_empty() {
+        return Err(Error::InvalidInput(t(language, "bulk.none")));
+    }
+    if questions.len() > MAX_BULK {
+        return Err(Error::InvalidInput(tf(language, "bulk.too_many", &[("max", &MAX_BULK)])));
+    }
+
+    let uids: Vec<&str> = questions.iter().map(|question| question.uid.as_str()).collect();
+    let id = bot
+        .save_bulk_operation(guild_id, invocation.user_id, action, value.as_deref(), &uids)
+        .await?;
+
+    let mut lines: Vec<String> = questions
+        .iter()
+        .take(PREVIEW_LINES)
+        .map(|question| format!("`{}` ({} - {}) {}", question.uid, question.question_type, question.rating, question.prompt))
+        .collect();
+
+    if questions.len() > PREVIEW_LINES {
+        lines.push(tf(language, "bulk.more", &[("count", &(questions.len() - PREVIEW_LINES))]));
+    }
+
+    let title = tf(
+        language,
+        &format!("bulk.preview.{}", action.name()),
+        &[("count", &questions.len()), ("value", &describe_value(value.as_deref(), language))],
+    );
+    let embed = lines
+        .join("\n")
+        .to_embed(title, tf(language, "bulk.footer", &[("minutes", &CONFIRM_MINUTES)]));
+
+    Ok(Reply::embed(embed)
+        .components(vec![confirm_buttons(id, action, language)])
+        .ephemeral(true))
+}
+
+/// Shows the new tag or rating of an operation
+fn describe_value(value: Option<&str>, language: &str) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => t(language, "bulk.no_tag"),
+    }
+}
+
+/// Makes the confirm and cancel buttons of a preview
+fn confirm_buttons(id: i64, action: BulkAction, language: &str) -> CreateActionRow {
+    let style = match action {
+        BulkAction::Remove => ButtonStyle::Danger,
+        BulkAction::Retag | BulkAction::Rerate => ButtonStyle::Primary,
+    };
+
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{BULK_PREFIX}confirm.{id}"))
+            .label(t(language, "button.confirm"))
+            .style(style),
+        CreateButton::new(format!("{BULK_PREFIX}cancel.{id}"))
+            .label(t(language, "button.cancel"))
+            .style(ButtonStyle::Secondary),
+    ])
+}
+
+/// Checks if a component id is a confirm or cancel button of a bulk operation
+pub fn is_bulk_id(id: &str) -> bool {
+    id.starts_with(BULK_PREFIX)
+}
+
+/// Runs or drops the operation a confirm or cancel button points to, replacing the preview with the result
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to access the database
+/// * `interaction: &str` - The id of the button, like `bulk.confirm.12`
+/// * `member: Member<'_>` - Member who pressed the button
+/// * `language: &str` - Language to answer in
+pub async fn bulk_button(
+    bot: &Bot,
+    interaction: &str,
+    member: Member<'_>,
+    language: &str,
+) -> Result<CreateInteractionResponse> {
+    let guild_id = member.guild_id.ok_or(Error::GuildOnly)?;
+
+    let Some((button, id)) = interaction
+        .strip_prefix(BULK_PREFIX)
+        .and_then(|rest| rest.split_once('.'))
+        .and_then(|(button, id)| Some((button, id.parse::<i64>().ok()?)))
+    else {
+        return Err(Error::InvalidInput(t(language, "common.unknown")));
+    };
+
+    let Some(operation) = bot.get_bulk_operation(guild_id, id).await? else {
+        return Ok(Reply::text(t(language, "bulk.expired")).to_update());
+    };
+
+    // Text command previews are seen by everyone, only the member who asked can answer them
+    if operation.user_id != member.user_id {
+        return Err(Error::InvalidInput(t(language, "bulk.not_yours")));
+    }
+
+    if button == "cancel" {
+        bot.delete_bulk_operation(id).await?;
+
+        return Ok(Reply::text(t(language, "bulk.cancelled")).to_update());
+    }
+
+    // Roles may have changed since the preview
+    bot.require(member, operation.action.capability()).await?;
+
+    let Some(changed) = bot.run_bulk_operation(&operation, member.user_id).await? else {
+        return Ok(Reply::text(t(language, "bulk.expired")).to_update());
+    };
+
+    // The uids are kept so removed questions can be found in the trash again
+    let mut entry = AuditEntry::new(guild_id, member.user_id, operation.action.audit_action())
+        .after(Some(operation.uids.join(", ")));
+
+    if operation.action != BulkAction::Remove {
+        entry = entry.target(describe_value(operation.value.as_deref(), language));
+    }
+
+    bot.record_audit(entry).await;
+
+    Ok(Reply::text(tf(
+        language,
+        &format!("bulk.done.{}", operation.action.name()),
+        &[("count", &changed), ("value", &describe_value(operation.value.as_deref(), language))],
+    ))
+    .to_update())
+}
@@ -1,33 +1,59 @@
 use std::str::FromStr;
 
 use serenity::all::{
-    CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption,
-    CreateInteractionResponse, GuildId, Permissions,
+    CommandOptionType, CreateCommand, CreateCommandOption,
 };
 use uuid::Uuid;
 
 use crate::{
-    bot::Bot, embed::send_page, interactions::truth_or_dare, menu_type::MenuType, other_impl::MessageMaker, questions::QuestionType
+    audit::{audit_command, AuditAction, AuditEntry}, autocomplete::question_uid_option, bot::Bot, bulk::bulk_command, content_policy::{blocklist_command, review_command, APPROVED, PENDING}, config::{valid_prefix, Config, MAX_PREFIX_LENGTH}, embed::send_page, error::{Error, Result}, i18n::{language_name, t, tf, CommandLocalizer, LANGUAGES}, interactions::truth_or_dare, invocation::{Invocation, Reply}, menu_type::MenuType, packs::packs_command, pagination::{ListFilter, PageState, SortOrder}, permissions::{permissions_command, Capability, Member}, qotd::qotd_command, question_form::question_form_command, questions::{normalize_tag, Question, QuestionType, MAX_TAG_LENGTH}, rate_limit::set_rate_limit_command, search::search_questions_command, submission::{validate_submission, Submission}, submission_limits::submissions_command, trash::{restore_question_command, trash_command, undo_button}
 };
 
 /// Creates a vector of commands for the bot
-pub fn create_commands() -> Vec<CreateCommand> {
-    vec![
+/// Commands of features turned off in the config are left out
+pub fn create_commands(config: &Config) -> Vec<CreateCommand> {
+    let mut commands = vec![
         set_rating_command(),
         add_question_command(),
         remove_question_command(),
+        restore_question_command(),
+        trash_command(),
+        bulk_command(),
+        edit_question_command(),
+        question_form_command(),
         list_questions_command(),
         list_custom_questions_command(),
+        my_questions_command(),
         set_question_permissions_command(),
+        set_language_command(),
+        blocklist_command(),
+        review_command(),
+        permissions_command(),
+        audit_command(),
+        submissions_command(),
+        packs_command(),
+        search_questions_command(),
         truth_command(),
-        dare_command()
-    ]
+        dare_command(),
+    ];
+
+    if config.features.text_commands {
+        commands.push(set_prefix_command());
+    }
+
+    if config.features.qotd {
+        commands.push(qotd_command());
+    }
+
+    commands.push(set_rate_limit_command());
+
+    commands
 }
 
 /// Command to set the rating limit for questions
 fn set_rating_command() -> CreateCommand {
     CreateCommand::new("set_rating")
-        .description("Set the question rating limit (PG or PG-13)")
+        .localized("set_rating")
         .add_option(
             // Option with a PG and PG-13 choice
             CreateCommandOption::new(
@@ -40,39 +66,32 @@ fn set_rating_command() -> CreateCommand {
             .add_string_choice("PG-13", "PG-13")
             .add_string_choice("PG & PG-13", "ALL"),
         )
-        // Only allow users with the Administrator permission to use this command
-        .default_member_permissions(Permissions::ADMINISTRATOR)
 }
 
-pub async fn set_rating(bot: &Bot, command: &CommandInteraction) -> CreateInteractionResponse {
+pub async fn set_rating(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+
     // Get the rating from the command
-    let rating = command
-        .data
-        .options
-        .iter()
-        .find(|option| option.name == "rating")
-        .and_then(|option| option.value.as_str())
-        .unwrap_or("PG");
-
-    if let Some(guild_id) = command.guild_id {
-        let guild_id_i64 = guild_id.get() as i64;
-
-        if let Err(err) = bot.set_guild_rating(guild_id_i64, rating).await {
-            if command.user.id == 741999030623535168 {
-                println!("{}", err);
-            }
-            
-            return "Failed to set rating.".to_interaction_message();
-        }
+    let rating = invocation.str_option("rating").unwrap_or("PG");
+
+    let before = bot.get_guild_rating(Some(guild_id)).await?;
+    bot.set_guild_rating(guild_id, rating).await?;
+
+    if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::SetRating) {
+        bot.record_audit(entry.before(Some(before)).after(Some(rating))).await;
     }
 
-    format!("Rating set to {}.", rating).to_interaction_message()
+    Ok(Reply::text(tf(language, "rating.set", &[("rating", &rating)])))
 }
 
 /// Command to add a question to the database
 fn add_question_command() -> CreateCommand {
     CreateCommand::new("add_question")
-        .description("Add a question to the database")
+        .localized("add_question")
         // Prompt to ask
         .add_option(
             CreateCommandOption::new(CommandOptionType::String, "question", "The question to add")
@@ -100,131 +119,482 @@ fn add_question_command() -> CreateCommand {
             .add_string_choice("PG", "PG")
             .add_string_choice("PG-13", "PG-13"),
         )
+        // Language of the question, defaults to the server's language
+        .add_option(language_option("The language the question is written in"))
+        // Tag to group the question under
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "tag", "A tag to group the question under, like party")
+                .max_length(MAX_TAG_LENGTH),
+        )
 }
 
-pub async fn add_question(bot: &Bot, command: &CommandInteraction) -> CreateInteractionResponse {
-    // Check if the user is an admin in the guild using the permissions field on the command
-    let is_admin = command
-        .member
-        .as_ref()
-        .and_then(|member| member.permissions)
-        .map(|perms| perms.administrator())
-        .unwrap_or(false);
-
-    if is_admin || !bot.get_guild_question_permissions(command.guild_id).await {
-        let get_option = |name| {
-            command
-                .data
-                .options
-                .iter()
-                .find(|o| o.name == name)
-                .and_then(|o| o.value.as_str())
-        };
-        // Sanitize input to remove potentially dangerous characters
-        let question = get_option("question").unwrap_or("");
-
-        let question_type = get_option("question_type")
+pub async fn add_question(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let get_option = |name| invocation.str_option(name);
+
+    let question = NewQuestion {
+        uid: Uuid::new_v4().to_string(),
+        prompt: get_option("question").unwrap_or("").to_string(),
+        question_type: get_option("question_type")
             .and_then(|s| QuestionType::from_str(s.to_uppercase().as_str()).ok())
-            .unwrap_or(QuestionType::NONE);
-        let rating = get_option("rating").unwrap_or("PG");
-
-        if question.is_empty() {
-            "Question cannot be empty.".to_interaction_message()
-        } else {
-            let uid = Uuid::new_v4().to_string();
-
-            sqlx::query(
-                        r#"INSERT INTO questions (prompt, question_type, rating, guild_id, uid) VALUES (?1, ?2, ?3, ?4, ?5)"#,
-                    )
-                    .bind(&question)
-                    .bind(question_type.to_string())
-                    .bind(rating)
-                    .bind(command.guild_id.unwrap().get() as i64)
-                    .bind(uid)
-                    .execute(&bot.database)
-                    .await
-                    .ok();
-
-            format!("Question added: {}", question).to_interaction_message()
-        }
-    } else {
-        "You must be an admin to run this command".to_interaction_message()
+            .unwrap_or(QuestionType::NONE),
+        rating: get_option("rating").unwrap_or("PG").to_string(),
+        language: get_option("language").unwrap_or(language).to_string(),
+        tag: get_option("tag").and_then(normalize_tag),
+    };
+
+    Ok(Reply::text(save_new_question(bot, Member::from(invocation), &question, language).await?))
+}
+
+/// A question a member is adding, before it is checked
+#[derive(Debug, Clone)]
+pub struct NewQuestion {
+    pub uid: String,
+    pub prompt: String,
+    pub question_type: QuestionType,
+    pub rating: String,
+    pub language: String,
+    pub tag: Option<String>,
+}
+
+/// Checks a member can add a question and runs it through the same checks as every new question
+///
+/// # Returns
+/// * `Submission` - The checked prompt, and whether it has to be reviewed
+pub async fn check_new_question(
+    bot: &Bot,
+    member: Member<'_>,
+    question: &NewQuestion,
+    language: &str,
+) -> Result<Submission> {
+    let guild_id = member.guild_id.ok_or(Error::GuildOnly)?;
+
+    // Banned members and members over their quota are turned away before anything is checked
+    bot.check_submitter(member, 1, language).await?;
+
+    // Runs the content policy and duplicate checks, and strips mentions and links
+    validate_submission(bot, guild_id, &question.prompt, &question.rating, None, language).await
+}
+
+/// Checks and adds a question to the guild of a member
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to access the database
+/// * `member: Member<'_>` - Member adding the question, recorded as its author
+/// * `question: &NewQuestion` - The question as it was written
+/// * `language: &str` - Language to answer in
+///
+/// # Returns
+/// * `String` - The message telling the member the question was added or is waiting for review
+pub async fn save_new_question(
+    bot: &Bot,
+    member: Member<'_>,
+    question: &NewQuestion,
+    language: &str,
+) -> Result<String> {
+    let guild_id = member.guild_id.ok_or(Error::GuildOnly)?;
+
+    let submission = check_new_question(bot, member, question, language).await?;
+    let status = if submission.needs_review { PENDING } else { APPROVED };
+
+    sqlx::query(
+        r#"INSERT INTO questions (prompt, question_type, rating, guild_id, uid, language, status, author_id, tag, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, CURRENT_TIMESTAMP)"#,
+    )
+    .bind(&submission.prompt)
+    .bind(question.question_type.to_string())
+    .bind(&question.rating)
+    .bind(guild_id.get() as i64)
+    .bind(&question.uid)
+    .bind(&question.language)
+    .bind(status)
+    .bind(member.user_id.get() as i64)
+    .bind(&question.tag)
+    .execute(&bot.database)
+    .await?;
+
+    bot.record_audit(
+        AuditEntry::new(guild_id, member.user_id, AuditAction::AddQuestion)
+            .target(&question.uid)
+            .after(Some(&submission.prompt)),
+    )
+    .await;
+
+    let key = if submission.needs_review { "add_question.pending" } else { "add_question.added" };
+    let mut reply = tf(language, key, &[("question", &submission.prompt)]);
+
+    // Close matches are still added, but the member is told about them
+    if let Some(warning) = submission.warning(language) {
+        reply = format!("{reply}\n{warning}");
     }
+
+    Ok(reply)
 }
 
 fn remove_question_command() -> CreateCommand {
     CreateCommand::new("remove_question")
-        .description("Remove a question from the database")
+        .localized("remove_question")
+        .add_option(question_uid_option("The question to be removed"))
+}
+
+pub async fn remove_question(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+
+    let question_uid = invocation.str_option("question_uid").unwrap_or("");
+
+    let Some(question) = bot.get_guild_question(guild_id, question_uid).await? else {
+        return Err(Error::QuestionNotFound);
+    };
+
+    // Authors can remove their own questions without the remove capability
+    bot.require_for_question(Member::from(invocation), Capability::Remove, &question).await?;
+
+    // Moved to the trash, so a wrong uid can still be undone
+    if !bot.trash_question(guild_id, question_uid, invocation.user_id).await? {
+        return Err(Error::QuestionNotFound);
+    }
+
+    if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::RemoveQuestion) {
+        bot.record_audit(entry.target(question_uid).before(Some(question.prompt))).await;
+    }
+
+    Ok(Reply::text(tf(
+        language,
+        "remove_question.removed",
+        &[("uid", &question_uid), ("days", &bot.config.trash_retention_days)],
+    ))
+    .components(vec![undo_button(question_uid, language)]))
+}
+
+fn edit_question_command() -> CreateCommand {
+    CreateCommand::new("edit_question")
+        .localized("edit_question")
+        .add_option(question_uid_option("The question to edit"))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "question",
+            "The new question, leave empty to keep it",
+        ))
         .add_option(
             CreateCommandOption::new(
                 CommandOptionType::String,
-                "question_uid",
-                "The question to be removed",
+                "question_type",
+                "The new type of question",
             )
-            .required(true),
+            .add_string_choice("Truth", "TRUTH")
+            .add_string_choice("Dare", "DARE"),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "rating",
+                "The new rating of the question",
+            )
+            .add_string_choice("PG", "PG")
+            .add_string_choice("PG-13", "PG-13"),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "tag", "The new tag, - to remove it")
+                .max_length(MAX_TAG_LENGTH),
         )
 }
 
-pub async fn remove_question(bot: &Bot, command: &CommandInteraction) -> CreateInteractionResponse {
-    let guild_id = command.guild_id;
-
-    let question_uid = command
-        .data
-        .options
-        .iter()
-        .find(|o| o.name == "question_uid")
-        .and_then(|o| o.value.as_str())
-        .unwrap()
-        .to_string();
-
-    let query = r#"DELETE FROM questions WHERE guild_id = ?1 AND uid = ?2"#;
-
-    match bot.check_question_guild(guild_id, &question_uid).await {
-        true => {
-            match sqlx::query(query)
-                .bind(guild_id.unwrap().get() as i64)
-                .bind(&question_uid)
-                .execute(&bot.database)
-                .await
-            {
-                Ok(_) => format!("Question with uid: {} has been removed.", question_uid)
-                    .to_interaction_message(),
-                Err(e) => e.to_string().to_interaction_message(),
-            }
-        }
-        false => "You can't remove a question outside of your server!".to_interaction_message(),
+pub async fn edit_question(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let question_uid = invocation.str_option("question_uid").unwrap_or("");
+
+    let edit = QuestionEdit {
+        prompt: invocation.str_option("question").map(str::to_string),
+        question_type: invocation
+            .str_option("question_type")
+            .and_then(|s| QuestionType::from_str(s).ok()),
+        rating: invocation.str_option("rating").map(str::to_string),
+        tag: invocation.str_option("tag").map(normalize_tag),
+    };
+
+    Ok(Reply::text(save_question_edit(bot, Member::from(invocation), question_uid, &edit, language).await?))
+}
+
+/// Changes a member is making to a question, fields that are `None` are kept
+#[derive(Debug, Clone, Default)]
+pub struct QuestionEdit {
+    pub prompt: Option<String>,
+    pub question_type: Option<QuestionType>,
+    pub rating: Option<String>,
+    /// `Some(None)` removes the tag
+    pub tag: Option<Option<String>>,
+}
+
+/// Checks a member can change a question and runs the changed question through the same checks as a new one
+///
+/// # Returns
+/// * `(Question, Question, Submission)` - The question before and after the changes, and the checked prompt
+pub async fn check_question_edit(
+    bot: &Bot,
+    member: Member<'_>,
+    question_uid: &str,
+    edit: &QuestionEdit,
+    language: &str,
+) -> Result<(Question, Question, Submission)> {
+    let guild_id = member.guild_id.ok_or(Error::GuildOnly)?;
+
+    let Some(question) = bot.get_guild_question(guild_id, question_uid).await? else {
+        return Err(Error::QuestionNotFound);
+    };
+
+    // Authors can edit their own questions without the edit capability
+    bot.require_for_question(member, Capability::Edit, &question).await?;
+
+    // Members banned from adding questions can't rewrite the ones they already added either
+    if question.author() == Some(member.user_id) {
+        bot.require_not_banned(guild_id, member.user_id, language).await?;
+    }
+
+    if edit.prompt.is_none() && edit.question_type.is_none() && edit.rating.is_none() && edit.tag.is_none() {
+        return Err(Error::InvalidInput(t(language, "edit_question.nothing")));
     }
+
+    let rating = edit.rating.clone().unwrap_or_else(|| question.rating.clone());
+
+    // The edited question goes through the same checks as a new one, without matching itself
+    let submission = validate_submission(
+        bot,
+        guild_id,
+        edit.prompt.as_deref().unwrap_or(&question.prompt),
+        &rating,
+        Some(question_uid),
+        language,
+    )
+    .await?;
+
+    let edited = Question {
+        prompt: submission.prompt.clone(),
+        question_type: edit.question_type.unwrap_or(question.question_type),
+        rating,
+        status: if submission.needs_review { PENDING.to_string() } else { question.status.clone() },
+        tag: edit.tag.clone().unwrap_or_else(|| question.tag.clone()),
+        ..question.clone()
+    };
+
+    Ok((question, edited, submission))
+}
+
+/// Checks and saves changes a member made to a question of their guild
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to access the database
+/// * `member: Member<'_>` - Member changing the question
+/// * `question_uid: &str` - The question to change
+/// * `edit: &QuestionEdit` - The changes, at least one has to be set
+/// * `language: &str` - Language to answer in
+///
+/// # Returns
+/// * `String` - The message telling the member the question was changed or is waiting for review
+pub async fn save_question_edit(
+    bot: &Bot,
+    member: Member<'_>,
+    question_uid: &str,
+    edit: &QuestionEdit,
+    language: &str,
+) -> Result<String> {
+    let guild_id = member.guild_id.ok_or(Error::GuildOnly)?;
+
+    let (question, edited, submission) = check_question_edit(bot, member, question_uid, edit, language).await?;
+
+    sqlx::query(
+        r#"UPDATE questions SET prompt = ?1, question_type = ?2, rating = ?3, status = ?4, tag = ?5 WHERE guild_id = ?6 AND uid = ?7"#,
+    )
+    .bind(&edited.prompt)
+    .bind(edited.question_type.to_string())
+    .bind(&edited.rating)
+    .bind(&edited.status)
+    .bind(&edited.tag)
+    .bind(guild_id.get() as i64)
+    .bind(question_uid)
+    .execute(&bot.database)
+    .await?;
+
+    let describe = |question: &Question| match &question.tag {
+        Some(tag) => format!("{} ({} - {}) #{tag}", question.prompt, question.question_type, question.rating),
+        None => format!("{} ({} - {})", question.prompt, question.question_type, question.rating),
+    };
+
+    bot.record_audit(
+        AuditEntry::new(guild_id, member.user_id, AuditAction::EditQuestion)
+            .target(question_uid)
+            .before(Some(describe(&question)))
+            .after(Some(describe(&edited))),
+    )
+    .await;
+
+    let key = if edited.status == PENDING { "edit_question.pending" } else { "edit_question.edited" };
+    let mut reply = tf(language, key, &[("question", &edited.prompt)]);
+
+    if let Some(warning) = submission.warning(language) {
+        reply = format!("{reply}\n{warning}");
+    }
+
+    Ok(reply)
 }
 
 fn list_questions_command() -> CreateCommand {
-    CreateCommand::new("list_questions")
-        .description("List all default questions and questions added by users in this server")
+    add_list_options(
+        CreateCommand::new("list_questions")
+            .localized("list_questions")
+    )
 }
 
-pub async fn list_questions(bot: &Bot, guild_id: Option<GuildId>) -> CreateInteractionResponse {
-    let questions = bot.get_questions(guild_id).await;
+pub async fn list_questions(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let state = PageState::new(
+        MenuType::DEFAULT,
+        list_filter(invocation),
+        list_sort(invocation).unwrap_or(SortOrder::Oldest),
+    );
+
+    // Moderators also see who added each question
+    let show_authors = bot.require(Member::from(invocation), Capability::Moderate).await.is_ok();
 
     // Send the response
-    send_page(1, questions, MenuType::DEFAULT).await
+    Ok(send_page(bot, invocation.guild_id, state, show_authors, language)
+        .await?
+        .ephemeral(list_ephemeral(invocation)))
 }
 
 fn list_custom_questions_command() -> CreateCommand {
-    CreateCommand::new("list_custom_questions")
-        .description("List all questions added by users in this server")
+    add_list_options(
+        CreateCommand::new("list_custom_questions")
+            .localized("list_custom_questions")
+    )
 }
 
 pub async fn list_custom_questions(
     bot: &Bot,
-    guild_id: Option<GuildId>,
-) -> CreateInteractionResponse {
-    let questions = bot.get_custom_questions(guild_id).await;
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let state = PageState::new(
+        MenuType::CUSTOM,
+        ListFilter {
+            custom_only: true,
+            ..list_filter(invocation)
+        },
+        list_sort(invocation).unwrap_or(SortOrder::Oldest),
+    );
+
+    // Moderators also see who added each question
+    let show_authors = bot.require(Member::from(invocation), Capability::Moderate).await.is_ok();
+
+    Ok(send_page(bot, invocation.guild_id, state, show_authors, language)
+        .await?
+        .ephemeral(list_ephemeral(invocation)))
+}
+
+fn my_questions_command() -> CreateCommand {
+    add_list_options(
+        CreateCommand::new("my_questions")
+            .localized("my_questions")
+    )
+}
+
+/// Lists the questions the member added, including ones still waiting for review
+pub async fn my_questions(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let state = PageState::new(
+        MenuType::MINE,
+        ListFilter {
+            custom_only: true,
+            author_id: Some(invocation.user_id),
+            ..list_filter(invocation)
+        },
+        list_sort(invocation).unwrap_or(SortOrder::Newest),
+    );
+
+    // Only the member's own questions are listed, so the list is private unless they ask otherwise
+    Ok(send_page(bot, invocation.guild_id, state, false, language)
+        .await?
+        .ephemeral(invocation.bool_option("ephemeral").unwrap_or(true)))
+}
+
+/// Adds the type, rating and sort options shared by every question list
+pub fn add_list_options(command: CreateCommand) -> CreateCommand {
+    command
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "type",
+                "Only show this type of question",
+            )
+            .add_string_choice("Truth", "TRUTH")
+            .add_string_choice("Dare", "DARE"),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "rating",
+                "Only show questions with this rating",
+            )
+            .add_string_choice("PG", "PG")
+            .add_string_choice("PG-13", "PG-13"),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "sort",
+                "The order to list the questions in",
+            )
+            .add_string_choice("Oldest first", "oldest")
+            .add_string_choice("Newest first", "newest")
+            .add_string_choice("Alphabetical", "alphabetical"),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "ephemeral",
+            "Only show the list to you",
+        ))
+}
+
+/// Gets the type and rating filters from a list command
+pub fn list_filter(invocation: &Invocation) -> ListFilter {
+    let get_option = |name| invocation.str_option(name);
+
+    ListFilter {
+        question_type: get_option("type").and_then(|s| QuestionType::from_str(s).ok()),
+        rating: get_option("rating").map(|s| s.to_string()),
+        ..ListFilter::default()
+    }
+}
+
+/// Gets if a list command should only be shown to the member who ran it
+pub fn list_ephemeral(invocation: &Invocation) -> bool {
+    invocation.bool_option("ephemeral").unwrap_or(false)
+}
 
-    send_page(1, questions, MenuType::CUSTOM).await
+/// Gets the sort order from a list command, if one was picked
+pub fn list_sort(invocation: &Invocation) -> Option<SortOrder> {
+    invocation
+        .str_option("sort")
+        .and_then(|s| SortOrder::from_str(s).ok())
 }
 
 fn set_question_permissions_command() -> CreateCommand {
     CreateCommand::new("set_question_permissions")
-        .description("Set if only admins should be able to add questions")
+        .localized("set_question_permissions")
         .add_option(
             CreateCommandOption::new(
                 CommandOptionType::String,
@@ -239,41 +609,137 @@ fn set_question_permissions_command() -> CreateCommand {
 
 pub async fn set_question_permissions(
     bot: &Bot,
-    command: &CommandInteraction,
-) -> CreateInteractionResponse {
-    let guild_id = command.guild_id;
-
-    let admin = command
-        .data
-        .options
-        .iter()
-        .find(|c| c.name == "admin")
-        .unwrap()
-        .value
-        .as_str()
-        .unwrap()
-        == "true";
-
-    match bot.set_guild_question_permissions(guild_id, admin).await {
-        Ok(_) => format!("Admin only set to {admin}").to_interaction_message(),
-        Err(_) => "Error setting permissions".to_interaction_message(),
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+
+    let admin = invocation.str_option("admin") == Some("true");
+
+    let before = bot.get_guild_question_permissions(Some(guild_id)).await?;
+    bot.set_guild_question_permissions(guild_id, admin).await?;
+
+    if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::SetQuestionPermissions) {
+        bot.record_audit(entry.before(Some(before)).after(Some(admin))).await;
     }
+
+    Ok(Reply::text(tf(language, "permissions.set", &[("admin", &admin)])))
+}
+
+fn set_language_command() -> CreateCommand {
+    CreateCommand::new("set_language")
+        .localized("set_language")
+        .add_option(
+            language_option("The language to use, or automatic to follow each member's Discord language")
+                .required(true)
+                .add_string_choice("Automatic", "auto"),
+        )
+}
+
+pub async fn set_language(
+    bot: &Bot,
+    invocation: &Invocation,
+    _language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+
+    // Automatic clears the setting so the member's locale is used
+    let new_language = invocation
+        .str_option("language")
+        .and_then(|code| LANGUAGES.iter().map(|(code, _)| *code).find(|c| *c == code));
+
+    let before = bot.get_guild_settings(guild_id).await?.language;
+    bot.set_guild_language(guild_id, new_language).await?;
+
+    if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::SetLanguage) {
+        let describe = |language: Option<&str>| language.unwrap_or("auto").to_string();
+        bot.record_audit(entry.before(Some(describe(before.as_deref()))).after(Some(describe(new_language)))).await;
+    }
+
+    // Reply in the language that was just set
+    let language = bot.get_language(Some(guild_id), &invocation.locale).await?;
+
+    Ok(match new_language {
+        Some(new_language) => Reply::text(tf(language, "language.set", &[("language", &language_name(new_language))])),
+        None => Reply::text(t(language, "language.auto")),
+    })
+}
+
+fn set_prefix_command() -> CreateCommand {
+    CreateCommand::new("set_prefix")
+        .localized("set_prefix")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "prefix",
+                "The prefix for text commands like !truth, leave empty to use the default",
+            )
+            .max_length(MAX_PREFIX_LENGTH as u16),
+        )
+}
+
+pub async fn set_prefix(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+
+    // No prefix goes back to the default one
+    let prefix = invocation.str_option("prefix").map(str::trim);
+
+    if let Some(prefix) = prefix
+        && !valid_prefix(prefix)
+    {
+        return Err(Error::InvalidInput(tf(
+            language,
+            "prefix.invalid",
+            &[("prefix", &prefix), ("max", &MAX_PREFIX_LENGTH)],
+        )));
+    }
+
+    let before = bot.get_guild_prefix(Some(guild_id)).await?;
+    bot.set_guild_prefix(guild_id, prefix).await?;
+
+    let prefix = bot.get_guild_prefix(Some(guild_id)).await?;
+
+    if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::SetPrefix) {
+        bot.record_audit(entry.before(Some(before)).after(Some(&prefix))).await;
+    }
+
+    Ok(Reply::text(tf(language, "prefix.set", &[("prefix", &prefix)])))
+}
+
+/// Makes a string option with a choice for every supported language
+fn language_option(description: &str) -> CreateCommandOption {
+    LANGUAGES.iter().fold(
+        CreateCommandOption::new(CommandOptionType::String, "language", description),
+        |option, (code, name)| option.add_string_choice(*name, *code),
+    )
 }
 
 fn truth_command() -> CreateCommand {
     CreateCommand::new("truth")
-        .description("Sends a truth question")
+        .localized("truth")
 }
 
-pub async fn truth(bot: &Bot, command: &CommandInteraction) -> CreateInteractionResponse {
-    truth_or_dare(bot, "truth", command.guild_id).await
+pub async fn truth(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    truth_or_dare(bot, "truth", invocation.guild_id, language).await
 }
 
 fn dare_command() -> CreateCommand {
     CreateCommand::new("dare")
-        .description("Sends a dare question")
+        .localized("dare")
 }
 
-pub async fn dare(bot: &Bot, command: &CommandInteraction) -> CreateInteractionResponse {
-    truth_or_dare(bot, "dare", command.guild_id).await
+pub async fn dare(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    truth_or_dare(bot, "dare", invocation.guild_id, language).await
 }
\ No newline at end of file
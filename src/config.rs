@@ -0,0 +1,481 @@
+//! Config module for the bot
+//! Loads the settings from a TOML file, then lets environment variables override them
+//!
+//! The file is `config.toml` unless `CONFIG_PATH` points somewhere else, and it is optional.
+//! Every setting has an environment variable, e.g. `database_url` is `DATABASE_URL` and
+//! `features.qotd` is `FEATURE_QOTD`. See `config.example.toml` for every setting.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serenity::all::{GuildId, UserId};
+
+use crate::content_policy::ContentPolicy;
+use crate::rate_limit::{Limit, RateLimits};
+use crate::registration::Scope;
+
+/// File read when `CONFIG_PATH` isn't set
+const DEFAULT_PATH: &str = "config.toml";
+
+/// Most questions a list page can show, long questions are shortened so the page fits in its embed
+const MAX_PAGE_SIZE: i64 = 25;
+
+/// Longest question allowed, lists shorten questions when they get too long for a message
+const MAX_QUESTION_LENGTH: usize = 1000;
+
+/// Shortest signing secret allowed, so ids can't be forged by guessing it
+const MIN_SIGNING_SECRET_LENGTH: usize = 16;
+
+/// Longest text command prefix allowed
+pub const MAX_PREFIX_LENGTH: usize = 5;
+
+/// Every setting of the bot
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// SQLite database to use, created if it doesn't exist
+    pub database_url: String,
+    /// Most connections the database pool opens
+    pub pool_size: u32,
+    /// Rating used in guilds that haven't set one: PG, PG-13 or ALL
+    pub default_rating: String,
+    /// Questions shown on each page of a list
+    pub page_size: i64,
+    /// Prefix of text commands like `!truth`, guilds can change theirs with `/set_prefix`
+    pub prefix: String,
+    /// Users who are shown the details of errors
+    pub owner_ids: Vec<u64>,
+    /// Queries slower than this many milliseconds are logged as warnings
+    pub slow_query_ms: u64,
+    /// Address to serve Prometheus metrics on, off when unset
+    pub metrics_addr: Option<SocketAddr>,
+    /// Address to serve the health check on, off when unset
+    pub health_addr: Option<SocketAddr>,
+    /// Guild to register commands in instead of globally, for development
+    pub dev_guild_id: Option<u64>,
+    /// Secret button ids are signed with, derived from the bot token when unset
+    pub signing_secret: Option<String>,
+    /// How alike a new question and an existing one can be, from 0 to 1, before a warning is shown
+    /// Questions that only differ in case, spacing or punctuation are always rejected
+    pub duplicate_threshold: f64,
+    /// Questions each member can add in 24 hours, 0 for no limit, guilds can change theirs with `/submissions quota`
+    pub daily_submission_quota: u32,
+    /// Days removed questions stay in the trash, where they can be restored, before they are deleted for good
+    pub trash_retention_days: u32,
+    /// Delete registered commands the bot doesn't have anymore, set by `--purge-stale-commands`
+    #[serde(skip)]
+    pub purge_stale_commands: bool,
+    pub features: Features,
+    /// Default rate limits, guilds can change theirs with `/set_rate_limit`
+    pub rate_limits: RateLimits,
+    /// Checks run on submitted questions
+    pub content_policy: ContentPolicy,
+}
+
+/// Parts of the bot that can be turned off
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    /// Answer text commands like `!truth`, needs the message content intent
+    pub text_commands: bool,
+    /// Register `/qotd` and post the question of the day
+    pub qotd: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            database_url: "sqlite://database.sqlite".to_string(),
+            pool_size: 5,
+            default_rating: "PG".to_string(),
+            page_size: 10,
+            prefix: "!".to_string(),
+            owner_ids: Vec::new(),
+            slow_query_ms: 250,
+            metrics_addr: None,
+            health_addr: None,
+            dev_guild_id: None,
+            signing_secret: None,
+            duplicate_threshold: 0.85,
+            daily_submission_quota: 20,
+            trash_retention_days: 30,
+            purge_stale_commands: false,
+            features: Features::default(),
+            rate_limits: RateLimits::default(),
+            content_policy: ContentPolicy::default(),
+        }
+    }
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features {
+            text_commands: true,
+            qotd: true,
+        }
+    }
+}
+
+/// Reasons the config can't be loaded
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file exists but couldn't be read
+    Read(String, std::io::Error),
+    /// The config file isn't valid TOML or has unknown settings
+    Parse(String, toml::de::Error),
+    /// An environment variable has a value that can't be used, (name, value, expected)
+    Env(&'static str, String, &'static str),
+    /// A setting has a value that can't be used, (setting, reason)
+    Invalid(&'static str, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "couldn't read {path}: {e}"),
+            ConfigError::Parse(path, e) => write!(f, "invalid config file {path}: {e}"),
+            ConfigError::Env(name, value, expected) => {
+                write!(f, "{name}={value:?} is invalid, expected {expected}")
+            }
+            ConfigError::Invalid(setting, reason) => write!(f, "invalid {setting}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Loads the config file and environment overrides, then validates the result
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = env::var("CONFIG_PATH").unwrap_or_else(|_| DEFAULT_PATH.to_string());
+
+        let mut config = match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| ConfigError::Parse(path.clone(), e))?,
+            // Only a missing default file is fine, a missing file that was asked for isn't
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && env::var("CONFIG_PATH").is_err() => {
+                Config::default()
+            }
+            Err(e) => return Err(ConfigError::Read(path, e)),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Overrides settings with the environment variables that are set
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(url) = env_var("DATABASE_URL") {
+            self.database_url = url;
+        }
+        if let Some(size) = env_parse("DATABASE_POOL_SIZE", "a number")? {
+            self.pool_size = size;
+        }
+        if let Some(rating) = env_var("DEFAULT_RATING") {
+            self.default_rating = rating;
+        }
+        if let Some(size) = env_parse("PAGE_SIZE", "a number")? {
+            self.page_size = size;
+        }
+        if let Some(prefix) = env_var("COMMAND_PREFIX") {
+            self.prefix = prefix;
+        }
+        if let Some(ids) = env_var("OWNER_IDS") {
+            self.owner_ids = ids
+                .split(',')
+                .map(|id| id.trim())
+                .filter(|id| !id.is_empty())
+                .map(|id| {
+                    id.parse::<u64>().map_err(|_| {
+                        ConfigError::Env("OWNER_IDS", ids.clone(), "user ids separated by commas")
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(ms) = env_parse("SLOW_QUERY_MS", "a number of milliseconds")? {
+            self.slow_query_ms = ms;
+        }
+        if let Some(addr) = env_parse("METRICS_ADDR", "an address like 127.0.0.1:9100")? {
+            self.metrics_addr = Some(addr);
+        }
+        if let Some(addr) = env_parse("HEALTH_ADDR", "an address like 127.0.0.1:9100")? {
+            self.health_addr = Some(addr);
+        }
+        if let Some(id) = env_parse("DEV_GUILD_ID", "a guild id")? {
+            self.dev_guild_id = Some(id);
+        }
+        if let Some(secret) = env_var("SIGNING_SECRET") {
+            self.signing_secret = Some(secret);
+        }
+        if let Some(threshold) = env_parse("DUPLICATE_THRESHOLD", "a number from 0 to 1")? {
+            self.duplicate_threshold = threshold;
+        }
+        if let Some(quota) = env_parse("DAILY_SUBMISSION_QUOTA", "a number of questions")? {
+            self.daily_submission_quota = quota;
+        }
+        if let Some(days) = env_parse("TRASH_RETENTION_DAYS", "a number of days")? {
+            self.trash_retention_days = days;
+        }
+        if let Some(enabled) = env_parse("FEATURE_TEXT_COMMANDS", "true or false")? {
+            self.features.text_commands = enabled;
+        }
+        if let Some(enabled) = env_parse("FEATURE_QOTD", "true or false")? {
+            self.features.qotd = enabled;
+        }
+        if let Some(enabled) = env_parse("RATE_LIMITS_ENABLED", "true or false")? {
+            self.rate_limits.enabled = enabled;
+        }
+        if let Some(limit) = env_parse::<Limit>("RATE_LIMIT_USER", "requests/seconds like 5/10")? {
+            self.rate_limits.user = limit;
+        }
+        if let Some(limit) = env_parse::<Limit>("RATE_LIMIT_CHANNEL", "requests/seconds like 5/10")? {
+            self.rate_limits.channel = limit;
+        }
+        if let Some(limit) = env_parse::<Limit>("RATE_LIMIT_GUILD", "requests/seconds like 5/10")? {
+            self.rate_limits.guild = limit;
+        }
+        if let Some(length) = env_parse("CONTENT_MIN_LENGTH", "a number of characters")? {
+            self.content_policy.min_length = length;
+        }
+        if let Some(length) = env_parse("CONTENT_MAX_LENGTH", "a number of characters")? {
+            self.content_policy.max_length = length;
+        }
+        if let Some(enabled) = env_parse("CONTENT_STRIP_MENTIONS", "true or false")? {
+            self.content_policy.strip_mentions = enabled;
+        }
+        if let Some(enabled) = env_parse("CONTENT_STRIP_LINKS", "true or false")? {
+            self.content_policy.strip_links = enabled;
+        }
+        if let Some(enabled) = env_parse("CONTENT_REVIEW_RATINGS", "true or false")? {
+            self.content_policy.review_ratings = enabled;
+        }
+
+        Ok(())
+    }
+
+    /// Checks every setting has a value the bot can run with
+    fn validate(&self) -> Result<(), ConfigError> {
+        if !self.database_url.starts_with("sqlite:") {
+            return Err(ConfigError::Invalid(
+                "database_url",
+                format!("{:?} isn't a sqlite url, e.g. sqlite://database.sqlite", self.database_url),
+            ));
+        }
+        if self.pool_size == 0 {
+            return Err(ConfigError::Invalid("pool_size", "must be at least 1".to_string()));
+        }
+        if !["PG", "PG-13", "ALL"].contains(&self.default_rating.as_str()) {
+            return Err(ConfigError::Invalid(
+                "default_rating",
+                format!("{:?} must be PG, PG-13 or ALL", self.default_rating),
+            ));
+        }
+        if !(1..=MAX_PAGE_SIZE).contains(&self.page_size) {
+            return Err(ConfigError::Invalid(
+                "page_size",
+                format!("{} must be between 1 and {MAX_PAGE_SIZE}", self.page_size),
+            ));
+        }
+        if !valid_prefix(&self.prefix) {
+            return Err(ConfigError::Invalid(
+                "prefix",
+                format!(
+                    "{:?} must be 1 to {MAX_PREFIX_LENGTH} characters without spaces",
+                    self.prefix
+                ),
+            ));
+        }
+        if !(self.duplicate_threshold > 0.0 && self.duplicate_threshold <= 1.0) {
+            return Err(ConfigError::Invalid(
+                "duplicate_threshold",
+                format!("{} must be above 0 and at most 1", self.duplicate_threshold),
+            ));
+        }
+        if self.content_policy.min_length == 0
+            || self.content_policy.min_length > self.content_policy.max_length
+            || self.content_policy.max_length > MAX_QUESTION_LENGTH
+        {
+            return Err(ConfigError::Invalid(
+                "content_policy",
+                format!(
+                    "min_length {} and max_length {} must be between 1 and {MAX_QUESTION_LENGTH}, with min_length the smaller",
+                    self.content_policy.min_length, self.content_policy.max_length
+                ),
+            ));
+        }
+        if self.trash_retention_days == 0 {
+            return Err(ConfigError::Invalid("trash_retention_days", "must be at least 1".to_string()));
+        }
+        if self.owner_ids.contains(&0) {
+            return Err(ConfigError::Invalid("owner_ids", "0 isn't a user id".to_string()));
+        }
+        if let Some(secret) = &self.signing_secret
+            && secret.len() < MIN_SIGNING_SECRET_LENGTH
+        {
+            return Err(ConfigError::Invalid(
+                "signing_secret",
+                format!("must be at least {MIN_SIGNING_SECRET_LENGTH} characters"),
+            ));
+        }
+        if self.dev_guild_id == Some(0) {
+            return Err(ConfigError::Invalid("dev_guild_id", "0 isn't a guild id".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Checks if a user is one of the owners
+    pub fn is_owner(&self, user_id: UserId) -> bool {
+        self.owner_ids.contains(&user_id.get())
+    }
+
+    /// Gets where slash commands are registered
+    pub fn command_scope(&self) -> Scope {
+        match self.dev_guild_id {
+            Some(id) => Scope::Guild(GuildId::new(id)),
+            None => Scope::Global,
+        }
+    }
+
+    /// Gets how long a query can take before it is logged as slow
+    pub fn slow_query_threshold(&self) -> Duration {
+        Duration::from_millis(self.slow_query_ms)
+    }
+}
+
+/// Checks a text command prefix is 1 to `MAX_PREFIX_LENGTH` characters without spaces
+pub fn valid_prefix(prefix: &str) -> bool {
+    !prefix.is_empty()
+        && prefix.chars().count() <= MAX_PREFIX_LENGTH
+        && !prefix.chars().any(char::is_whitespace)
+}
+
+/// Gets an environment variable, treating an empty one as unset
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+/// Gets and parses an environment variable
+///
+/// # Parameters
+/// * `name: &'static str` - Name of the variable
+/// * `expected: &'static str` - What the value should look like, shown if it can't be parsed
+fn env_parse<T: FromStr>(name: &'static str, expected: &'static str) -> Result<Option<T>, ConfigError> {
+    env_var(name)
+        .map(|value| {
+            value
+                .trim()
+                .parse::<T>()
+                .map_err(|_| ConfigError::Env(name, value.clone(), expected))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(config: Config) -> &'static str {
+        match config.validate() {
+            Err(ConfigError::Invalid(setting, _)) => setting,
+            other => panic!("expected an invalid setting, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn example_file_is_valid() {
+        let config: Config = toml::from_str(include_str!("../config.example.toml")).unwrap();
+
+        config.validate().unwrap();
+        assert_eq!(config.page_size, Config::default().page_size);
+        assert_eq!(config.rate_limits.user, Config::default().rate_limits.user);
+    }
+
+    #[test]
+    fn file_settings() {
+        let config: Config = toml::from_str(
+            r#"
+            page_size = 5
+            owner_ids = [42]
+
+            [features]
+            qotd = false
+
+            [rate_limits]
+            user = "2/30"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.page_size, 5);
+        assert!(config.is_owner(UserId::new(42)));
+        assert!(!config.features.qotd);
+        assert!(config.features.text_commands);
+        assert_eq!(config.rate_limits.user, "2/30".parse().unwrap());
+        // Settings the file leaves out keep their defaults
+        assert_eq!(config.prefix, "!");
+
+        assert!(toml::from_str::<Config>("page_sise = 5").is_err());
+        assert!(toml::from_str::<Config>("[rate_limits]\nuser = \"five\"").is_err());
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert_eq!(invalid(Config { page_size: 0, ..Config::default() }), "page_size");
+        assert_eq!(invalid(Config { page_size: MAX_PAGE_SIZE + 1, ..Config::default() }), "page_size");
+        assert_eq!(invalid(Config { default_rating: "R".to_string(), ..Config::default() }), "default_rating");
+        assert_eq!(invalid(Config { prefix: "! ".to_string(), ..Config::default() }), "prefix");
+        assert_eq!(invalid(Config { database_url: "postgres://db".to_string(), ..Config::default() }), "database_url");
+        assert_eq!(invalid(Config { duplicate_threshold: 0.0, ..Config::default() }), "duplicate_threshold");
+        assert_eq!(invalid(Config { signing_secret: Some("short".to_string()), ..Config::default() }), "signing_secret");
+        assert_eq!(invalid(Config { dev_guild_id: Some(0), ..Config::default() }), "dev_guild_id");
+
+        let mut config = Config::default();
+        config.content_policy.max_length = MAX_QUESTION_LENGTH + 1;
+        assert_eq!(invalid(config), "content_policy");
+    }
+
+    #[test]
+    fn environment_overrides() {
+        let names = ["CONFIG_PATH", "PAGE_SIZE", "OWNER_IDS", "FEATURE_QOTD", "RATE_LIMIT_GUILD", "COMMAND_PREFIX"];
+        let set = |name: &str, value: &str| {
+            // SAFETY: this is the only test that touches the environment
+            unsafe { env::set_var(name, value) }
+        };
+
+        set("PAGE_SIZE", "7");
+        set("OWNER_IDS", "1, 2,");
+        set("FEATURE_QOTD", "false");
+        set("RATE_LIMIT_GUILD", "0/10");
+        // Empty variables are treated as unset
+        set("COMMAND_PREFIX", " ");
+
+        let mut config = Config::default();
+        config.apply_env().unwrap();
+
+        assert_eq!(config.page_size, 7);
+        assert_eq!(config.owner_ids, vec![1, 2]);
+        assert!(!config.features.qotd);
+        assert_eq!(config.rate_limits.guild, "0/10".parse().unwrap());
+        assert_eq!(config.prefix, "!");
+
+        set("PAGE_SIZE", "seven");
+        assert!(matches!(Config::default().apply_env(), Err(ConfigError::Env("PAGE_SIZE", _, _))));
+        set("PAGE_SIZE", "7");
+
+        // A config file that was asked for has to exist
+        set("CONFIG_PATH", "/nonexistent/config.toml");
+        assert!(matches!(Config::load(), Err(ConfigError::Read(..))));
+
+        for name in names {
+            // SAFETY: see above
+            unsafe { env::remove_var(name) }
+        }
+    }
+}
@@ -0,0 +1,531 @@
+//! Content policy module for the bot
+//! Cleans up and checks the text of submitted questions: length limits, blocked words,
+//! mentions and links, and a rating check that holds PG questions with PG-13 words for review
+//!
+//! The bundled word lists are in `content/`, and servers can block more words with `/blocklist`.
+
+use std::sync::LazyLock;
+
+use serde::Deserialize;
+use serenity::all::{
+    CommandOptionType, CreateCommand, CreateCommandOption, GuildId, UserId,
+};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    autocomplete::question_uid_option,
+    bot::Bot,
+    embed::{fit_lines, MAX_MESSAGE_LENGTH},
+    error::{Error, Result},
+    i18n::{t, tf, CommandLocalizer},
+    invocation::{Invocation, Reply},
+    questions::Question,
+    submission::normalize,
+    trash::undo_button,
+};
+
+/// Status of a question that can be drawn
+pub const APPROVED: &str = "approved";
+
+/// Status of a question waiting for a moderator
+pub const PENDING: &str = "pending";
+
+/// Longest word or phrase a server can block
+const MAX_BLOCKED_WORD_LENGTH: u16 = 50;
+
+/// Most pending questions `/review list` shows at once
+const REVIEW_PAGE: i64 = 15;
+
+/// Words and phrases that are never allowed
+static BLOCKLIST: LazyLock<Vec<String>> = LazyLock::new(|| terms(include_str!("../content/blocklist.txt")));
+
+/// Words and phrases that make a question PG-13
+static PG13_TERMS: LazyLock<Vec<String>> = LazyLock::new(|| terms(include_str!("../content/pg13_terms.txt")));
+
+/// Settings of the content policy
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentPolicy {
+    /// Fewest characters a question can have
+    pub min_length: usize,
+    /// Most characters a question can have
+    pub max_length: usize,
+    /// Remove user and role mentions, and stop `@everyone` and `@here` from pinging
+    pub strip_mentions: bool,
+    /// Remove links
+    pub strip_links: bool,
+    /// Hold PG questions with PG-13 words for a moderator to review
+    pub review_ratings: bool,
+}
+
+impl Default for ContentPolicy {
+    fn default() -> Self {
+        ContentPolicy {
+            min_length: 3,
+            max_length: 300,
+            strip_mentions: true,
+            strip_links: true,
+            review_ratings: true,
+        }
+    }
+}
+
+/// A question that passed the content policy
+#[derive(Debug, Clone)]
+pub struct CheckedContent {
+    /// The prompt with mentions and links removed
+    pub prompt: String,
+    /// Whether a moderator has to approve the question before it is drawn
+    pub needs_review: bool,
+}
+
+/// Reads a word list, skipping comments and blank lines
+fn terms(list: &str) -> Vec<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(normalize)
+        .collect()
+}
+
+/// Finds the first term that appears in the text as whole words
+///
+/// # Parameters
+/// * `normalized: &str` - Text that went through `normalize`
+/// * `terms: &[String]` - Normalized words or phrases to look for
+pub fn find_term<'a>(normalized: &str, terms: &'a [String]) -> Option<&'a str> {
+    let padded = format!(" {normalized} ");
+
+    terms
+        .iter()
+        .find(|term| !term.is_empty() && padded.contains(&format!(" {term} ")))
+        .map(String::as_str)
+}
+
+/// Removes user, role and channel mentions, and takes the `@` off `@everyone` and `@here`
+pub fn strip_mentions(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        result.push_str(&rest[..start]);
+        let candidate = &rest[start..];
+
+        match mention_length(candidate) {
+            Some(length) => rest = &candidate[length..],
+            None => {
+                result.push('<');
+                rest = &candidate[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    ["@everyone", "@here"].iter().fold(result, |text, ping| {
+        replace_ignore_case(&text, ping, &ping[1..])
+    })
+}
+
+/// Gets the length of a mention like `<@123>`, `<@!123>`, `<@&123>` or `<#123>` at the start of the text
+fn mention_length(text: &str) -> Option<usize> {
+    let inner = text
+        .strip_prefix("<@!")
+        .or_else(|| text.strip_prefix("<@&"))
+        .or_else(|| text.strip_prefix("<@"))
+        .or_else(|| text.strip_prefix("<#"))?;
+    let digits = inner.chars().take_while(char::is_ascii_digit).count();
+
+    (digits > 0 && inner[digits..].starts_with('>')).then(|| text.len() - inner.len() + digits + 1)
+}
+
+/// Replaces an ASCII word ignoring case, e.g. `@Everyone` for `@everyone`
+fn replace_ignore_case(text: &str, from: &str, to: &str) -> String {
+    // Only ASCII letters change, so the positions match the original text
+    let lower = text.to_ascii_lowercase();
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (index, _) in lower.match_indices(from) {
+        result.push_str(&text[last..index]);
+        result.push_str(to);
+        last = index + from.len();
+    }
+    result.push_str(&text[last..]);
+
+    result
+}
+
+/// Removes words that are links
+pub fn strip_links(text: &str) -> String {
+    text.split_whitespace()
+        .filter(|word| {
+            let word = word.trim_start_matches(['<', '(']).to_lowercase();
+            !(word.starts_with("http://")
+                || word.starts_with("https://")
+                || word.starts_with("www.")
+                || word.starts_with("discord.gg/"))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Checks a submitted question against the content policy
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to get the settings and the server's blocklist
+/// * `guild_id: GuildId` - Server the question is submitted in
+/// * `prompt: &str` - The question as it was submitted
+/// * `rating: &str` - Rating the question was submitted with
+/// * `language: &str` - Language to write errors in
+pub async fn check_content(
+    bot: &Bot,
+    guild_id: GuildId,
+    prompt: &str,
+    rating: &str,
+    language: &str,
+) -> Result<CheckedContent> {
+    let policy = &bot.config.content_policy;
+    let mut prompt = prompt.trim().to_string();
+
+    if policy.strip_mentions {
+        prompt = strip_mentions(&prompt);
+    }
+    if policy.strip_links {
+        prompt = strip_links(&prompt);
+    }
+    let prompt = prompt.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    let length = prompt.chars().count();
+    if length < policy.min_length {
+        return Err(Error::InvalidInput(tf(language, "content.too_short", &[("min", &policy.min_length)])));
+    }
+    if length > policy.max_length {
+        return Err(Error::InvalidInput(tf(language, "content.too_long", &[("max", &policy.max_length)])));
+    }
+
+    let normalized = normalize(&prompt);
+    let guild_blocklist = bot.get_guild_blocklist(guild_id).await?;
+
+    if find_term(&normalized, &BLOCKLIST).is_some() || find_term(&normalized, &guild_blocklist).is_some() {
+        return Err(Error::InvalidInput(t(language, "content.blocked")));
+    }
+
+    let needs_review = policy.review_ratings && rating == "PG" && find_term(&normalized, &PG13_TERMS).is_some();
+
+    Ok(CheckedContent { prompt, needs_review })
+}
+
+/// Command to manage the words blocked in a server
+pub fn blocklist_command() -> CreateCommand {
+    let word_option = |description| {
+        CreateCommandOption::new(CommandOptionType::String, "word", description)
+            .required(true)
+            .max_length(MAX_BLOCKED_WORD_LENGTH)
+    };
+
+    CreateCommand::new("blocklist")
+        .localized("blocklist")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "add", "Block a word or phrase in questions")
+                .add_sub_option(word_option("The word or phrase to block")),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "Stop blocking a word or phrase")
+                .add_sub_option(word_option("The word or phrase to allow again")),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "show",
+            "Show the words blocked in this server",
+        ))
+}
+
+pub async fn blocklist(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+    let word = normalize(invocation.str_option("word").unwrap_or(""));
+
+    match invocation.subcommand.as_deref() {
+        Some("add") | Some("remove") if word.is_empty() => {
+            Err(Error::InvalidInput(t(language, "blocklist.empty_word")))
+        }
+        Some("add") => {
+            bot.add_blocked_word(guild_id, &word).await?;
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::BlockWord) {
+                bot.record_audit(entry.target(&word)).await;
+            }
+
+            Ok(Reply::text(tf(language, "blocklist.added", &[("word", &word)])).ephemeral(true))
+        }
+        Some("remove") => {
+            if !bot.remove_blocked_word(guild_id, &word).await? {
+                return Err(Error::InvalidInput(tf(language, "blocklist.not_found", &[("word", &word)])));
+            }
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::UnblockWord) {
+                bot.record_audit(entry.target(&word)).await;
+            }
+
+            Ok(Reply::text(tf(language, "blocklist.removed", &[("word", &word)])).ephemeral(true))
+        }
+        Some("show") => {
+            let words = bot.get_guild_blocklist(guild_id).await?;
+
+            Ok(Reply::text(match words.is_empty() {
+                true => t(language, "blocklist.none"),
+                false => tf(language, "blocklist.show", &[("words", &words.join(", "))]),
+            })
+            .ephemeral(true))
+        }
+        _ => Err(Error::InvalidInput(t(language, "common.unknown"))),
+    }
+}
+
+/// Command for moderators to go through questions held for review
+pub fn review_command() -> CreateCommand {
+    CreateCommand::new("review")
+        .localized("review")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "list",
+            "Show the questions waiting for review",
+        ))
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "approve", "Allow a question to be drawn")
+                .add_sub_option(question_uid_option("The question to approve"))
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "rating",
+                        "The rating to give it, defaults to the one it was submitted with",
+                    )
+                    .add_string_choice("PG", "PG")
+                    .add_string_choice("PG-13", "PG-13"),
+                ),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "reject", "Remove a question waiting for review")
+                .add_sub_option(question_uid_option("The question to reject")),
+        )
+}
+
+pub async fn review(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+    let question_uid = invocation.str_option("question_uid").unwrap_or("");
+
+    match invocation.subcommand.as_deref() {
+        Some("list") => {
+            let (questions, total) = bot.get_pending_questions(guild_id).await?;
+
+            if questions.is_empty() {
+                return Ok(Reply::text(t(language, "review.empty")).ephemeral(true));
+            }
+
+            // Long questions are shortened so the list fits in a message
+            let header = tf(language, "review.list", &[("shown", &questions.len()), ("total", &total), ("questions", &"")]);
+            let lines = fit_lines(
+                &questions,
+                MAX_MESSAGE_LENGTH.saturating_sub(header.chars().count()),
+                |question| question.prompt.as_str(),
+                |question, prompt| format!("`{}` ({} - {}) {prompt}", question.uid, question.question_type, question.rating),
+            );
+
+            Ok(Reply::text(tf(
+                language,
+                "review.list",
+                &[("shown", &questions.len()), ("total", &total), ("questions", &lines)],
+            ))
+            .ephemeral(true))
+        }
+        Some("approve") => {
+            let rating = invocation.str_option("rating");
+
+            if !bot.approve_question(guild_id, question_uid, rating).await? {
+                return Err(Error::QuestionNotFound);
+            }
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::ApproveQuestion) {
+                bot.record_audit(entry.target(question_uid).after(rating)).await;
+            }
+
+            Ok(Reply::text(tf(language, "review.approved", &[("uid", &question_uid)])))
+        }
+        Some("reject") => {
+            let prompt = bot.get_guild_question(guild_id, question_uid).await?.map(|question| question.prompt);
+
+            if !bot.reject_question(guild_id, question_uid, invocation.user_id).await? {
+                return Err(Error::QuestionNotFound);
+            }
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::RejectQuestion) {
+                bot.record_audit(entry.target(question_uid).before(prompt)).await;
+            }
+
+            Ok(Reply::text(tf(
+                language,
+                "review.rejected",
+                &[("uid", &question_uid), ("days", &bot.config.trash_retention_days)],
+            ))
+            .components(vec![undo_button(question_uid, language)]))
+        }
+        _ => Err(Error::InvalidInput(t(language, "common.unknown"))),
+    }
+}
+
+impl Bot {
+    /// Gets the words a server blocked, already normalized
+    pub async fn get_guild_blocklist(&self, guild_id: GuildId) -> Result<Vec<String>> {
+        let words = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT word FROM guild_blocklist
+            WHERE guild_id = ?
+            ORDER BY word
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(words)
+    }
+
+    pub async fn add_blocked_word(&self, guild_id: GuildId, word: &str) -> Result<()> {
+        sqlx::query("INSERT OR IGNORE INTO guild_blocklist (guild_id, word) VALUES (?, ?)")
+            .bind(guild_id.get() as i64)
+            .bind(word)
+            .execute(&self.database)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Removes a blocked word, returning false if it wasn't blocked
+    pub async fn remove_blocked_word(&self, guild_id: GuildId, word: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM guild_blocklist WHERE guild_id = ? AND word = ?")
+            .bind(guild_id.get() as i64)
+            .bind(word)
+            .execute(&self.database)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Gets the oldest questions waiting for review in a server
+    ///
+    /// # Returns
+    /// * `(Vec<Question>, i64)` - Up to `REVIEW_PAGE` questions, and how many are waiting in total
+    pub async fn get_pending_questions(&self, guild_id: GuildId) -> Result<(Vec<Question>, i64)> {
+        let questions = sqlx::query_as::<_, Question>(
+            r#"
+            SELECT * FROM questions
+            WHERE guild_id = ? AND status = ? AND deleted_at IS NULL
+            ORDER BY id
+            LIMIT ?
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(PENDING)
+        .bind(REVIEW_PAGE)
+        .fetch_all(&self.database)
+        .await?;
+
+        let total = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM questions WHERE guild_id = ? AND status = ? AND deleted_at IS NULL")
+            .bind(guild_id.get() as i64)
+            .bind(PENDING)
+            .fetch_one(&self.database)
+            .await?;
+
+        Ok((questions, total))
+    }
+
+    /// Approves a question waiting for review, optionally changing its rating
+    /// Returns false if the server has no such question waiting
+    pub async fn approve_question(&self, guild_id: GuildId, question_uid: &str, rating: Option<&str>) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE questions SET status = ?, rating = COALESCE(?, rating)
+            WHERE guild_id = ? AND uid = ? AND status = ? AND deleted_at IS NULL
+            "#,
+        )
+        .bind(APPROVED)
+        .bind(rating)
+        .bind(guild_id.get() as i64)
+        .bind(question_uid)
+        .bind(PENDING)
+        .execute(&self.database)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Moves a question waiting for review to the trash, so a wrong reject can be undone
+    /// Returns false if the server has no such question waiting
+    pub async fn reject_question(&self, guild_id: GuildId, question_uid: &str, user_id: UserId) -> Result<bool> {
+        match self.get_guild_question(guild_id, question_uid).await? {
+            Some(question) if question.status == PENDING => self.trash_question(guild_id, question_uid, user_id).await,
+            _ => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| normalize(word)).collect()
+    }
+
+    #[test]
+    fn terms_match_whole_words() {
+        let terms = list(&["ass", "strip poker"]);
+
+        assert_eq!(find_term(&normalize("You're an ass!"), &terms), Some("ass"));
+        assert_eq!(find_term(&normalize("ass"), &terms), Some("ass"));
+        assert_eq!(find_term(&normalize("Play strip  poker tonight"), &terms), Some("strip poker"));
+        assert_eq!(find_term(&normalize("Do you like classic music?"), &terms), None);
+        assert_eq!(find_term(&normalize("Assemble a team"), &terms), None);
+        assert_eq!(find_term(&normalize("Strip the poker table"), &terms), None);
+    }
+
+    #[test]
+    fn terms_ignore_case_and_punctuation() {
+        let terms = list(&["Beer", "Jäger"]);
+
+        assert_eq!(find_term(&normalize("Drink a BEER."), &terms), Some("beer"));
+        assert_eq!(find_term(&normalize("one JÄGER, please"), &terms), Some("jäger"));
+        assert_eq!(find_term(&normalize("Beers?"), &terms), None);
+    }
+
+    #[test]
+    fn empty_terms_never_match() {
+        assert_eq!(find_term(&normalize("anything at all"), &list(&["?!", ""])), None);
+        assert_eq!(terms("# comment\n\n  Beer  \nshots\n"), vec!["beer", "shots"]);
+    }
+
+    #[test]
+    fn mentions_are_stripped() {
+        assert_eq!(strip_mentions("Dare <@123> to sing"), "Dare  to sing");
+        assert_eq!(strip_mentions("<@!123><@&456> and <#789>"), " and ");
+        assert_eq!(strip_mentions("Ping @everyone and @HERE"), "Ping everyone and here");
+        assert_eq!(strip_mentions("Is 1 < 2 or <@abc> or <@>?"), "Is 1 < 2 or <@abc> or <@>?");
+        assert_eq!(strip_mentions("Trailing <@123"), "Trailing <@123");
+        assert_eq!(strip_mentions("Ünïcode <@1> ok"), "Ünïcode  ok");
+    }
+
+    #[test]
+    fn links_are_stripped() {
+        assert_eq!(strip_links("Go to https://example.com now"), "Go to now");
+        assert_eq!(strip_links("Join discord.gg/abc or (www.example.com)"), "Join or");
+        assert_eq!(strip_links("<HTTP://EXAMPLE.COM> is loud"), "is loud");
+        assert_eq!(strip_links("Say https, http and www out loud"), "Say https, http and www out loud");
+    }
+}
@@ -1,12 +1,19 @@
 use rand::random_bool;
-use serenity::all::{ButtonStyle, CreateActionRow, CreateButton, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, GuildId, Timestamp};
+use serenity::all::{ButtonStyle, Mentionable, CreateActionRow, CreateButton, CreateEmbed, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, GuildId, Timestamp};
+use tracing::warn;
 
 use crate::{
-    bot::Bot, menu_type::{MenuToStr, MenuType}, other_impl::{EmbedMaker, FooterMaker, MessageMaker}, questions::{Question, QuestionType}
+    bot::Bot, content_policy::PENDING, error::Result, i18n::{t, tf}, invocation::Reply, menu_type::MenuType, other_impl::{EmbedMaker, FooterMaker}, pagination::{PageState, PageStateError}, questions::{Question, QuestionType}
 };
 
-use std::future::Future;
-use std::pin::Pin;
+/// Most options Discord allows in a select menu
+const MAX_SELECT_OPTIONS: usize = 25;
+
+/// Most characters Discord allows in an embed description
+pub const MAX_EMBED_DESCRIPTION: usize = 4096;
+
+/// Most characters Discord allows in the content of a message
+pub const MAX_MESSAGE_LENGTH: usize = 2000;
 
 /// Makes the embed that is sent to the front end for questions
 /// 
@@ -15,38 +22,27 @@ use std::pin::Pin;
 /// * 'question_type: QuestionType' - The type of question being asked
 /// * `rating_limit: impl AsRef<str>` - What rating the question must be
 /// * 'guild_id: Option<GuildId>' - GuildId to use when looking at the database
+/// * `language: &str` - Language to prefer questions in and to display the embed in
 pub async fn embed_text(
     bot: &Bot,
     question_type: QuestionType,
     rating_limit: impl AsRef<str>,
-    guild_id: Option<GuildId>
-) -> CreateEmbed {
+    guild_id: Option<GuildId>,
+    language: &str
+) -> Result<CreateEmbed> {
     // Tracks how many times it has tried to find a question
     let mut loops: u8 = 0;
 
     // Gets the description, rating, and uid of the question
     let question: Question = loop {
         // Sets the rating, will be the set rating unless it is ALL
-        // If it is ALL, it has a random change of PG or PG-13
-        let rating: &str = match rating_limit.as_ref() {
-            "ALL" => {
-                if random_bool(0.5) {
-                    "PG-13"
-                } else {
-                    "PG"
-                }
-            },
-            "PG-13" => "PG-13",
-            _ => "PG",
-        };
+        let rating = resolve_rating(rating_limit.as_ref());
 
         // Gets a random question from the database
-        let question = bot.get_random_question(question_type, rating, guild_id).await;
-
-        // Unwrap the question and return the unwrap if it is Ok
-        if question.is_ok() {
-            let question = question.unwrap().unwrap();
+        let question = bot.get_random_question(question_type, rating, guild_id, language, &[]).await?;
 
+        // Return the question if one was found
+        if let Some(question) = question {
             break question;
         }
 
@@ -55,42 +51,157 @@ pub async fn embed_text(
 
         // This really should never happen, but if it does, this protects against infinite loops
         if loops == 5 {
-            break Question::new(-1, "N/A".to_string(), QuestionType::NONE, "PG-13".to_string(), "0".to_string());
+            break Question::new(-1, "N/A".to_string(), QuestionType::NONE, "PG-13".to_string(), "0".to_string(), language.to_string());
         }
     };
 
-    // Creates the embed to send to the client
-    let embed = CreateEmbed::new()
+    Ok(question_embed(question_type, &question, language))
+}
+
+/// Picks the rating to draw from based on the rating limit
+/// If it is ALL, it has a random chance of PG or PG-13
+pub fn resolve_rating(rating_limit: &str) -> &'static str {
+    match rating_limit {
+        "ALL" if random_bool(0.5) => "PG-13",
+        "PG-13" => "PG-13",
+        _ => "PG",
+    }
+}
+
+/// Creates the embed to send to the client for a question
+///
+/// # Parameters
+/// * `question_type: QuestionType` - The type of question being asked
+/// * `question: &Question` - The question to display
+/// * `language: &str` - Language to display the embed in
+pub fn question_embed(question_type: QuestionType, question: &Question, language: &str) -> CreateEmbed {
+    CreateEmbed::new()
         .title(if question_type == QuestionType::TRUTH {
-            "Truth"
+            t(language, "question.truth")
         } else {
-            "Dare"
+            t(language, "question.dare")
         })
-        .description(question.prompt)
-        .footer(format!("Rating: {} | UID: {}", question.rating, question.uid).to_footer())
-        .timestamp(Timestamp::now());
-
-    embed
+        .description(question.prompt.as_str())
+        .footer(
+            tf(language, "question.footer", &[("rating", &question.rating), ("uid", &question.uid)])
+                .to_footer(),
+        )
+        .timestamp(Timestamp::now())
 }
 
 /// Returns a `CreateButton` for Truths
-pub fn truth_button() -> CreateButton {
-    make_button("truth", "Truth", ButtonStyle::Primary)
+pub fn truth_button(language: &str) -> CreateButton {
+    make_button("truth", &t(language, "button.truth"), ButtonStyle::Primary)
 }
 
 /// Returns a `CreateButton` for Dares
-pub fn dare_button() -> CreateButton {
-    make_button("dare", "Dare", ButtonStyle::Danger)
+pub fn dare_button(language: &str) -> CreateButton {
+    make_button("dare", &t(language, "button.dare"), ButtonStyle::Danger)
+}
+
+/// Returns a `CreateButton` for going to the first page
+/// `id` is the encoded state of the page it goes to
+pub fn first_page_button(id: impl AsRef<str>, language: &str, disabled: bool) -> CreateButton {
+    make_button(id, &t(language, "button.first_page"), ButtonStyle::Secondary).disabled(disabled)
+}
+
+/// Returns a `CreateButton` for going to the previous page
+/// `id` is the encoded state of the page it goes to
+pub fn previous_page_button(id: impl AsRef<str>, language: &str) -> CreateButton {
+    make_button(id, &t(language, "button.previous_page"), ButtonStyle::Secondary)
 }
 
-/// Returns a `CreateButton` for the "Get Question" action
-pub fn previous_page_button(page_number :usize, menu_type: &str) -> CreateButton {
-    make_button(format!("previous_page-{page_number}:{menu_type}"), "Previous Page", ButtonStyle::Secondary)
+/// Returns a `CreateButton` for going to the next page
+/// `id` is the encoded state of the page it goes to
+pub fn next_page_button(id: impl AsRef<str>, language: &str) -> CreateButton {
+    make_button(id, &t(language, "button.next_page"), ButtonStyle::Secondary)
 }
 
-/// Returns a `CreateButton` for the "Get Question" action
-pub fn next_page_button(page_number :usize, menu_type: &str) -> CreateButton {
-    make_button(format!("next_page-{page_number}:{menu_type}").as_str(), "Next Page", ButtonStyle::Secondary)
+/// Returns a `CreateButton` for going to the last page
+/// `id` is the encoded state of the page it goes to
+pub fn last_page_button(id: impl AsRef<str>, language: &str, disabled: bool) -> CreateButton {
+    make_button(id, &t(language, "button.last_page"), ButtonStyle::Secondary).disabled(disabled)
+}
+
+/// Returns a `CreateSelectMenu` for jumping to a page
+/// Discord allows 25 options, so lists with more pages show the ones around the current page
+///
+/// # Parameters
+/// * `id: impl AsRef<str>` - Encoded state of the current page
+/// * `page: usize` - The current page
+/// * `pages: usize` - How many pages the list has
+/// * `language: &str` - Language to display the menu in
+pub fn page_select_menu(id: impl AsRef<str>, page: usize, pages: usize, language: &str) -> CreateSelectMenu {
+    let first = page.saturating_sub(MAX_SELECT_OPTIONS / 2).clamp(1, pages.saturating_sub(MAX_SELECT_OPTIONS - 1).max(1));
+    let last = (first + MAX_SELECT_OPTIONS - 1).min(pages);
+
+    let options = (first..=last)
+        .map(|option| {
+            CreateSelectMenuOption::new(tf(language, "list.jump_option", &[("page", &option)]), option.to_string())
+                .default_selection(option == page)
+        })
+        .collect();
+
+    CreateSelectMenu::new(id.as_ref(), CreateSelectMenuKind::String { options })
+        .placeholder(t(language, "list.jump"))
+}
+
+/// Shortens text to at most `max` characters, ending it with `…` when it is cut
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+
+    let mut shortened: String = text.chars().take(max.saturating_sub(1)).collect();
+    shortened.push('…');
+    shortened
+}
+
+/// Joins the lines of a list so it fits in `limit` characters
+/// When the lines are too long together, every line gets an equal share of the limit
+/// and the question in it is shortened until the line fits its share
+///
+/// # Parameters
+/// * `items: &[T]` - What the list shows, one line each
+/// * `limit: usize` - Most characters the list can have
+/// * `text: impl Fn(&T) -> &str` - Gets the question of an item, the part of the line that can be shortened
+/// * `line: impl Fn(&T, &str) -> String` - Makes the line of an item with its question, shortened or not
+pub fn fit_lines<T>(
+    items: &[T],
+    limit: usize,
+    text: impl Fn(&T) -> &str,
+    line: impl Fn(&T, &str) -> String,
+) -> String {
+    let lines: Vec<String> = items.iter().map(|item| line(item, text(item))).collect();
+    let list = lines.join("\n");
+
+    if list.chars().count() <= limit {
+        return list;
+    }
+
+    let share = limit.saturating_sub(items.len().saturating_sub(1)) / items.len().max(1);
+
+    items
+        .iter()
+        .zip(lines)
+        .map(|(item, full)| {
+            let overflow = full.chars().count().saturating_sub(share);
+
+            if overflow == 0 {
+                return full;
+            }
+
+            let text = text(item);
+            let shortened = line(item, &truncate(text, text.chars().count().saturating_sub(overflow)));
+
+            // Lines that are too long even without their question are cut at the end
+            truncate(&shortened, share)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 /// Makes a button based on provided input
@@ -103,58 +214,165 @@ fn make_button(id: impl AsRef<str>, label: &str, style: ButtonStyle) -> CreateBu
     CreateButton::new(id.as_ref()).label(label).style(style)
 }
 
-/// Sends a page of questions as an embed
-pub fn send_page(
-    page_number: usize,
-    questions: Vec<Question>,
-    menu_type: MenuType
-) -> Pin<Box<dyn Future<Output = CreateInteractionResponse> + Send>> {
-    Box::pin(async move {
-        
-        if questions.is_empty() {
-            return "No questions found...".to_interaction_message();
+/// Makes the navigation buttons and page select menu for a page
+///
+/// # Parameters
+/// * `page: usize` - The current page
+/// * `pages: usize` - How many pages the list has
+/// * `language: &str` - Language to display the components in
+/// * `encode: impl Fn(usize, char) -> Result<String, PageStateError>` - Makes the id of a component from the page it goes to and its slot
+pub fn page_components(
+    page: usize,
+    pages: usize,
+    language: &str,
+    encode: impl Fn(usize, char) -> std::result::Result<String, PageStateError>,
+) -> std::result::Result<Vec<CreateActionRow>, PageStateError> {
+    // Previous on the first page goes to the last page, and next on the last goes to the first
+    let previous = if page == 1 { pages } else { page - 1 };
+    let next = page % pages + 1;
+
+    let mut components = vec![CreateActionRow::Buttons(vec![
+        first_page_button(encode(1, 'f')?, language, page == 1),
+        previous_page_button(encode(previous, 'p')?, language),
+        next_page_button(encode(next, 'n')?, language),
+        last_page_button(encode(pages, 'l')?, language, page == pages),
+    ])];
+
+    if pages > 1 {
+        components.push(CreateActionRow::SelectMenu(page_select_menu(
+            encode(page, 'j')?,
+            page,
+            pages,
+            language,
+        )));
+    }
+
+    Ok(components)
+}
+
+/// Makes a page of questions as an embed
+/// Commands send it as a new message and page buttons update the message in place with `Reply::to_update`
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to access the database and sign the page buttons
+/// * `guild_id: Option<GuildId>` - Guild to list the questions of
+/// * `state: PageState` - Which page to send and how the list is filtered and sorted
+/// * `show_authors: bool` - Whether to show who added each question and when, for moderators
+/// * `language: &str` - Language to display the page in
+pub async fn send_page(
+    bot: &Bot,
+    guild_id: Option<GuildId>,
+    mut state: PageState,
+    show_authors: bool,
+    language: &str
+) -> Result<Reply> {
+    let Some(guild_id) = guild_id else {
+        return Ok(Reply::text(t(language, "list.empty")));
+    };
+
+    let total = bot.count_questions(guild_id, &state.filter).await?;
+
+    if total == 0 {
+        return Ok(Reply::text(t(language, "list.empty")));
+    }
+
+    // The list may have shrunk since the button was made, so keep the page in range
+    let pages = (total as usize).div_ceil(bot.config.page_size as usize);
+    state.page = state.page.clamp(1, pages);
+
+    // Questions to be sent to the requested page
+    let page_questions = bot.get_question_page(guild_id, &state).await?;
+
+    let encode = |page, slot| state.with_page(page).encode(slot, &bot.signing_key);
+
+    let components = match page_components(state.page, pages, language, encode) {
+        Ok(components) => components,
+        Err(e) => {
+            warn!(error = %e, "failed to encode page buttons");
+            Vec::new()
+        }
+    };
+
+    // Format the questions for the response, shortening long ones so the page fits in the embed
+    let line = |question: &Question, prompt: &str| {
+        // This prevents the uid of default questions from being sent to the user
+        let uid =  match state.menu_type {
+            MenuType::CUSTOM | MenuType::MINE => format!(" UID: {}", question.uid),
+            MenuType::SEARCH if question.guild_id != 0 => format!(" UID: {}", question.uid),
+            _ => "".to_string()
+        };
+
+        let mut line = format!("{prompt} ({} - {}){}", question.question_type, question.rating, uid);
+
+        if let Some(tag) = &question.tag {
+            line.push_str(&format!(" #{tag}"));
         }
 
-        let pages = questions.len() / 10 + if questions.len() % 10 > 0 { 1 } else { 0 };
-        // If the requested page is 0, send 0, otherwise, send page_number - 1
-        let start = (if page_number == 0 {0} else {page_number - 1}) * 10;
-        let end = start + 10;
-        
-        if page_number > pages {
-            return send_page(1, questions, menu_type).await;
-        } else if  page_number < 1 {
-            return send_page(pages, questions, menu_type).await;
+        if question.status == PENDING {
+            line.push_str(&format!(" {}", t(language, "list.pending")));
         }
 
-        // Questions to be sent to the quested page
-        let page_questions = &questions[start..end.min(questions.len())];
-
-        let buttons = CreateActionRow::Buttons(vec![
-            previous_page_button(page_number-1, &menu_type.to_str()),
-            next_page_button(page_number, &menu_type.to_str())]);
-
-        // Format the questions for the response
-        let questions: Vec<String> = page_questions
-            .iter()
-            .map(|question| {
-                // This prevents the uid of default questions from being sent to the user
-                let uid =  match menu_type {
-                    MenuType::CUSTOM => format!(" UID: {}", question.uid),
-                    MenuType::DEFAULT => "".to_string()
-                };
-
-                format!("{} ({} - {}){}", question.prompt, question.question_type, question.rating, uid)
-            
-            })
-            .collect();
-        // Join the questions into a single string
-        let response = format!("{}", questions.join("\n"));
-        let title = format!("Page {}/{}", page_number, pages);
-        // Create the embed
-        let embed = response.to_embed(title, "List of Questions");
-
-        CreateInteractionResponse::Message(CreateInteractionResponseMessage::new().add_embed(embed).components(vec![buttons]))
-
-        
-    })
+        if show_authors && let Some(author) = question.author() {
+            let added = match question.created_timestamp() {
+                Some(timestamp) => tf(language, "list.added_on", &[("author", &author.mention()), ("date", &format!("<t:{timestamp}:d>"))]),
+                None => tf(language, "list.added_by", &[("author", &author.mention())]),
+            };
+            line.push_str(&format!(" - {added}"));
+        }
+
+        line
+    };
+    // Join the questions into a single string
+    let response = fit_lines(&page_questions, MAX_EMBED_DESCRIPTION, |question| question.prompt.as_str(), line);
+    let title = tf(language, "list.page", &[("page", &state.page), ("pages", &pages)]);
+    // Create the embed
+    let embed = response.to_embed(title, t(language, "list.footer"));
+
+    Ok(Reply::embed(embed).components(components))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(prompt: &str) -> String {
+        format!("{prompt} (TRUTH - PG-13) UID: 0d4f9c3e-5b1a-4c1e-9f7a-2b8d6e0c1a3f")
+    }
+
+    #[test]
+    fn truncating() {
+        assert_eq!(truncate("short", 5), "short");
+        assert_eq!(truncate("longer", 5), "long…");
+        assert_eq!(truncate("ééééé", 3), "éé…");
+        assert_eq!(truncate("text", 0), "");
+    }
+
+    #[test]
+    fn lines_that_fit_are_kept() {
+        let prompts = ["one", "two"];
+        let list = fit_lines(&prompts, MAX_MESSAGE_LENGTH, |prompt| prompt, |_, prompt| line(prompt));
+
+        assert_eq!(list, format!("{}\n{}", line("one"), line("two")));
+    }
+
+    #[test]
+    fn long_questions_are_shortened() {
+        let prompts: Vec<String> = (0..25).map(|i| format!("{i} {}", "a".repeat(1000))).collect();
+        let list = fit_lines(&prompts, MAX_EMBED_DESCRIPTION, |prompt| prompt.as_str(), |_, prompt| line(prompt));
+
+        assert!(list.chars().count() <= MAX_EMBED_DESCRIPTION);
+        assert_eq!(list.lines().count(), 25);
+        // The rest of each line stays whole
+        assert!(list.lines().all(|line| line.contains("… (TRUTH - PG-13) UID: ")));
+        assert!(list.lines().nth(7).unwrap().starts_with("7 aaa"));
+    }
+
+    #[test]
+    fn lines_too_long_without_their_question_are_cut() {
+        let prompts = ["a"; 3];
+        let list = fit_lines(&prompts, 30, |prompt| prompt, |_, prompt| line(prompt));
+
+        assert!(list.chars().count() <= 30);
+        assert_eq!(list.lines().count(), 3);
+    }
 }
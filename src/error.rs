@@ -0,0 +1,142 @@
+//! Error module for the bot
+//! Has the Error enum used across the crate and turns errors into the embeds users see
+
+use std::fmt;
+
+use serenity::all::{
+    Colour, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    i18n::{t, tf},
+    metrics::METRICS,
+    other_impl::FooterMaker,
+    permissions::{capability_name, Capability},
+};
+
+/// Result type used by everything that can fail in the bot
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can go wrong while handling an event
+#[derive(Debug)]
+pub enum Error {
+    /// A database query failed
+    Database(sqlx::Error),
+    /// A request to Discord failed, boxed since serenity's error is much larger than the others
+    Discord(Box<serenity::Error>),
+    /// The command can only be used in a server
+    GuildOnly,
+    /// The member isn't allowed to do this
+    PermissionDenied,
+    /// The member has none of the roles the server granted the capability to
+    MissingCapability(Capability),
+    /// The question doesn't exist in this server
+    QuestionNotFound,
+    /// An option is missing or invalid, the message is already translated for the user
+    InvalidInput(String),
+}
+
+impl Error {
+    /// Checks if the error is a problem with the bot rather than with what the user asked for
+    pub fn is_internal(&self) -> bool {
+        matches!(self, Error::Database(_) | Error::Discord(_))
+    }
+
+    /// Gets the message shown to the user for the error
+    ///
+    /// # Parameters
+    /// * `language: &str` - Language to translate the message into
+    pub fn user_message(&self, language: &str) -> String {
+        match self {
+            Error::Database(_) | Error::Discord(_) => t(language, "error.internal"),
+            Error::GuildOnly => t(language, "error.guild_only"),
+            Error::PermissionDenied => t(language, "error.permission_denied"),
+            Error::MissingCapability(capability) => tf(
+                language,
+                "error.missing_capability",
+                &[("capability", &capability_name(*capability, language))],
+            ),
+            Error::QuestionNotFound => t(language, "error.question_not_found"),
+            Error::InvalidInput(message) => message.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Database(e) => write!(f, "database error: {e}"),
+            Error::Discord(e) => write!(f, "discord error: {e}"),
+            Error::GuildOnly => write!(f, "command used outside of a server"),
+            Error::PermissionDenied => write!(f, "permission denied"),
+            Error::MissingCapability(capability) => write!(f, "missing capability: {capability}"),
+            Error::QuestionNotFound => write!(f, "question not found"),
+            Error::InvalidInput(message) => write!(f, "invalid input: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(e) => Some(e),
+            Error::Discord(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Every failed query goes through here on its way up, so this is where they are counted
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        METRICS.database_errors.inc();
+        Error::Database(e)
+    }
+}
+
+impl From<serenity::Error> for Error {
+    fn from(e: serenity::Error) -> Self {
+        Error::Discord(Box::new(e))
+    }
+}
+
+/// Logs an error with a new correlation id and makes the embed shown to the user
+/// The id is in the footer so a report from a user can be matched to the log
+///
+/// # Parameters
+/// * `error: &Error` - The error to report
+/// * `language: &str` - Language to display the embed in
+/// * `debug: bool` - Whether to show the underlying error, used for owners
+pub fn report_error(error: &Error, language: &str, debug: bool) -> CreateEmbed {
+    let correlation_id = Uuid::new_v4().simple().to_string()[..8].to_string();
+
+    // Problems with the bot are errors, mistakes by the user are only noted
+    if error.is_internal() {
+        error!(correlation_id = correlation_id.as_str(), error = %error, "request failed");
+    } else {
+        info!(correlation_id = correlation_id.as_str(), error = %error, "request rejected");
+    }
+
+    let embed = CreateEmbed::new()
+        .title(t(language, "error.title"))
+        .description(error.user_message(language))
+        .colour(Colour::RED)
+        .footer(tf(language, "error.footer", &[("id", &correlation_id)]).to_footer());
+
+    if debug {
+        embed.field("Debug", format!("```{error}```"), false)
+    } else {
+        embed
+    }
+}
+
+/// Reports an error as an ephemeral interaction response
+pub fn error_response(error: &Error, language: &str, debug: bool) -> CreateInteractionResponse {
+    CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .embed(report_error(error, language, debug))
+            .ephemeral(true),
+    )
+}
@@ -1,10 +1,299 @@
-use serenity::all::GuildId;
+//! Guild settings module for the bot
+//! Every setting of a guild lives in one `guild_settings` row, which is loaded and saved as a
+//! whole through `GuildSettings`, so changing one setting can't lose the others.
+//! Guilds without a row use the defaults from the config.
 
-/// This struct defines the GuildSettings struct and its database representation.
-/// I'm aware it is not used
-#[derive(Debug, sqlx::FromRow)]
+use serenity::all::{ChannelId, GuildId};
+use sqlx::{sqlite::SqliteRow, FromRow, Row, SqliteExecutor};
+
+use crate::{
+    bot::Bot,
+    config::Config,
+    error::Result,
+    rate_limit::{Limit, LimitScope},
+};
+
+/// The settings of a guild
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GuildSettings {
-    pub guild_id: Option<GuildId>,
+    pub guild_id: GuildId,
+    /// Highest rating drawn, `PG`, `PG-13` or `ALL`
     pub rating: String,
-    pub permissions: bool
-}
\ No newline at end of file
+    /// Whether only admins can add questions
+    pub admin: bool,
+    /// Timezone the question of the day is posted in
+    pub timezone: String,
+    /// Language the bot answers in, `None` follows each member's Discord language
+    pub language: Option<String>,
+    /// Prefix of text commands, `None` uses the configured prefix
+    pub prefix: Option<String>,
+    /// Rate limit overrides, `None` uses the configured limit
+    pub rate_limit_user: Option<Limit>,
+    pub rate_limit_channel: Option<Limit>,
+    pub rate_limit_guild: Option<Limit>,
+    /// Channel the audit log is mirrored to, `None` doesn't mirror it
+    pub audit_channel: Option<ChannelId>,
+    /// Questions each member can add in 24 hours, `None` uses the configured quota and 0 is no limit
+    pub submission_quota: Option<u32>,
+}
+
+impl GuildSettings {
+    /// Makes the settings of a guild that never changed any
+    pub fn new(guild_id: GuildId, config: &Config) -> GuildSettings {
+        GuildSettings {
+            guild_id,
+            rating: config.default_rating.clone(),
+            admin: false,
+            timezone: "UTC".to_string(),
+            language: None,
+            prefix: None,
+            rate_limit_user: None,
+            rate_limit_channel: None,
+            rate_limit_guild: None,
+            audit_channel: None,
+            submission_quota: None,
+        }
+    }
+
+    /// Gets the rate limit override of a scope
+    pub fn rate_limit(&self, scope: LimitScope) -> Option<Limit> {
+        match scope {
+            LimitScope::User => self.rate_limit_user,
+            LimitScope::Channel => self.rate_limit_channel,
+            LimitScope::Guild => self.rate_limit_guild,
+        }
+    }
+
+    /// Sets the rate limit override of a scope
+    pub fn set_rate_limit(&mut self, scope: LimitScope, limit: Option<Limit>) {
+        match scope {
+            LimitScope::User => self.rate_limit_user = limit,
+            LimitScope::Channel => self.rate_limit_channel = limit,
+            LimitScope::Guild => self.rate_limit_guild = limit,
+        }
+    }
+}
+
+/// Rate limits are stored as text like `5/10`, one that can't be read is ignored
+impl FromRow<'_, SqliteRow> for GuildSettings {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        let limit = |column: &str| -> sqlx::Result<Option<Limit>> {
+            Ok(row
+                .try_get::<Option<String>, _>(column)?
+                .and_then(|limit| limit.parse::<Limit>().ok()))
+        };
+
+        Ok(GuildSettings {
+            guild_id: GuildId::new(row.try_get::<i64, _>("guild_id")? as u64),
+            rating: row.try_get("rating")?,
+            admin: row.try_get("admin")?,
+            timezone: row.try_get("timezone")?,
+            language: row.try_get("language")?,
+            prefix: row.try_get("prefix")?,
+            rate_limit_user: limit("rate_limit_user")?,
+            rate_limit_channel: limit("rate_limit_channel")?,
+            rate_limit_guild: limit("rate_limit_guild")?,
+            audit_channel: row
+                .try_get::<Option<i64>, _>("audit_channel")?
+                .map(|channel| ChannelId::new(channel as u64)),
+            submission_quota: row
+                .try_get::<Option<i64>, _>("submission_quota")?
+                .map(|quota| quota as u32),
+        })
+    }
+}
+
+/// Loads the saved settings of a guild
+async fn load_settings<'e>(executor: impl SqliteExecutor<'e>, guild_id: GuildId) -> Result<Option<GuildSettings>> {
+    let settings = sqlx::query_as::<_, GuildSettings>(
+        r#"
+        SELECT guild_id, rating, admin, timezone, language, prefix,
+            rate_limit_user, rate_limit_channel, rate_limit_guild, audit_channel, submission_quota
+        FROM guild_settings
+        WHERE guild_id = ?
+        "#,
+    )
+    .bind(guild_id.get() as i64)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(settings)
+}
+
+/// Saves every setting of a guild, adding its row if it has none
+async fn store_settings<'e>(executor: impl SqliteExecutor<'e>, settings: &GuildSettings) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO guild_settings (guild_id, rating, admin, timezone, language, prefix,
+            rate_limit_user, rate_limit_channel, rate_limit_guild, audit_channel, submission_quota)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(guild_id) DO UPDATE SET
+            rating = excluded.rating,
+            admin = excluded.admin,
+            timezone = excluded.timezone,
+            language = excluded.language,
+            prefix = excluded.prefix,
+            rate_limit_user = excluded.rate_limit_user,
+            rate_limit_channel = excluded.rate_limit_channel,
+            rate_limit_guild = excluded.rate_limit_guild,
+            audit_channel = excluded.audit_channel,
+            submission_quota = excluded.submission_quota
+        "#,
+    )
+    .bind(settings.guild_id.get() as i64)
+    .bind(&settings.rating)
+    .bind(settings.admin)
+    .bind(&settings.timezone)
+    .bind(&settings.language)
+    .bind(&settings.prefix)
+    .bind(settings.rate_limit_user.map(|limit| limit.to_string()))
+    .bind(settings.rate_limit_channel.map(|limit| limit.to_string()))
+    .bind(settings.rate_limit_guild.map(|limit| limit.to_string()))
+    .bind(settings.audit_channel.map(|channel| channel.get() as i64))
+    .bind(settings.submission_quota.map(|quota| quota as i64))
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+impl Bot {
+    /// Gets the settings of a guild, the defaults if it has none saved
+    pub async fn get_guild_settings(&self, guild_id: GuildId) -> Result<GuildSettings> {
+        let settings = load_settings(&self.database, guild_id).await?;
+
+        // Every guild command and button loads the settings, so this is what the health report shows
+        self.health.database_succeeded();
+
+        Ok(settings.unwrap_or_else(|| GuildSettings::new(guild_id, &self.config)))
+    }
+
+    /// Saves every setting of a guild
+    pub async fn save_guild_settings(&self, settings: &GuildSettings) -> Result<()> {
+        store_settings(&self.database, settings).await
+    }
+
+    /// Loads the settings of a guild, changes them and saves them
+    /// The whole update runs in one transaction, so updates running at once can't undo each other
+    ///
+    /// # Parameters
+    /// * `guild_id: GuildId` - The guild to change the settings of
+    /// * `change: impl FnOnce(&mut GuildSettings)` - Changes the loaded settings
+    ///
+    /// # Returns
+    /// * `GuildSettings` - The settings that were saved
+    pub async fn update_guild_settings(
+        &self,
+        guild_id: GuildId,
+        change: impl FnOnce(&mut GuildSettings),
+    ) -> Result<GuildSettings> {
+        let mut transaction = self.database.begin().await?;
+
+        // Writing before reading takes the write lock like `BEGIN IMMEDIATE` would, even when no row matches,
+        // so another update waits for this one to commit instead of reading the settings it is about to change
+        sqlx::query("UPDATE guild_settings SET guild_id = guild_id WHERE guild_id = ?")
+            .bind(guild_id.get() as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        let mut settings = load_settings(&mut *transaction, guild_id)
+            .await?
+            .unwrap_or_else(|| GuildSettings::new(guild_id, &self.config));
+        change(&mut settings);
+        store_settings(&mut *transaction, &settings).await?;
+
+        transaction.commit().await?;
+
+        Ok(settings)
+    }
+
+    pub async fn get_guild_rating(&self, guild_id: Option<GuildId>) -> Result<String> {
+        // Default the guild_rating to the configured rating
+        let Some(guild_id) = guild_id else {
+            return Ok(self.config.default_rating.clone());
+        };
+
+        Ok(self.get_guild_settings(guild_id).await?.rating)
+    }
+
+    pub async fn set_guild_rating(&self, guild_id: GuildId, rating: &str) -> Result<()> {
+        self.update_guild_settings(guild_id, |settings| settings.rating = rating.to_string()).await?;
+
+        Ok(())
+    }
+
+    /// Gets the timezone of the guild, defaulting to UTC
+    pub async fn get_guild_timezone(&self, guild_id: Option<GuildId>) -> Result<String> {
+        let Some(guild_id) = guild_id else {
+            return Ok("UTC".to_string());
+        };
+
+        Ok(self.get_guild_settings(guild_id).await?.timezone)
+    }
+
+    pub async fn set_guild_timezone(&self, guild_id: GuildId, timezone: &str) -> Result<()> {
+        self.update_guild_settings(guild_id, |settings| settings.timezone = timezone.to_string()).await?;
+
+        Ok(())
+    }
+
+    /// Sets the language of a guild, `None` follows each member's locale
+    pub async fn set_guild_language(&self, guild_id: GuildId, language: Option<&str>) -> Result<()> {
+        self.update_guild_settings(guild_id, |settings| settings.language = language.map(str::to_string)).await?;
+
+        Ok(())
+    }
+
+    /// Gets the prefix of text commands in a guild, defaulting to the configured prefix
+    pub async fn get_guild_prefix(&self, guild_id: Option<GuildId>) -> Result<String> {
+        let Some(guild_id) = guild_id else {
+            return Ok(self.config.prefix.clone());
+        };
+
+        let prefix = self.get_guild_settings(guild_id).await?.prefix;
+
+        Ok(prefix.unwrap_or_else(|| self.config.prefix.clone()))
+    }
+
+    /// Sets the prefix of text commands in a guild, `None` goes back to the configured prefix
+    pub async fn set_guild_prefix(&self, guild_id: GuildId, prefix: Option<&str>) -> Result<()> {
+        self.update_guild_settings(guild_id, |settings| settings.prefix = prefix.map(str::to_string)).await?;
+
+        Ok(())
+    }
+
+    /// Sets the question permissions for a guild.
+    ///
+    /// # Parameters
+    /// * `guild_id: GuildId` - The guild to set permissions for
+    /// * `admin: bool` - Whether only admins can add questions
+    pub async fn set_guild_question_permissions(&self, guild_id: GuildId, admin: bool) -> Result<()> {
+        self.update_guild_settings(guild_id, |settings| settings.admin = admin).await?;
+
+        Ok(())
+    }
+
+    /// Checks if only admins can add questions in a guild
+    pub async fn get_guild_question_permissions(&self, guild_id: Option<GuildId>) -> Result<bool> {
+        // Default to anyone being able to add questions
+        let Some(guild_id) = guild_id else {
+            return Ok(false);
+        };
+
+        Ok(self.get_guild_settings(guild_id).await?.admin)
+    }
+
+    /// Gets how many questions each member of a guild can add in 24 hours, 0 for no limit
+    pub async fn get_submission_quota(&self, guild_id: GuildId) -> Result<u32> {
+        let quota = self.get_guild_settings(guild_id).await?.submission_quota;
+
+        Ok(quota.unwrap_or(self.config.daily_submission_quota))
+    }
+
+    /// Sets how many questions each member of a guild can add in 24 hours, `None` goes back to the configured quota
+    pub async fn set_submission_quota(&self, guild_id: GuildId, quota: Option<u32>) -> Result<()> {
+        self.update_guild_settings(guild_id, |settings| settings.submission_quota = quota).await?;
+
+        Ok(())
+    }
+}
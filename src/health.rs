@@ -0,0 +1,128 @@
+//! Health module for the bot
+//! Tracks whether the bot is connected to Discord and can reach the database, served on `/health`
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::migrate::Migrator;
+use tracing::warn;
+
+use crate::{bot::Bot, error::Result};
+
+/// Migrations bundled into the binary, run at startup
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// How long the database check can take before the database counts as unreachable
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Health state shared between the event handler and the endpoint
+#[derive(Debug, Default)]
+pub struct Health {
+    /// Whether the gateway is connected, the bot runs a single shard
+    gateway_connected: AtomicBool,
+    /// When one of the bot's own queries last succeeded, the health check's query doesn't count
+    last_database_success: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl Health {
+    /// Sets whether the gateway is connected
+    pub fn set_gateway_connected(&self, connected: bool) {
+        self.gateway_connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn gateway_connected(&self) -> bool {
+        self.gateway_connected.load(Ordering::Relaxed)
+    }
+
+    /// Records that one of the bot's database queries just succeeded
+    pub fn database_succeeded(&self) {
+        if let Ok(mut last) = self.last_database_success.lock() {
+            *last = Some(Utc::now());
+        }
+    }
+
+    pub fn last_database_success(&self) -> Option<DateTime<Utc>> {
+        self.last_database_success.lock().ok().and_then(|last| *last)
+    }
+}
+
+/// Body of the health endpoint
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub gateway_connected: bool,
+    pub database_reachable: bool,
+    pub last_database_success: Option<DateTime<Utc>>,
+    pub migration_version: Option<i64>,
+    pub expected_migration_version: Option<i64>,
+}
+
+impl Bot {
+    /// Gets the newest migration applied to the database, also checking the database is reachable
+    pub async fn get_migration_version(&self) -> Result<Option<i64>> {
+        let version = sqlx::query_scalar::<_, Option<i64>>(
+            r#"
+            SELECT MAX(version) FROM _sqlx_migrations
+            WHERE success = 1
+            "#,
+        )
+        .fetch_one(&self.database)
+        .await?;
+
+        Ok(version)
+    }
+
+    /// Checks every part of the bot's health
+    pub async fn health_report(&self) -> HealthReport {
+        let expected_migration_version = MIGRATOR.iter().map(|migration| migration.version).max();
+
+        let migration_version =
+            match tokio::time::timeout(DATABASE_TIMEOUT, self.get_migration_version()).await {
+                Ok(Ok(version)) => Ok(version),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err("timed out".to_string()),
+            };
+
+        if let Err(e) = &migration_version {
+            warn!(error = e.as_str(), "health check couldn't reach the database");
+        }
+
+        let database_reachable = migration_version.is_ok();
+        let migration_version = migration_version.ok().flatten();
+        let gateway_connected = self.health.gateway_connected();
+
+        HealthReport {
+            healthy: gateway_connected
+                && database_reachable
+                && migration_version == expected_migration_version,
+            gateway_connected,
+            database_reachable,
+            last_database_success: self.health.last_database_success(),
+            migration_version,
+            expected_migration_version,
+        }
+    }
+}
+
+async fn health(State(bot): State<Bot>) -> (StatusCode, Json<HealthReport>) {
+    let report = bot.health_report().await;
+
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(report))
+}
+
+/// Routes for the health endpoint
+pub fn health_router(bot: Bot) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .with_state(bot)
+}
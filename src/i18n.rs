@@ -0,0 +1,134 @@
+//! Localization module for the bot
+//! Loads the translation files in `locales/` and looks up UI text by key
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::LazyLock;
+
+use serenity::all::CreateCommand;
+
+/// Language used when nothing else is set, every key must exist in it
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Supported languages as (code, native name)
+pub const LANGUAGES: &[(&str, &str)] = &[("en", "English"), ("es", "Español"), ("fr", "Français")];
+
+/// Translation files bundled into the binary
+const FILES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.toml")),
+    ("es", include_str!("../locales/es.toml")),
+    ("fr", include_str!("../locales/fr.toml")),
+];
+
+/// Discord locales each language is registered under for command localizations
+const DISCORD_LOCALES: &[(&str, &[&str])] = &[
+    ("en", &["en-US", "en-GB"]),
+    ("es", &["es-ES", "es-419"]),
+    ("fr", &["fr"]),
+];
+
+static TRANSLATIONS: LazyLock<HashMap<&'static str, toml::Table>> = LazyLock::new(|| {
+    FILES
+        .iter()
+        .map(|(language, file)| {
+            let table = file
+                .parse::<toml::Table>()
+                .unwrap_or_else(|e| panic!("Invalid translation file for {language}: {e}"));
+            (*language, table)
+        })
+        .collect()
+});
+
+/// Looks up a dotted key (e.g. `list.page`) in a language's translations
+fn lookup(language: &str, key: &str) -> Option<&'static str> {
+    let mut parts = key.split('.');
+    let mut value = TRANSLATIONS.get(language)?.get(parts.next()?)?;
+
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+
+    value.as_str()
+}
+
+/// Gets the translated text for a key
+/// Falls back to English, then to the key itself if it is missing
+///
+/// # Parameters
+/// * `language: &str` - Language code to translate into
+/// * `key: &str` - Dotted key of the text
+pub fn t(language: &str, key: &str) -> String {
+    lookup(language, key)
+        .or_else(|| lookup(DEFAULT_LANGUAGE, key))
+        .unwrap_or(key)
+        .to_string()
+}
+
+/// Gets the translated text for a key and fills in its `{name}` placeholders
+///
+/// # Parameters
+/// * `language: &str` - Language code to translate into
+/// * `key: &str` - Dotted key of the text
+/// * `args: &[(&str, &dyn Display)]` - Placeholder names and their values
+pub fn tf(language: &str, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter().fold(t(language, key), |text, (name, value)| {
+        text.replace(&format!("{{{name}}}"), &value.to_string())
+    })
+}
+
+/// Converts a Discord locale (e.g. `es-ES`) into a supported language code
+pub fn language_from_locale(locale: &str) -> Option<&'static str> {
+    let prefix = locale.split('-').next().unwrap_or(locale);
+
+    LANGUAGES
+        .iter()
+        .map(|(code, _)| *code)
+        .find(|code| code.eq_ignore_ascii_case(prefix))
+}
+
+/// Gets the language for a Discord locale, or the default if it isn't supported
+pub fn fallback_language(locale: &str) -> &'static str {
+    language_from_locale(locale).unwrap_or(DEFAULT_LANGUAGE)
+}
+
+/// Gets the native name of a supported language
+pub fn language_name(language: &str) -> &str {
+    LANGUAGES
+        .iter()
+        .find(|(code, _)| *code == language)
+        .map(|(_, name)| *name)
+        .unwrap_or(language)
+}
+
+/// Trait to add translated names and descriptions to commands
+pub trait CommandLocalizer {
+    fn localized(self, name: &str) -> CreateCommand;
+}
+
+impl CommandLocalizer for CreateCommand {
+    /// Sets the description from `commands.<name>.description` and adds the
+    /// name and description in every other language that has them
+    fn localized(self, name: &str) -> CreateCommand {
+        let name_key = format!("commands.{name}.name");
+        let description_key = format!("commands.{name}.description");
+
+        let mut command = self.description(t(DEFAULT_LANGUAGE, &description_key));
+
+        for (language, locales) in DISCORD_LOCALES {
+            if *language == DEFAULT_LANGUAGE {
+                continue;
+            }
+
+            for locale in *locales {
+                if let Some(name) = lookup(language, &name_key) {
+                    command = command.name_localized(*locale, name);
+                }
+                if let Some(description) = lookup(language, &description_key) {
+                    command = command.description_localized(*locale, description);
+                }
+            }
+        }
+
+        command
+    }
+}
@@ -1,76 +1,63 @@
-use serenity::all::{CreateInteractionResponse, CreateInteractionResponseMessage, GuildId};
+use serenity::all::{CreateActionRow, CreateInteractionResponse, GuildId, UserId};
+use tracing::warn;
 
-use crate::{bot::Bot, embed::{dare_button, embed_text, send_page, truth_button}, menu_type::MenuType, other_impl::FindMenuType, questions::QuestionType};
+use crate::{bot::Bot, error::Result, embed::{dare_button, embed_text, send_page, truth_button}, i18n::t, invocation::Reply, menu_type::MenuType, other_impl::MessageMaker, pagination::PageState, questions::QuestionType};
 
-pub async fn truth_or_dare(bot: &Bot, action: &str, guild_id: Option<GuildId>) -> CreateInteractionResponse {
+pub async fn truth_or_dare(bot: &Bot, action: &str, guild_id: Option<GuildId>, language: &str) -> Result<Reply> {
     let question_type: QuestionType = match action {
         "truth" => QuestionType::TRUTH,
         "dare" => QuestionType::DARE,
         _ => QuestionType::NONE,
     };
 
-    let response = CreateInteractionResponse::Message(
-        CreateInteractionResponseMessage::new()
-            .embed(
-                embed_text(
-                    &bot,
-                    question_type,
-                    bot.get_guild_rating(guild_id).await,
-                    guild_id
-                )
-                .await,
-            )
-            .button(truth_button())
-            .button(dare_button()),
-    );
-
-    response
+    Ok(Reply::embed(
+        embed_text(
+            bot,
+            question_type,
+            bot.get_guild_rating(guild_id).await?,
+            guild_id,
+            language
+        )
+        .await?,
+    )
+    .components(vec![CreateActionRow::Buttons(vec![truth_button(language), dare_button(language)])]))
 }
 
-/// Sends the next page of the menu
+/// Updates a list to the page a pagination button or select menu points to
 /// 
 /// # Parameters
 /// * `bot: &Bot` - Used to access the database
-/// * `interaction: &str` - The interaction sent by the client
+/// * `interaction: &str` - The id of the component the client used
+/// * `selected_page: Option<&str>` - The page picked in the page select menu, if it was used
 /// * `guild_id: Option<GuildId>` - Guild Id of the guild the interaction came from
-pub async fn next_page(bot: &Bot, interaction: &str, guild_id: Option<GuildId>) -> CreateInteractionResponse{
-    let end = interaction.find("-").unwrap();
-
-    let after_dash = &interaction[end+1..];
-    let num_str: String = after_dash.chars()
-        .take_while(|c| c.is_ascii_digit())
-        .collect();
-    let num: usize = num_str.parse().unwrap();
-    let menu_type = interaction.to_menu_type();
-
-    let questions = match menu_type {
-        MenuType::CUSTOM => bot.get_custom_questions(guild_id).await,
-        MenuType::DEFAULT => bot.get_questions(guild_id).await
-    };
-
-    send_page(num+1, questions, menu_type).await
+/// * `user_id: UserId` - Member who clicked, lists of their own questions are for them
+/// * `show_authors: bool` - Whether the member may see who added each question
+/// * `language: &str` - Language to display the page in
+pub async fn change_page(
+    bot: &Bot,
+    interaction: &str,
+    selected_page: Option<&str>,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+    show_authors: bool,
+    language: &str,
+) -> Result<CreateInteractionResponse> {
+    let state = PageState::decode(interaction, &bot.signing_key).map(|mut state| {
+        if state.menu_type == MenuType::MINE {
+            state.filter.author_id = Some(user_id);
+        }
+
+        match selected_page.and_then(|page| page.parse::<usize>().ok()) {
+            Some(page) => state.with_page(page),
+            None => state,
+        }
+    });
+
+    match state {
+        Ok(state) => Ok(send_page(bot, guild_id, state, show_authors, language).await?.to_update()),
+        Err(e) => {
+            warn!(error = %e, "rejected page id");
+            Ok(Reply::text(t(language, "list.expired")).ephemeral(true).to_interaction_message())
+        }
+    }
 }
-
-/// Sends the previous page of the menu
-/// 
-/// # Parameters
-/// * `bot: &Bot` - Used to access the database
-/// * `interaction: &str` - The interaction sent by the client
-/// * `guild_id: Option<GuildId>` - Guild Id of the guild the interaction came from
-pub async fn previous_page(bot: &Bot, interaction: &str, guild_id: Option<GuildId>) -> CreateInteractionResponse {
-    let end = interaction.find("-").unwrap();
-
-    let after_dash = &interaction[end+1..];
-    let num_str: String = after_dash.chars()
-        .take_while(|c| c.is_ascii_digit())
-        .collect();
-    let num: usize = num_str.parse().unwrap();
-    let menu_type = interaction.to_menu_type();
-
-    let questions = match menu_type {
-        MenuType::CUSTOM => bot.get_custom_questions(guild_id).await,
-        MenuType::DEFAULT => bot.get_questions(guild_id).await
-    };
-
-    send_page(num, questions, menu_type).await
-}
\ No newline at end of file
@@ -0,0 +1,205 @@
+//! Invocation module for the bot
+//! Slash commands and text commands are both turned into an `Invocation`, so every command
+//! handler works the same for both, and handlers answer with a `Reply` that either can send
+
+use serenity::all::{
+    ChannelId, CommandDataOption, CommandDataOptionValue, CommandInteraction, CreateActionRow, CreateAllowedMentions, CreateEmbed,
+    CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, CreateModal, GuildId,
+    Permissions, RoleId, UserId,
+};
+
+use crate::other_impl::MessageMaker;
+
+/// A command someone ran, with the options they gave it
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    /// Name of the command, e.g. `add_question`
+    pub name: String,
+    /// Subcommand that was picked, e.g. `set` for `/qotd set`
+    pub subcommand: Option<String>,
+    /// Options given to the command, or to the subcommand if one was picked
+    pub options: Vec<(String, CommandDataOptionValue)>,
+    pub guild_id: Option<GuildId>,
+    pub channel_id: ChannelId,
+    pub user_id: UserId,
+    /// Discord locale of the member, empty for text commands
+    pub locale: String,
+    /// Permissions of the member in the guild, `None` outside of guilds
+    pub permissions: Option<Permissions>,
+    /// Roles of the member in the guild, empty outside of guilds
+    pub roles: Vec<RoleId>,
+}
+
+impl Invocation {
+    /// Makes an invocation from a slash command
+    pub fn from_command(command: &CommandInteraction) -> Invocation {
+        let (subcommand, options) = match command.data.options.first() {
+            Some(CommandDataOption {
+                name,
+                value: CommandDataOptionValue::SubCommand(options),
+                ..
+            }) => (Some(name.clone()), options.as_slice()),
+            _ => (None, command.data.options.as_slice()),
+        };
+
+        Invocation {
+            name: command.data.name.clone(),
+            subcommand,
+            options: options
+                .iter()
+                .map(|option| (option.name.clone(), option.value.clone()))
+                .collect(),
+            guild_id: command.guild_id,
+            channel_id: command.channel_id,
+            user_id: command.user.id,
+            locale: command.locale.clone(),
+            permissions: command.member.as_ref().and_then(|member| member.permissions),
+            roles: command
+                .member
+                .as_ref()
+                .map(|member| member.roles.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Gets the value of an option, if it was given
+    pub fn option(&self, name: &str) -> Option<&CommandDataOptionValue> {
+        self.options
+            .iter()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Gets the value of a string option
+    pub fn str_option(&self, name: &str) -> Option<&str> {
+        self.option(name).and_then(|value| value.as_str())
+    }
+
+    /// Gets the value of an integer option
+    pub fn int_option(&self, name: &str) -> Option<i64> {
+        self.option(name).and_then(|value| value.as_i64())
+    }
+
+    /// Gets the value of a boolean option
+    pub fn bool_option(&self, name: &str) -> Option<bool> {
+        self.option(name).and_then(|value| value.as_bool())
+    }
+
+    /// Gets the value of a channel option
+    pub fn channel_option(&self, name: &str) -> Option<ChannelId> {
+        self.option(name).and_then(|value| value.as_channel_id())
+    }
+
+    /// Gets the value of a user option
+    pub fn user_option(&self, name: &str) -> Option<UserId> {
+        self.option(name).and_then(|value| value.as_user_id())
+    }
+
+    /// Gets the value of a role option
+    pub fn role_option(&self, name: &str) -> Option<RoleId> {
+        match self.option(name) {
+            Some(CommandDataOptionValue::Role(role_id)) => Some(*role_id),
+            _ => None,
+        }
+    }
+
+    /// Checks if the member has the Administrator permission
+    pub fn is_admin(&self) -> bool {
+        self.permissions
+            .map(|permissions| permissions.administrator())
+            .unwrap_or(false)
+    }
+}
+
+/// What a command answers with
+#[derive(Debug, Clone, Default)]
+pub struct Reply {
+    pub content: Option<String>,
+    pub embeds: Vec<CreateEmbed>,
+    pub components: Vec<CreateActionRow>,
+    /// Only show the reply to the member who ran the command, text commands can't do this
+    pub ephemeral: bool,
+    /// Form to open instead of answering, text commands send the rest of the reply since they can't open one
+    pub modal: Option<CreateModal>,
+}
+
+impl Reply {
+    /// Makes a reply that only has text
+    pub fn text(content: impl Into<String>) -> Reply {
+        Reply {
+            content: Some(content.into()),
+            ..Reply::default()
+        }
+    }
+
+    /// Makes a reply that only has an embed
+    pub fn embed(embed: CreateEmbed) -> Reply {
+        Reply {
+            embeds: vec![embed],
+            ..Reply::default()
+        }
+    }
+
+    /// Adds buttons or select menus to the reply
+    pub fn components(mut self, components: Vec<CreateActionRow>) -> Reply {
+        self.components = components;
+        self
+    }
+
+    /// Opens a form when the reply is sent to a slash command
+    pub fn modal(mut self, modal: CreateModal) -> Reply {
+        self.modal = Some(modal);
+        self
+    }
+
+    /// Sets if the reply is only shown to the member who ran the command
+    pub fn ephemeral(mut self, ephemeral: bool) -> Reply {
+        self.ephemeral = ephemeral;
+        self
+    }
+
+    /// Makes a response that replaces the message a component is on
+    /// Everything is set, so the text, embeds or components of the old message don't stay behind
+    pub fn to_update(&self) -> CreateInteractionResponse {
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(self.content.clone().unwrap_or_default())
+                .embeds(self.embeds.clone())
+                .components(self.components.clone()),
+        )
+    }
+}
+
+/// Replies never ping anyone, roles are mentioned by `/permissions` only to name them
+impl MessageMaker for Reply {
+    fn to_interaction_message(&self) -> CreateInteractionResponse {
+        if let Some(modal) = &self.modal {
+            return CreateInteractionResponse::Modal(modal.clone());
+        }
+
+        let mut message = CreateInteractionResponseMessage::new()
+            .embeds(self.embeds.clone())
+            .components(self.components.clone())
+            .ephemeral(self.ephemeral)
+            .allowed_mentions(CreateAllowedMentions::new());
+
+        if let Some(content) = &self.content {
+            message = message.content(content.as_str());
+        }
+
+        CreateInteractionResponse::Message(message)
+    }
+
+    fn to_message(&self) -> CreateMessage {
+        let mut message = CreateMessage::new()
+            .embeds(self.embeds.clone())
+            .components(self.components.clone())
+            .allowed_mentions(CreateAllowedMentions::new());
+
+        if let Some(content) = &self.content {
+            message = message.content(content.as_str());
+        }
+
+        message
+    }
+}
@@ -1,8 +1,33 @@
+pub mod audit;
+pub mod autocomplete;
 pub mod bot;
+pub mod bulk;
 pub mod commands;
+pub mod config;
+pub mod content_policy;
 pub mod embed;
+pub mod error;
 pub mod guild_settings;
+pub mod health;
+pub mod i18n;
 pub mod interactions;
+pub mod invocation;
+pub mod logging;
 pub mod menu_type;
+pub mod metrics;
 pub mod other_impl;
+pub mod packs;
+pub mod pagination;
+pub mod permissions;
+pub mod qotd;
+pub mod question_form;
+pub mod rate_limit;
 pub mod questions;
+pub mod registration;
+pub mod search;
+pub mod server;
+pub mod signing;
+pub mod submission;
+pub mod submission_limits;
+pub mod text_commands;
+pub mod trash;
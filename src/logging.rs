@@ -0,0 +1,23 @@
+//! Logging module for the bot
+//! Sets up `tracing` output, which is read from the environment so it works before the config loads
+
+use std::env;
+
+use tracing_subscriber::EnvFilter;
+
+/// Filter used when `RUST_LOG` isn't set, serenity is noisy at info
+const DEFAULT_FILTER: &str = "info,serenity=warn";
+
+/// Starts printing logs
+/// `LOG_FORMAT` picks the output: `pretty` (default), `compact`, or `json`,
+/// and `RUST_LOG` picks what is logged, e.g. `debug` or `info,sqlx=debug`
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match env::var("LOG_FORMAT").unwrap_or_default().to_lowercase().as_str() {
+        "json" => builder.json().with_current_span(true).with_span_list(true).init(),
+        "compact" => builder.compact().init(),
+        _ => builder.pretty().init(),
+    }
+}
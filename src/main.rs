@@ -1,51 +1,135 @@
 use std::env;
+use std::process::exit;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use dotenv::dotenv;
+use log::LevelFilter;
 use serenity::{all::GatewayIntents, Client};
-use truth_or_dare_bot::bot::Bot;
+use sqlx::{sqlite::SqliteConnectOptions, ConnectOptions};
+use tracing::error;
+use truth_or_dare_bot::{
+    audit::run_audit_mirror,
+    bot::Bot,
+    commands::create_commands,
+    config::Config,
+    health::{health_router, Health, MIGRATOR},
+    logging,
+    metrics::metrics_router,
+    qotd::run_scheduler,
+    rate_limit::RateLimiter,
+    server,
+    signing::signing_key,
+    text_commands::command_specs,
+    trash::run_trash_purge,
+};
 
 #[tokio::main]
 async fn main() {
     // Load environment variables from the .env file
     dotenv().ok();
 
-    unsafe {
-        env::set_var("DATABASE_URL", "sqlite://database.sqlite");
+    // Start logging before anything else can fail
+    logging::init();
+
+    // Load the settings, stopping with the reason if any are invalid
+    let mut config = Config::load().unwrap_or_else(|e| {
+        error!("Invalid configuration: {e}");
+        exit(1);
+    });
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--purge-stale-commands" => config.purge_stale_commands = true,
+            _ => {
+                error!("Unknown argument {arg:?}, the only option is --purge-stale-commands");
+                exit(1);
+            }
+        }
     }
 
     // Configure the client with your Discord bot token in the environment.
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
 
+    let connect_options = SqliteConnectOptions::from_str(&config.database_url)
+        .unwrap_or_else(|e| {
+            error!("Invalid configuration: database_url {:?}: {e}", config.database_url);
+            exit(1);
+        })
+        .create_if_missing(true)
+        // Every query is logged with its timing at debug, slow ones at warn
+        .log_statements(LevelFilter::Debug)
+        .log_slow_statements(LevelFilter::Warn, config.slow_query_threshold());
+
     // Initiate a connection to the database file, creating the file if required.
     let database = sqlx::sqlite::SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect_with(
-            sqlx::sqlite::SqliteConnectOptions::new()
-                .filename("database.sqlite")
-                .create_if_missing(true),
-        )
+        .max_connections(config.pool_size)
+        .connect_with(connect_options)
         .await
         .expect("Couldn't connect to database");
 
     // Run the database migrations to ensure the schema is up to date.
-    sqlx::migrate!("./migrations")
+    MIGRATOR
         .run(&database)
         .await
         .expect("Couldn't run database migrations");
 
+    // Routers for the HTTP endpoints that have an address set
+    let mut routers = Vec::new();
+    if let Some(addr) = config.metrics_addr {
+        routers.push((addr, metrics_router()));
+    }
+
+    // Reading message content is only needed for text commands
+    // They also need the guilds cached, to work out the permissions of the member from their roles
+    let mut intents = GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES;
+    if config.features.text_commands {
+        intents |= GatewayIntents::MESSAGE_CONTENT | GatewayIntents::GUILDS;
+    }
+
+    // Text commands are parsed with the definitions of the slash commands, read once here
+    let specs = command_specs(&create_commands(&config));
+
     // Create the bot instance with the database connection.
-    let bot = Bot { database };
+    let bot = Bot {
+        database,
+        signing_key: signing_key(config.signing_secret.as_deref(), &token),
+        config: Arc::new(config),
+        health: Arc::new(Health::default()),
+        rate_limiter: Arc::new(RateLimiter::default()),
+        command_specs: Arc::new(specs),
+    };
+    let scheduler_bot = bot.clone();
+    let audit_bot = bot.clone();
+    let trash_bot = bot.clone();
+
+    if let Some(addr) = bot.config.health_addr {
+        routers.push((addr, health_router(bot.clone())));
+    }
 
     // Create a new client with the bot token and intents, and set the event handler to the bot.
-    let intents = GatewayIntents::GUILD_MESSAGES
-        | GatewayIntents::DIRECT_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT;
     let mut client = Client::builder(&token, intents)
         .event_handler(bot)
         .await
         .expect("Err creating client");
 
+    // Start posting the question of the day alongside the client.
+    if scheduler_bot.config.features.qotd {
+        tokio::spawn(run_scheduler(scheduler_bot, client.http.clone()));
+    }
+
+    // Mirror audit entries to the log channels guilds picked
+    tokio::spawn(run_audit_mirror(audit_bot, client.http.clone()));
+
+    // Delete questions that were in the trash longer than the retention period
+    tokio::spawn(run_trash_purge(trash_bot));
+
+    // Serve the metrics and health endpoints
+    if !routers.is_empty() {
+        tokio::spawn(server::serve(routers));
+    }
+
     if let Err(error) = client.start().await {
-        eprintln!("Client error: {error:?}");
+        error!(error = %error, "client error");
     }
 }
@@ -1,7 +1,10 @@
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MenuType {
     CUSTOM,
     DEFAULT,
+    SEARCH,
+    /// Questions added by the member looking at the list
+    MINE,
 }
 
 pub trait MenuToStr {
@@ -12,7 +15,9 @@ impl MenuToStr for MenuType {
     fn to_str<'a>(self) -> &'a str {
         match  self {
             MenuType::CUSTOM => "CUSTOM",
-            MenuType::DEFAULT => "DEFAULT"
+            MenuType::DEFAULT => "DEFAULT",
+            MenuType::SEARCH => "SEARCH",
+            MenuType::MINE => "MINE"
         }
     }
 }
\ No newline at end of file
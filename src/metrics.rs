@@ -0,0 +1,115 @@
+//! Metrics module for the bot
+//! Counts what the bot does and serves it in the Prometheus format on `/metrics`
+
+use std::sync::LazyLock;
+
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use tracing::error;
+
+/// Buckets for handling times in seconds, Discord gives up on a response after 3
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Every metric the bot records
+pub struct Metrics {
+    registry: Registry,
+    /// Slash and text commands run, by command name
+    pub commands: IntCounterVec,
+    /// Buttons and select menus used, by component
+    pub components: IntCounterVec,
+    /// Questions drawn, by question type and rating
+    pub draws: IntCounterVec,
+    /// Database queries that failed
+    pub database_errors: IntCounter,
+    /// Responses that couldn't be sent to Discord, by kind of event
+    pub response_failures: IntCounterVec,
+    /// Time taken to handle an event, by kind of event and name
+    pub latency: HistogramVec,
+    /// Commands and buttons turned away by a rate limit, by the limit that was hit
+    pub rate_limited: IntCounterVec,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let registry = Registry::new_custom(Some("truth_or_dare".to_string()), None)
+        .expect("Metric prefix is valid");
+
+    let commands = IntCounterVec::new(
+        Opts::new("commands_total", "Commands run"),
+        &["command"],
+    )
+    .expect("Metric is valid");
+    let components = IntCounterVec::new(
+        Opts::new("components_total", "Buttons and select menus used"),
+        &["component"],
+    )
+    .expect("Metric is valid");
+    let draws = IntCounterVec::new(
+        Opts::new("draws_total", "Questions drawn"),
+        &["question_type", "rating"],
+    )
+    .expect("Metric is valid");
+    let database_errors = IntCounter::new("database_errors_total", "Database queries that failed")
+        .expect("Metric is valid");
+    let response_failures = IntCounterVec::new(
+        Opts::new("response_failures_total", "Responses that couldn't be sent to Discord"),
+        &["kind"],
+    )
+    .expect("Metric is valid");
+    let latency = HistogramVec::new(
+        HistogramOpts::new("handle_seconds", "Time taken to handle an event")
+            .buckets(LATENCY_BUCKETS.to_vec()),
+        &["kind", "name"],
+    )
+    .expect("Metric is valid");
+    let rate_limited = IntCounterVec::new(
+        Opts::new("rate_limited_total", "Commands and buttons turned away by a rate limit"),
+        &["scope"],
+    )
+    .expect("Metric is valid");
+
+    registry.register(Box::new(commands.clone())).expect("Metric is registered once");
+    registry.register(Box::new(components.clone())).expect("Metric is registered once");
+    registry.register(Box::new(draws.clone())).expect("Metric is registered once");
+    registry.register(Box::new(database_errors.clone())).expect("Metric is registered once");
+    registry.register(Box::new(response_failures.clone())).expect("Metric is registered once");
+    registry.register(Box::new(latency.clone())).expect("Metric is registered once");
+    registry.register(Box::new(rate_limited.clone())).expect("Metric is registered once");
+
+    Metrics {
+        registry,
+        commands,
+        components,
+        draws,
+        database_errors,
+        response_failures,
+        latency,
+        rate_limited,
+    }
+});
+
+impl Metrics {
+    /// Encodes every metric in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            error!(error = %e, "failed to encode metrics");
+        }
+
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+async fn metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        METRICS.encode(),
+    )
+}
+
+/// Routes for the metrics endpoint
+pub fn metrics_router() -> Router {
+    Router::new().route("/metrics", get(metrics))
+}
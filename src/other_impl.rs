@@ -2,8 +2,6 @@ use serenity::all::{
     CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
 };
 
-use crate::menu_type::MenuType;
-
 /// Trait to convert into a footer
 pub trait FooterMaker {
     fn to_footer(&self) -> CreateEmbedFooter;
@@ -20,11 +18,6 @@ pub trait EmbedMaker {
     fn to_embed(&self, title: impl AsRef<str>, footer: impl AsRef<str>) -> CreateEmbed;
 }
 
-/// Trait to convert to MenuType
-pub trait FindMenuType {
-    fn to_menu_type(&self) -> MenuType;
-}
-
 // Implementations for FooterMaker and MessageMaker traits for &str and String types
 impl FooterMaker for &str {
     fn to_footer(&self) -> CreateEmbedFooter {
@@ -105,27 +98,3 @@ impl EmbedMaker for String {
             .footer(CreateEmbedFooter::new(footer.as_ref()))
     }
 }
-
-impl FindMenuType for &str {
-    fn to_menu_type(&self) -> MenuType {
-        if self.contains("CUSTOM") {
-            MenuType::CUSTOM
-        } else if self.contains("DEFAULT") {
-            MenuType::DEFAULT
-        } else {
-            MenuType::DEFAULT
-        }
-    }
-}
-
-impl FindMenuType for String {
-    fn to_menu_type(&self) -> MenuType {
-        if self.contains("CUSTOM") {
-            MenuType::CUSTOM
-        } else if self.contains("DEFAULT") {
-            MenuType::DEFAULT
-        } else {
-            MenuType::DEFAULT
-        }
-    }
-}
\ No newline at end of file
@@ -0,0 +1,483 @@
+//! Packs module for the bot
+//! A guild can publish the approved questions it gave a tag as a pack, which other guilds subscribe to with
+//! the pack's share code. Subscribed packs are drawn from like the guild's own questions, but the questions
+//! stay in the publishing guild, so only it can edit, remove or retag them. Unsubscribing, or the publisher
+//! unpublishing the pack, takes them out of the pool again.
+//! Pack questions were checked and reviewed by the publishing guild, so subscribing trusts its moderators,
+//! but words on the subscriber's own blocklist still keep a pack question from being drawn there.
+
+use serenity::all::{CommandOptionType, CreateCommand, CreateCommandOption, GuildId, UserId};
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use uuid::Uuid;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    bot::Bot,
+    content_policy::find_term,
+    error::{Error, Result},
+    i18n::{t, tf, CommandLocalizer},
+    invocation::{Invocation, Reply},
+    questions::{normalize_tag, QuestionType, MAX_TAG_LENGTH},
+    submission::normalize,
+};
+
+/// Characters in a share code, like `9F2C41AB`
+const SHARE_CODE_LENGTH: usize = 8;
+
+/// Share codes tried before giving up on publishing, a code is only taken again once in billions
+const SHARE_CODE_ATTEMPTS: usize = 5;
+
+/// Most packs a guild can subscribe to
+const MAX_SUBSCRIPTIONS: i64 = 20;
+
+/// Columns of a pack, with how many questions it has and how many guilds subscribed to it
+const PACK_COLUMNS: &str = r#"
+    question_packs.*,
+    (
+        SELECT COUNT(*) FROM questions
+        WHERE questions.guild_id = question_packs.guild_id AND questions.tag = question_packs.tag
+            AND questions.status = 'approved' AND questions.deleted_at IS NULL
+    ) AS questions,
+    (SELECT COUNT(*) FROM pack_subscriptions WHERE pack_subscriptions.pack_id = question_packs.id) AS subscribers
+"#;
+
+/// A published pack of questions
+#[derive(Debug, Clone)]
+pub struct QuestionPack {
+    pub id: i64,
+    /// Guild that published the pack and owns its questions
+    pub guild_id: GuildId,
+    /// Tag of the questions in the pack, which is also its name
+    pub tag: String,
+    /// Code other guilds subscribe with
+    pub share_code: String,
+    /// Approved questions in the pack
+    pub questions: i64,
+    /// Guilds subscribed to the pack
+    pub subscribers: i64,
+}
+
+impl FromRow<'_, SqliteRow> for QuestionPack {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(QuestionPack {
+            id: row.try_get("id")?,
+            guild_id: GuildId::new(row.try_get::<i64, _>("guild_id")? as u64),
+            tag: row.try_get("tag")?,
+            share_code: row.try_get("share_code")?,
+            questions: row.try_get("questions")?,
+            subscribers: row.try_get("subscribers")?,
+        })
+    }
+}
+
+/// Makes a share code, which is sent uppercase and typed in any case
+fn new_share_code() -> String {
+    Uuid::new_v4().simple().to_string()[..SHARE_CODE_LENGTH].to_uppercase()
+}
+
+impl Bot {
+    /// Counts the approved questions of a guild with a tag, which publishing it as a pack would share
+    pub async fn count_pack_questions(&self, guild_id: GuildId, tag: &str) -> Result<i64> {
+        let count = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*) FROM questions
+            WHERE guild_id = ? AND tag = ? AND status = 'approved' AND deleted_at IS NULL
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(tag)
+        .fetch_one(&self.database)
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Publishes the questions of a guild with a tag as a pack
+    ///
+    /// # Parameters
+    /// * `guild_id: GuildId` - Guild publishing the pack
+    /// * `tag: &str` - Tag of the questions to share
+    /// * `user_id: UserId` - Member who published it
+    ///
+    /// # Returns
+    /// * `Option<QuestionPack>` - The new pack, `None` if the guild already published that tag
+    pub async fn publish_pack(&self, guild_id: GuildId, tag: &str, user_id: UserId) -> Result<Option<QuestionPack>> {
+        let mut attempts = 0;
+        let id = loop {
+            attempts += 1;
+            let inserted = sqlx::query_scalar::<_, i64>(
+                r#"
+                INSERT INTO question_packs (guild_id, tag, share_code, published_by)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT (guild_id, tag) DO NOTHING
+                RETURNING id
+                "#,
+            )
+            .bind(guild_id.get() as i64)
+            .bind(tag)
+            .bind(new_share_code())
+            .bind(user_id.get() as i64)
+            .fetch_optional(&self.database)
+            .await;
+
+            match inserted {
+                // The tag conflict is ignored above, so a unique violation means the share code is taken
+                Err(sqlx::Error::Database(error)) if error.is_unique_violation() && attempts < SHARE_CODE_ATTEMPTS => {
+                    continue;
+                }
+                inserted => break inserted?,
+            }
+        };
+
+        match id {
+            Some(id) => self.get_pack_by_id(id).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn get_pack_by_id(&self, id: i64) -> Result<Option<QuestionPack>> {
+        let pack = sqlx::query_as::<_, QuestionPack>(&format!("SELECT {PACK_COLUMNS} FROM question_packs WHERE id = ?"))
+            .bind(id)
+            .fetch_optional(&self.database)
+            .await?;
+
+        Ok(pack)
+    }
+
+    /// Gets a pack by its share code, ignoring case and spaces around it
+    pub async fn get_pack(&self, share_code: &str) -> Result<Option<QuestionPack>> {
+        let pack = sqlx::query_as::<_, QuestionPack>(&format!(
+            "SELECT {PACK_COLUMNS} FROM question_packs WHERE share_code = ?"
+        ))
+        .bind(share_code.trim().to_uppercase())
+        .fetch_optional(&self.database)
+        .await?;
+
+        Ok(pack)
+    }
+
+    /// Gets the pack a guild published for a tag
+    pub async fn get_guild_pack(&self, guild_id: GuildId, tag: &str) -> Result<Option<QuestionPack>> {
+        let pack = sqlx::query_as::<_, QuestionPack>(&format!(
+            "SELECT {PACK_COLUMNS} FROM question_packs WHERE guild_id = ? AND tag = ?"
+        ))
+        .bind(guild_id.get() as i64)
+        .bind(tag)
+        .fetch_optional(&self.database)
+        .await?;
+
+        Ok(pack)
+    }
+
+    /// Gets the packs a guild published, by tag
+    pub async fn get_published_packs(&self, guild_id: GuildId) -> Result<Vec<QuestionPack>> {
+        let packs = sqlx::query_as::<_, QuestionPack>(&format!(
+            "SELECT {PACK_COLUMNS} FROM question_packs WHERE guild_id = ? ORDER BY tag"
+        ))
+        .bind(guild_id.get() as i64)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(packs)
+    }
+
+    /// Gets the packs a guild subscribed to, by tag
+    pub async fn get_subscribed_packs(&self, guild_id: GuildId) -> Result<Vec<QuestionPack>> {
+        let packs = sqlx::query_as::<_, QuestionPack>(&format!(
+            r#"
+            SELECT {PACK_COLUMNS} FROM question_packs
+            JOIN pack_subscriptions ON pack_subscriptions.pack_id = question_packs.id
+            WHERE pack_subscriptions.guild_id = ?
+            ORDER BY question_packs.tag
+            "#
+        ))
+        .bind(guild_id.get() as i64)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(packs)
+    }
+
+    /// Deletes a pack along with every subscription to it
+    pub async fn unpublish_pack(&self, pack_id: i64) -> Result<()> {
+        let mut transaction = self.database.begin().await?;
+
+        sqlx::query("DELETE FROM pack_subscriptions WHERE pack_id = ?")
+            .bind(pack_id)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("DELETE FROM question_packs WHERE id = ?")
+            .bind(pack_id)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Subscribes a guild to a pack, returning false if it already was
+    pub async fn subscribe_pack(&self, guild_id: GuildId, pack_id: i64, user_id: UserId) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO pack_subscriptions (guild_id, pack_id, subscribed_by) VALUES (?, ?, ?)
+            ON CONFLICT (guild_id, pack_id) DO NOTHING
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(pack_id)
+        .bind(user_id.get() as i64)
+        .execute(&self.database)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Gets the ids of the questions in a guild's subscribed packs that use words on its blocklist
+    /// They passed the publisher's blocklist, not this guild's, so draws leave them out
+    ///
+    /// # Parameters
+    /// * `guild_id: GuildId` - Guild drawing the questions
+    /// * `question_type: QuestionType` - Type of question being drawn
+    /// * `rating: &str` - Rating being drawn
+    pub async fn get_blocked_pack_questions(
+        &self,
+        guild_id: GuildId,
+        question_type: QuestionType,
+        rating: &str,
+    ) -> Result<Vec<i64>> {
+        let blocklist = self.get_guild_blocklist(guild_id).await?;
+
+        if blocklist.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let questions = sqlx::query_as::<_, (i64, String)>(
+            r#"
+            SELECT questions.id, questions.prompt FROM questions
+            JOIN question_packs ON question_packs.guild_id = questions.guild_id AND question_packs.tag = questions.tag
+            JOIN pack_subscriptions ON pack_subscriptions.pack_id = question_packs.id
+            WHERE pack_subscriptions.guild_id = ? AND questions.question_type = ? AND questions.rating = ?
+                AND questions.status = 'approved' AND questions.deleted_at IS NULL
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(question_type.to_string())
+        .bind(rating)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(questions
+            .into_iter()
+            .filter(|(_, prompt)| find_term(&normalize(prompt), &blocklist).is_some())
+            .map(|(id, _)| id)
+            .collect())
+    }
+
+    /// Unsubscribes a guild from a pack, returning false if it wasn't subscribed
+    pub async fn unsubscribe_pack(&self, guild_id: GuildId, pack_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM pack_subscriptions WHERE guild_id = ? AND pack_id = ?")
+            .bind(guild_id.get() as i64)
+            .bind(pack_id)
+            .execute(&self.database)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Command to share questions with other servers and draw the questions they share
+pub fn packs_command() -> CreateCommand {
+    let tag_option = |description| {
+        CreateCommandOption::new(CommandOptionType::String, "tag", description)
+            .required(true)
+            .max_length(MAX_TAG_LENGTH)
+    };
+    let code_option = |description| {
+        CreateCommandOption::new(CommandOptionType::String, "code", description)
+            .required(true)
+            .max_length(SHARE_CODE_LENGTH as u16)
+    };
+
+    CreateCommand::new("packs")
+        .localized("packs")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "publish",
+                "Share the questions with a tag as a pack other servers can subscribe to",
+            )
+            .add_sub_option(tag_option("The tag of the questions to share")),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "unpublish", "Stop sharing a pack")
+                .add_sub_option(tag_option("The tag of the pack")),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "subscribe", "Draw the questions of another server's pack")
+                .add_sub_option(code_option("The share code of the pack")),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "unsubscribe", "Stop drawing the questions of a pack")
+                .add_sub_option(code_option("The share code of the pack")),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "list",
+            "Show the packs this server shares and subscribed to",
+        ))
+}
+
+pub async fn packs(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+    let tag = invocation.str_option("tag").and_then(normalize_tag).unwrap_or_default();
+    let code = invocation.str_option("code").unwrap_or_default();
+
+    match invocation.subcommand.as_deref() {
+        Some("publish") => {
+            let count = bot.count_pack_questions(guild_id, &tag).await?;
+
+            // Only approved questions are shared, so a pack of questions still waiting for review would be empty
+            if count == 0 {
+                return Err(Error::InvalidInput(tf(language, "packs.no_questions", &[("tag", &tag)])));
+            }
+
+            let Some(pack) = bot.publish_pack(guild_id, &tag, invocation.user_id).await? else {
+                let code = bot
+                    .get_guild_pack(guild_id, &tag)
+                    .await?
+                    .map(|pack| pack.share_code)
+                    .unwrap_or_default();
+
+                return Err(Error::InvalidInput(tf(
+                    language,
+                    "packs.already_published",
+                    &[("tag", &tag), ("code", &code)],
+                )));
+            };
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::PublishPack) {
+                bot.record_audit(entry.target(&pack.tag).after(Some(&pack.share_code))).await;
+            }
+
+            Ok(Reply::text(tf(
+                language,
+                "packs.published",
+                &[("tag", &pack.tag), ("count", &pack.questions), ("code", &pack.share_code)],
+            )))
+        }
+        Some("unpublish") => {
+            let Some(pack) = bot.get_guild_pack(guild_id, &tag).await? else {
+                return Err(Error::InvalidInput(tf(language, "packs.not_published", &[("tag", &tag)])));
+            };
+
+            bot.unpublish_pack(pack.id).await?;
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::UnpublishPack) {
+                bot.record_audit(entry.target(&pack.tag).before(Some(&pack.share_code))).await;
+            }
+
+            Ok(Reply::text(tf(
+                language,
+                "packs.unpublished",
+                &[("tag", &pack.tag), ("subscribers", &pack.subscribers)],
+            )))
+        }
+        Some("subscribe") => {
+            let Some(pack) = bot.get_pack(code).await? else {
+                return Err(Error::InvalidInput(tf(language, "packs.not_found", &[("code", &code.trim())])));
+            };
+
+            if pack.guild_id == guild_id {
+                return Err(Error::InvalidInput(t(language, "packs.own_pack")));
+            }
+
+            if bot.get_subscribed_packs(guild_id).await?.len() as i64 >= MAX_SUBSCRIPTIONS {
+                return Err(Error::InvalidInput(tf(language, "packs.too_many", &[("max", &MAX_SUBSCRIPTIONS)])));
+            }
+
+            if !bot.subscribe_pack(guild_id, pack.id, invocation.user_id).await? {
+                return Err(Error::InvalidInput(tf(language, "packs.already_subscribed", &[("tag", &pack.tag)])));
+            }
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::SubscribePack) {
+                bot.record_audit(entry.target(&pack.share_code).after(Some(&pack.tag))).await;
+            }
+
+            Ok(Reply::text(tf(
+                language,
+                "packs.subscribed",
+                &[("tag", &pack.tag), ("count", &pack.questions)],
+            )))
+        }
+        Some("unsubscribe") => {
+            let not_subscribed = || Error::InvalidInput(tf(language, "packs.not_subscribed", &[("code", &code.trim())]));
+
+            let Some(pack) = bot.get_pack(code).await? else {
+                return Err(not_subscribed());
+            };
+
+            if !bot.unsubscribe_pack(guild_id, pack.id).await? {
+                return Err(not_subscribed());
+            }
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::UnsubscribePack) {
+                bot.record_audit(entry.target(&pack.share_code).before(Some(&pack.tag))).await;
+            }
+
+            Ok(Reply::text(tf(language, "packs.unsubscribed", &[("tag", &pack.tag)])))
+        }
+        Some("list") => {
+            let published = bot.get_published_packs(guild_id).await?;
+            let subscribed = bot.get_subscribed_packs(guild_id).await?;
+
+            if published.is_empty() && subscribed.is_empty() {
+                return Ok(Reply::text(t(language, "packs.none")).ephemeral(true));
+            }
+
+            let mut sections = Vec::new();
+
+            if !published.is_empty() {
+                let lines: Vec<String> = published
+                    .iter()
+                    .map(|pack| {
+                        tf(
+                            language,
+                            "packs.published_line",
+                            &[
+                                ("code", &pack.share_code),
+                                ("tag", &pack.tag),
+                                ("count", &pack.questions),
+                                ("subscribers", &pack.subscribers),
+                            ],
+                        )
+                    })
+                    .collect();
+
+                sections.push(tf(language, "packs.published_list", &[("packs", &lines.join("\n"))]));
+            }
+
+            if !subscribed.is_empty() {
+                let lines: Vec<String> = subscribed
+                    .iter()
+                    .map(|pack| {
+                        tf(
+                            language,
+                            "packs.subscribed_line",
+                            &[("code", &pack.share_code), ("tag", &pack.tag), ("count", &pack.questions)],
+                        )
+                    })
+                    .collect();
+
+                sections.push(tf(language, "packs.subscribed_list", &[("packs", &lines.join("\n"))]));
+            }
+
+            Ok(Reply::text(sections.join("\n\n")).ephemeral(true))
+        }
+        _ => Err(Error::InvalidInput(t(language, "common.unknown"))),
+    }
+}
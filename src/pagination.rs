@@ -0,0 +1,490 @@
+//! Pagination module for the bot
+//! The whole state of a paginated list is encoded into its button ids, so any page
+//! can be rebuilt from the id alone without storing anything between clicks.
+//!
+//! Ids look like `pg1.n.3.C.T.p.1.o.<signature>.<query>`, which is the prefix and version,
+//! button slot, page, menu type, question type, rating, custom only, sort, signature,
+//! and the search query last so it can contain `.`
+
+use std::fmt;
+use std::str::FromStr;
+
+use serenity::all::{GuildId, UserId};
+
+use crate::{
+    bot::Bot,
+    error,
+    menu_type::MenuType,
+    questions::{Question, QuestionType},
+    signing::{sign, verify},
+};
+
+/// Prefix of every pagination id
+pub const PREFIX: &str = "pg";
+
+/// Version of the id format, bump it when the layout changes
+pub const VERSION: u8 = 1;
+
+/// Longest id Discord accepts for a component
+const MAX_ID_LENGTH: usize = 100;
+
+/// Highest page number an id may point to
+const MAX_PAGE: usize = 100_000;
+
+/// Number of `.` separated fields in an id
+const FIELDS: usize = 10;
+
+/// Order questions are listed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Oldest,
+    Newest,
+    Alphabetical,
+    /// Best search match first, lists without a query fall back to oldest
+    Relevance,
+}
+
+impl SortOrder {
+    fn code(self) -> char {
+        match self {
+            SortOrder::Oldest => 'o',
+            SortOrder::Newest => 'n',
+            SortOrder::Alphabetical => 'a',
+            SortOrder::Relevance => 'r',
+        }
+    }
+
+    fn from_code(code: &str) -> Option<SortOrder> {
+        match code {
+            "o" => Some(SortOrder::Oldest),
+            "n" => Some(SortOrder::Newest),
+            "a" => Some(SortOrder::Alphabetical),
+            "r" => Some(SortOrder::Relevance),
+            _ => None,
+        }
+    }
+
+    /// Gets the `ORDER BY` clause for the sort
+    fn sql(self, has_query: bool) -> &'static str {
+        match self {
+            SortOrder::Newest => "q.id DESC",
+            SortOrder::Alphabetical => "q.prompt COLLATE NOCASE, q.id",
+            SortOrder::Relevance if has_query => "f.rank, q.id",
+            SortOrder::Oldest | SortOrder::Relevance => "q.id",
+        }
+    }
+}
+
+/// Get SortOrder from a command option value
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "oldest" => Ok(SortOrder::Oldest),
+            "newest" => Ok(SortOrder::Newest),
+            "alphabetical" => Ok(SortOrder::Alphabetical),
+            "relevance" => Ok(SortOrder::Relevance),
+            _ => Err(format!("Unknown sort order: {input}")),
+        }
+    }
+}
+
+/// Filters applied to a list of questions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListFilter {
+    pub question_type: Option<QuestionType>,
+    pub rating: Option<String>,
+    pub custom_only: bool,
+    pub query: Option<String>,
+    /// Only list questions this member added, pending ones included
+    /// Not part of page ids, `MenuType::MINE` lists are for whoever clicks the button
+    pub author_id: Option<UserId>,
+}
+
+impl ListFilter {
+    /// Converts the query into an FTS5 match expression
+    /// Each word is quoted so FTS syntax in the query is searched for literally,
+    /// and matched as a prefix so partial words still find results
+    pub fn match_expression(&self) -> Option<String> {
+        let terms: Vec<String> = self
+            .query
+            .as_deref()?
+            .split_whitespace()
+            .map(|term| term.replace('"', ""))
+            .filter(|term| !term.is_empty())
+            .map(|term| format!("\"{term}\"*"))
+            .collect();
+
+        if terms.is_empty() {
+            None
+        } else {
+            Some(terms.join(" "))
+        }
+    }
+}
+
+/// Everything needed to rebuild a page of a list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageState {
+    pub page: usize,
+    pub menu_type: MenuType,
+    pub filter: ListFilter,
+    pub sort: SortOrder,
+}
+
+/// Reasons a pagination id can be rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageStateError {
+    /// The id isn't a pagination id
+    Prefix,
+    /// The id was made by a different version of the format
+    Version,
+    /// A field is missing or has an invalid value
+    Malformed,
+    /// The signature doesn't match the contents
+    Signature,
+    /// The encoded id is longer than Discord allows
+    TooLong,
+}
+
+impl fmt::Display for PageStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            PageStateError::Prefix => "not a pagination id",
+            PageStateError::Version => "unsupported pagination id version",
+            PageStateError::Malformed => "malformed pagination id",
+            PageStateError::Signature => "invalid pagination id signature",
+            PageStateError::TooLong => "pagination id is too long",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::error::Error for PageStateError {}
+
+/// Checks if a component id belongs to a paginated list
+pub fn is_page_id(id: &str) -> bool {
+    id.starts_with(PREFIX)
+}
+
+impl PageState {
+    /// Makes the state for the first page of a list
+    pub fn new(menu_type: MenuType, filter: ListFilter, sort: SortOrder) -> PageState {
+        PageState {
+            page: 1,
+            menu_type,
+            filter,
+            sort,
+        }
+    }
+
+    /// Copies the state, pointing at another page
+    pub fn with_page(&self, page: usize) -> PageState {
+        PageState {
+            page,
+            ..self.clone()
+        }
+    }
+
+    /// Encodes the state into a signed component id
+    ///
+    /// # Parameters
+    /// * `slot: char` - Which button the id is for, ids must be unique within a message
+    /// * `key: &[u8]` - Key to sign the id with
+    pub fn encode(&self, slot: char, key: &[u8]) -> Result<String, PageStateError> {
+        let menu_type = match self.menu_type {
+            MenuType::CUSTOM => 'C',
+            MenuType::DEFAULT => 'D',
+            MenuType::SEARCH => 'S',
+            MenuType::MINE => 'M',
+        };
+        let question_type = match self.filter.question_type {
+            Some(QuestionType::TRUTH) => 'T',
+            Some(QuestionType::DARE) => 'D',
+            _ => '-',
+        };
+        let rating = match self.filter.rating.as_deref() {
+            Some("PG") => 'p',
+            Some("PG-13") => 't',
+            _ => '-',
+        };
+        let custom_only = if self.filter.custom_only { '1' } else { '0' };
+
+        let header = format!(
+            "{PREFIX}{VERSION}.{slot}.{}.{menu_type}.{question_type}.{rating}.{custom_only}.{}",
+            self.page,
+            self.sort.code()
+        );
+        let query = self.filter.query.as_deref().unwrap_or("");
+        let signature = sign(key, &format!("{header}.{query}"));
+
+        let id = format!("{header}.{signature}.{query}");
+
+        if id.chars().count() > MAX_ID_LENGTH {
+            Err(PageStateError::TooLong)
+        } else {
+            Ok(id)
+        }
+    }
+
+    /// Decodes and verifies a component id made by `encode`
+    pub fn decode(id: &str, key: &[u8]) -> Result<PageState, PageStateError> {
+        let version = id
+            .strip_prefix(PREFIX)
+            .ok_or(PageStateError::Prefix)?
+            .split('.')
+            .next()
+            .and_then(|version| version.parse::<u8>().ok())
+            .ok_or(PageStateError::Malformed)?;
+
+        if version != VERSION {
+            return Err(PageStateError::Version);
+        }
+
+        let fields: Vec<&str> = id.splitn(FIELDS, '.').collect();
+        if fields.len() != FIELDS {
+            return Err(PageStateError::Malformed);
+        }
+
+        // Check the signature before trusting any of the fields
+        let header = fields[..8].join(".");
+        let query = fields[9];
+        if !verify(key, &format!("{header}.{query}"), fields[8]) {
+            return Err(PageStateError::Signature);
+        }
+
+        let page = fields[2]
+            .parse::<usize>()
+            .ok()
+            .filter(|page| (1..=MAX_PAGE).contains(page))
+            .ok_or(PageStateError::Malformed)?;
+        let menu_type = match fields[3] {
+            "C" => MenuType::CUSTOM,
+            "D" => MenuType::DEFAULT,
+            "S" => MenuType::SEARCH,
+            "M" => MenuType::MINE,
+            _ => return Err(PageStateError::Malformed),
+        };
+        let question_type = match fields[4] {
+            "T" => Some(QuestionType::TRUTH),
+            "D" => Some(QuestionType::DARE),
+            "-" => None,
+            _ => return Err(PageStateError::Malformed),
+        };
+        let rating = match fields[5] {
+            "p" => Some("PG".to_string()),
+            "t" => Some("PG-13".to_string()),
+            "-" => None,
+            _ => return Err(PageStateError::Malformed),
+        };
+        let custom_only = match fields[6] {
+            "1" => true,
+            "0" => false,
+            _ => return Err(PageStateError::Malformed),
+        };
+        let sort = SortOrder::from_code(fields[7]).ok_or(PageStateError::Malformed)?;
+
+        Ok(PageState {
+            page,
+            menu_type,
+            filter: ListFilter {
+                question_type,
+                rating,
+                custom_only,
+                query: (!query.is_empty()).then(|| query.to_string()),
+                author_id: None,
+            },
+            sort,
+        })
+    }
+}
+
+impl Bot {
+    /// Counts the questions in a list
+    ///
+    /// # Parameters
+    /// * `guild_id: GuildId` - Guild to list the questions of
+    /// * `filter: &ListFilter` - Filters to apply
+    pub async fn count_questions(&self, guild_id: GuildId, filter: &ListFilter) -> error::Result<i64> {
+        let expression = filter.match_expression();
+
+        let query = format!(
+            r#"
+            SELECT COUNT(*) FROM questions q
+            {}
+            "#,
+            list_conditions(expression.is_some())
+        );
+
+        let total = sqlx::query_scalar::<_, i64>(&query)
+            .bind(guild_id.get() as i64)
+            .bind(filter.custom_only)
+            .bind(filter.question_type.map(|t| t.to_string()))
+            .bind(&filter.rating)
+            .bind(expression)
+            .bind(filter.author_id.map(|author_id| author_id.get() as i64))
+            .fetch_one(&self.database)
+            .await?;
+
+        Ok(total)
+    }
+
+    /// Gets the questions on a page of a list
+    ///
+    /// # Parameters
+    /// * `guild_id: GuildId` - Guild to list the questions of
+    /// * `state: &PageState` - Page, filters and sort of the list
+    pub async fn get_question_page(&self, guild_id: GuildId, state: &PageState) -> error::Result<Vec<Question>> {
+        let expression = state.filter.match_expression();
+
+        let query = format!(
+            r#"
+            SELECT q.* FROM questions q
+            {}
+            ORDER BY {}
+            LIMIT ?7 OFFSET ?8
+            "#,
+            list_conditions(expression.is_some()),
+            state.sort.sql(expression.is_some())
+        );
+
+        let questions = sqlx::query_as::<_, Question>(&query)
+            .bind(guild_id.get() as i64)
+            .bind(state.filter.custom_only)
+            .bind(state.filter.question_type.map(|t| t.to_string()))
+            .bind(&state.filter.rating)
+            .bind(expression)
+            .bind(state.filter.author_id.map(|author_id| author_id.get() as i64))
+            .bind(self.config.page_size)
+            .bind((state.page.saturating_sub(1) as i64) * self.config.page_size)
+            .fetch_all(&self.database)
+            .await?;
+
+        Ok(questions)
+    }
+}
+
+/// Gets the join and `WHERE` clause shared by the list queries
+/// Binds are the guild id, custom only, question type, rating, FTS expression and author
+/// Questions waiting for review are only listed for their author, and questions in the trash aren't listed
+fn list_conditions(has_query: bool) -> &'static str {
+    if has_query {
+        r#"
+            JOIN questions_fts f ON f.rowid = q.id
+            WHERE (q.guild_id = ?1 OR (?2 = 0 AND q.guild_id IS NULL))
+                AND (?3 IS NULL OR q.question_type = ?3)
+                AND (?4 IS NULL OR q.rating = ?4)
+                AND questions_fts MATCH ?5
+                AND (?6 IS NULL OR q.author_id = ?6)
+                AND (q.status = 'approved' OR ?6 IS NOT NULL)
+                AND q.deleted_at IS NULL
+        "#
+    } else {
+        r#"
+            WHERE (q.guild_id = ?1 OR (?2 = 0 AND q.guild_id IS NULL))
+                AND (?3 IS NULL OR q.question_type = ?3)
+                AND (?4 IS NULL OR q.rating = ?4)
+                AND ?5 IS NULL
+                AND (?6 IS NULL OR q.author_id = ?6)
+                AND (q.status = 'approved' OR ?6 IS NOT NULL)
+                AND q.deleted_at IS NULL
+        "#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"test key";
+
+    fn state() -> PageState {
+        PageState::new(
+            MenuType::SEARCH,
+            ListFilter {
+                question_type: Some(QuestionType::DARE),
+                rating: Some("PG-13".to_string()),
+                custom_only: true,
+                query: Some("never. have.I".to_string()),
+                author_id: None,
+            },
+            SortOrder::Relevance,
+        )
+        .with_page(3)
+    }
+
+    /// Replaces one `.` separated field of an id
+    fn replace_field(id: &str, index: usize, value: &str) -> String {
+        let mut fields: Vec<&str> = id.splitn(FIELDS, '.').collect();
+        fields[index] = value;
+        fields.join(".")
+    }
+
+    #[test]
+    fn round_trip() {
+        let state = state();
+        let id = state.encode('n', KEY).unwrap();
+
+        assert!(is_page_id(&id));
+        assert_eq!(PageState::decode(&id, KEY), Ok(state));
+
+        let empty = PageState::new(MenuType::DEFAULT, ListFilter::default(), SortOrder::Oldest);
+        let id = empty.encode('p', KEY).unwrap();
+        assert_eq!(PageState::decode(&id, KEY), Ok(empty));
+    }
+
+    #[test]
+    fn tampered_signature() {
+        let id = state().encode('n', KEY).unwrap();
+
+        assert_eq!(PageState::decode(&id, b"other key"), Err(PageStateError::Signature));
+        assert_eq!(
+            PageState::decode(&replace_field(&id, 2, "4"), KEY),
+            Err(PageStateError::Signature)
+        );
+        assert_eq!(
+            PageState::decode(&replace_field(&id, 9, "something else"), KEY),
+            Err(PageStateError::Signature)
+        );
+        assert_eq!(
+            PageState::decode(&replace_field(&id, 8, "000000000000"), KEY),
+            Err(PageStateError::Signature)
+        );
+    }
+
+    #[test]
+    fn wrong_version() {
+        let id = state().encode('n', KEY).unwrap();
+        let id = id.replacen(&format!("{PREFIX}{VERSION}."), &format!("{PREFIX}{}.", VERSION + 1), 1);
+
+        assert_eq!(PageState::decode(&id, KEY), Err(PageStateError::Version));
+        assert_eq!(PageState::decode("next_page-3", KEY), Err(PageStateError::Prefix));
+    }
+
+    #[test]
+    fn too_long() {
+        let mut state = state();
+        state.filter.query = Some("a".repeat(MAX_ID_LENGTH));
+
+        assert_eq!(state.encode('n', KEY), Err(PageStateError::TooLong));
+    }
+
+    #[test]
+    fn malformed_fields() {
+        let id = state().encode('n', KEY).unwrap();
+
+        assert_eq!(PageState::decode("pgx.n.1", KEY), Err(PageStateError::Malformed));
+        assert_eq!(PageState::decode(&format!("{PREFIX}{VERSION}.n.1.C"), KEY), Err(PageStateError::Malformed));
+
+        // Signed ids with values the format doesn't allow are still rejected
+        for page in [0, MAX_PAGE + 1] {
+            let id = state().with_page(page).encode('n', KEY).unwrap();
+            assert_eq!(PageState::decode(&id, KEY), Err(PageStateError::Malformed));
+        }
+
+        let header = replace_field(&id, 3, "X").splitn(FIELDS, '.').take(8).collect::<Vec<_>>().join(".");
+        let forged = format!("{header}.{}.", sign(KEY, &format!("{header}.")));
+        assert_eq!(PageState::decode(&forged, KEY), Err(PageStateError::Malformed));
+    }
+}
@@ -0,0 +1,316 @@
+//! Permissions module for the bot
+//! Maps what the bot lets members do to capabilities, and lets each server grant those
+//! capabilities to roles with `/permissions`. Every button and most commands are checked here
+//! before they run, editing, removing and restoring questions are checked by their handlers since
+//! authors can always change their own questions.
+//!
+//! Administrators can always do everything. When a server hasn't granted a capability to any
+//! role, the default applies: everyone can play and submit questions, the rest is admin only.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serenity::all::{
+    CommandOptionType, CreateCommand, CreateCommandOption, GuildId, RoleId, UserId,
+};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    bot::Bot,
+    error::{Error, Result},
+    i18n::{t, tf, CommandLocalizer},
+    invocation::{Invocation, Reply},
+    questions::Question,
+};
+
+/// Something members can be allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Add questions
+    Submit,
+    /// Change questions that were already added
+    Edit,
+    /// Remove questions
+    Remove,
+    /// Review questions held by the content policy and manage the blocklist
+    Moderate,
+    /// Change the settings of the server
+    Settings,
+    /// Draw questions and browse the lists
+    Play,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 6] = [
+        Capability::Submit,
+        Capability::Edit,
+        Capability::Remove,
+        Capability::Moderate,
+        Capability::Settings,
+        Capability::Play,
+    ];
+
+    /// Name used in commands and the database
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::Submit => "submit",
+            Capability::Edit => "edit",
+            Capability::Remove => "remove",
+            Capability::Moderate => "moderate",
+            Capability::Settings => "settings",
+            Capability::Play => "play",
+        }
+    }
+
+    /// Whether members without the Administrator permission can do this when no role was granted it
+    pub fn open_by_default(&self) -> bool {
+        matches!(self, Capability::Submit | Capability::Play)
+    }
+
+    /// Gets the capability needed to run a command, `None` if anyone can run it
+    /// Editing, removing and restoring questions are checked by their handlers, since authors can always change their own,
+    /// and so are `/bulk`, which needs a different capability for each subcommand, and `/question_form`, which adds or edits
+    pub fn for_command(name: &str) -> Option<Capability> {
+        match name {
+            "add_question" => Some(Capability::Submit),
+            "review" | "blocklist" | "audit" | "submissions" | "trash" => Some(Capability::Moderate),
+            "set_rating" | "set_question_permissions" | "set_language" | "set_prefix" | "set_rate_limit"
+            | "qotd" | "permissions" | "packs" => Some(Capability::Settings),
+            "truth" | "dare" | "list_questions" | "list_custom_questions" | "search_questions" | "my_questions" => {
+                Some(Capability::Play)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Capability::ALL.into_iter().find(|capability| capability.name() == s).ok_or(())
+    }
+}
+
+/// Who is trying to do something, as far as permissions go
+#[derive(Debug, Clone, Copy)]
+pub struct Member<'a> {
+    pub guild_id: Option<GuildId>,
+    pub user_id: UserId,
+    pub roles: &'a [RoleId],
+    pub is_admin: bool,
+}
+
+impl<'a> From<&'a Invocation> for Member<'a> {
+    fn from(invocation: &'a Invocation) -> Self {
+        Member {
+            guild_id: invocation.guild_id,
+            user_id: invocation.user_id,
+            roles: &invocation.roles,
+            is_admin: invocation.is_admin(),
+        }
+    }
+}
+
+impl Bot {
+    /// Checks a member has a capability
+    /// Outside of servers there is nothing to manage, so only playing is allowed
+    pub async fn require(&self, member: Member<'_>, capability: Capability) -> Result<()> {
+        let Some(guild_id) = member.guild_id else {
+            return match capability {
+                Capability::Play => Ok(()),
+                _ => Err(Error::GuildOnly),
+            };
+        };
+
+        if member.is_admin {
+            return Ok(());
+        }
+
+        let granted = self.get_capability_roles(guild_id, capability).await?;
+
+        let allowed = match granted.is_empty() {
+            true => self.open_without_roles(guild_id, capability).await?,
+            false => member.roles.iter().any(|role| granted.contains(role)),
+        };
+
+        match allowed {
+            true => Ok(()),
+            false => Err(Error::MissingCapability(capability)),
+        }
+    }
+
+    /// Checks if everyone has a capability when no role was granted it
+    /// Adding questions also follows the older admin-only setting of the guild
+    async fn open_without_roles(&self, guild_id: GuildId, capability: Capability) -> Result<bool> {
+        match capability {
+            Capability::Submit => Ok(!self.get_guild_question_permissions(Some(guild_id)).await?),
+            _ => Ok(capability.open_by_default()),
+        }
+    }
+
+    /// Checks a member can change a question, which its author always can
+    pub async fn require_for_question(
+        &self,
+        member: Member<'_>,
+        capability: Capability,
+        question: &Question,
+    ) -> Result<()> {
+        match question.author() == Some(member.user_id) {
+            true => Ok(()),
+            false => self.require(member, capability).await,
+        }
+    }
+
+    /// Gets the roles a server granted a capability to
+    pub async fn get_capability_roles(&self, guild_id: GuildId, capability: Capability) -> Result<Vec<RoleId>> {
+        let roles = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT role_id FROM guild_role_capabilities
+            WHERE guild_id = ? AND capability = ?
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(capability.name())
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(roles.into_iter().map(|role| RoleId::new(role as u64)).collect())
+    }
+
+    pub async fn grant_capability(&self, guild_id: GuildId, role_id: RoleId, capability: Capability) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO guild_role_capabilities (guild_id, role_id, capability)
+            VALUES (?, ?, ?)
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(role_id.get() as i64)
+        .bind(capability.name())
+        .execute(&self.database)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Takes a capability away from a role, returning false if the role didn't have it
+    pub async fn revoke_capability(&self, guild_id: GuildId, role_id: RoleId, capability: Capability) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM guild_role_capabilities
+            WHERE guild_id = ? AND role_id = ? AND capability = ?
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(role_id.get() as i64)
+        .bind(capability.name())
+        .execute(&self.database)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Command to grant capabilities to roles
+pub fn permissions_command() -> CreateCommand {
+    let capability_option = || {
+        Capability::ALL.iter().fold(
+            CreateCommandOption::new(CommandOptionType::String, "capability", "What the role can do").required(true),
+            |option, capability| option.add_string_choice(capability.name(), capability.name()),
+        )
+    };
+    let role_option = || CreateCommandOption::new(CommandOptionType::Role, "role", "The role").required(true);
+
+    CreateCommand::new("permissions")
+        .localized("permissions")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "grant", "Let a role do something")
+                .add_sub_option(role_option())
+                .add_sub_option(capability_option()),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "revoke", "Stop letting a role do something")
+                .add_sub_option(role_option())
+                .add_sub_option(capability_option()),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "show",
+            "Show which roles can do what",
+        ))
+}
+
+pub async fn permissions(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+    let role_id = invocation.role_option("role");
+    let capability = invocation
+        .str_option("capability")
+        .and_then(|capability| Capability::from_str(capability).ok());
+
+    match (invocation.subcommand.as_deref(), role_id, capability) {
+        (Some("grant"), Some(role_id), Some(capability)) => {
+            bot.grant_capability(guild_id, role_id, capability).await?;
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::GrantCapability) {
+                bot.record_audit(entry.target(capability).after(Some(format!("<@&{role_id}>")))).await;
+            }
+
+            Ok(Reply::text(tf(
+                language,
+                "permissions.granted",
+                &[("role", &format!("<@&{role_id}>")), ("capability", &capability_name(capability, language))],
+            )))
+        }
+        (Some("revoke"), Some(role_id), Some(capability)) => {
+            let revoked = bot.revoke_capability(guild_id, role_id, capability).await?;
+
+            if revoked && let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::RevokeCapability) {
+                bot.record_audit(entry.target(capability).before(Some(format!("<@&{role_id}>")))).await;
+            }
+
+            let key = match revoked {
+                true => "permissions.revoked",
+                false => "permissions.not_granted",
+            };
+
+            Ok(Reply::text(tf(
+                language,
+                key,
+                &[("role", &format!("<@&{role_id}>")), ("capability", &capability_name(capability, language))],
+            )))
+        }
+        (Some("show"), _, _) => {
+            let mut lines = Vec::new();
+
+            for capability in Capability::ALL {
+                let roles = bot.get_capability_roles(guild_id, capability).await?;
+                let who = match (roles.is_empty(), bot.open_without_roles(guild_id, capability).await?) {
+                    (false, _) => roles.iter().map(|role| format!("<@&{role}>")).collect::<Vec<_>>().join(", "),
+                    (true, true) => t(language, "permissions.everyone"),
+                    (true, false) => t(language, "permissions.admins"),
+                };
+
+                lines.push(format!("**{}**: {who}", capability_name(capability, language)));
+            }
+
+            Ok(Reply::text(tf(language, "permissions.show", &[("capabilities", &lines.join("\n"))])).ephemeral(true))
+        }
+        _ => Err(Error::InvalidInput(t(language, "common.unknown"))),
+    }
+}
+
+/// Gets the translated name of a capability
+pub fn capability_name(capability: Capability, language: &str) -> String {
+    t(language, &format!("permissions.capability.{}", capability.name()))
+}
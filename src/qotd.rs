@@ -0,0 +1,514 @@
+//! Question of the day module for the bot
+//! Stores per-guild schedules and runs the background task that posts them
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveTime, Timelike, Utc};
+use chrono_tz::Tz;
+use serenity::all::{
+    ChannelId, ChannelType, CommandOptionType, CreateActionRow, CreateCommand,
+    CreateCommandOption, CreateMessage, GuildId, Http,
+};
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    bot::Bot,
+    embed::{dare_button, question_embed, resolve_rating, truth_button},
+    error::{Error, Result},
+    i18n::{t, tf, CommandLocalizer},
+    invocation::{Invocation, Reply},
+    questions::{Question, QuestionType},
+};
+
+/// How many of the most recently posted questions are skipped when drawing
+const RECENT_WINDOW: i64 = 30;
+
+/// How often the scheduler checks for schedules that are due
+const TICK: Duration = Duration::from_secs(30);
+
+/// Format used for the post time of a schedule
+const TIME_FORMAT: &str = "%H:%M";
+
+/// Struct for a question of the day schedule in the database
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct QotdSchedule {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub post_time: String,
+    pub question_type: QuestionType,
+    pub rating: String,
+    pub timezone: String,
+    pub last_posted: Option<String>,
+}
+
+impl QotdSchedule {
+    /// Gets the timezone of the schedule, falling back to UTC if it can't be parsed
+    pub fn tz(&self) -> Tz {
+        Tz::from_str(&self.timezone).unwrap_or(Tz::UTC)
+    }
+
+    /// Checks if the schedule should be posted at the provided time
+    ///
+    /// # Returns
+    /// * `Option<String>` - The local date being posted for, if the schedule is due
+    pub fn due(&self, now: DateTime<Utc>) -> Option<String> {
+        let post_time = NaiveTime::parse_from_str(&self.post_time, TIME_FORMAT).ok()?;
+        let local = now.with_timezone(&self.tz());
+        let today = local.date_naive().to_string();
+
+        if local.time() >= post_time && self.last_posted.as_deref() != Some(today.as_str()) {
+            Some(today)
+        } else {
+            None
+        }
+    }
+}
+
+/// Command to manage the question of the day
+pub fn qotd_command() -> CreateCommand {
+    CreateCommand::new("qotd")
+        .localized("qotd")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "set",
+                "Post a question every day at a set time",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Channel,
+                    "channel",
+                    "The channel to post in",
+                )
+                .required(true)
+                .channel_types(vec![ChannelType::Text]),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "time",
+                    "The time to post at in 24 hour HH:MM format",
+                )
+                .required(true),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "type",
+                    "The type of question (TRUTH or DARE)",
+                )
+                .required(true)
+                .add_string_choice("Truth", "TRUTH")
+                .add_string_choice("Dare", "DARE"),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "rating",
+                    "The rating of the questions to post",
+                )
+                .required(true)
+                .add_string_choice("PG", "PG")
+                .add_string_choice("PG-13", "PG-13")
+                .add_string_choice("PG & PG-13", "ALL"),
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "timezone",
+                "The server's timezone, e.g. America/New_York (defaults to UTC)",
+            )),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "clear",
+            "Stop posting the question of the day",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "show",
+            "Show the question of the day schedule",
+        ))
+}
+
+pub async fn qotd(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+
+    match invocation.subcommand.as_deref() {
+        Some("set") => set_qotd(bot, guild_id, invocation, language).await,
+        Some("clear") => {
+            bot.delete_qotd_schedule(guild_id).await?;
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::ClearQotd) {
+                bot.record_audit(entry).await;
+            }
+
+            Ok(Reply::text(t(language, "qotd.cleared")))
+        }
+        Some("show") => match bot.get_qotd_schedule(guild_id).await? {
+            Some(schedule) => {
+                let question_type = match schedule.question_type {
+                    QuestionType::DARE => t(language, "question.dare"),
+                    _ => t(language, "question.truth"),
+                };
+
+                Ok(Reply::text(tf(
+                    language,
+                    "qotd.show",
+                    &[
+                        ("question_type", &question_type.to_lowercase()),
+                        ("rating", &schedule.rating),
+                        ("channel", &schedule.channel_id),
+                        ("time", &schedule.post_time),
+                        ("timezone", &schedule.timezone),
+                    ],
+                )))
+            }
+            None => Ok(Reply::text(t(language, "qotd.not_set"))),
+        },
+        _ => Err(Error::InvalidInput(t(language, "common.unknown"))),
+    }
+}
+
+async fn set_qotd(
+    bot: &Bot,
+    guild_id: GuildId,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let Some(channel_id) = invocation.channel_option("channel") else {
+        return Err(Error::InvalidInput(t(language, "qotd.channel_required")));
+    };
+
+    let time = invocation.str_option("time").unwrap_or("");
+    let Ok(post_time) = NaiveTime::parse_from_str(time.trim(), TIME_FORMAT) else {
+        return Err(Error::InvalidInput(tf(language, "qotd.invalid_time", &[("time", &time)])));
+    };
+
+    let question_type = invocation
+        .str_option("type")
+        .and_then(|s| QuestionType::from_str(s).ok())
+        .unwrap_or(QuestionType::TRUTH);
+    let rating = invocation.str_option("rating").unwrap_or("PG");
+
+    // Use the provided timezone, or keep the one the guild already has
+    let timezone = match invocation.str_option("timezone") {
+        Some(timezone) => Tz::from_str(timezone.trim()).map_err(|_| {
+            Error::InvalidInput(tf(language, "qotd.invalid_timezone", &[("timezone", &timezone)]))
+        })?,
+        None => Tz::from_str(&bot.get_guild_timezone(Some(guild_id)).await?).unwrap_or(Tz::UTC),
+    };
+
+    // If the time already passed today, wait until tomorrow for the first post
+    let local = Utc::now().with_timezone(&timezone);
+    let last_posted = (local.time() >= post_time).then(|| local.date_naive().to_string());
+
+    let schedule = QotdSchedule {
+        guild_id: guild_id.get() as i64,
+        channel_id: channel_id.get() as i64,
+        post_time: format!("{:02}:{:02}", post_time.hour(), post_time.minute()),
+        question_type,
+        rating: rating.to_string(),
+        timezone: timezone.name().to_string(),
+        last_posted,
+    };
+
+    bot.set_guild_timezone(guild_id, timezone.name()).await?;
+    bot.set_qotd_schedule(&schedule).await?;
+
+    if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::SetQotd) {
+        let after = format!(
+            "{} {} <#{}> {} ({})",
+            schedule.question_type, schedule.rating, schedule.channel_id, schedule.post_time, schedule.timezone
+        );
+        bot.record_audit(entry.after(Some(after))).await;
+    }
+
+    Ok(Reply::text(tf(
+        language,
+        "qotd.set",
+        &[
+            ("channel", &schedule.channel_id),
+            ("time", &schedule.post_time),
+            ("timezone", &schedule.timezone),
+        ],
+    )))
+}
+
+/// Runs forever, posting the question of the day for every guild when it is due
+///
+/// # Parameters
+/// * `bot: Bot` - Bot instance for database interaction
+/// * `http: Arc<Http>` - Http client used to post to channels
+pub async fn run_scheduler(bot: Bot, http: Arc<Http>) {
+    let mut interval = tokio::time::interval(TICK);
+
+    loop {
+        interval.tick().await;
+
+        let schedules = match bot.get_qotd_schedules().await {
+            Ok(schedules) => schedules,
+            Err(e) => {
+                error!(error = %e, "failed to load question of the day schedules");
+                continue;
+            }
+        };
+
+        for schedule in schedules {
+            if let Some(today) = schedule.due(Utc::now())
+                && let Err(e) = post_qotd(&bot, &http, &schedule, &today).await
+            {
+                error!(guild = schedule.guild_id, error = %e, "failed to post question of the day");
+            }
+        }
+    }
+}
+
+/// Draws and posts the question of the day for a schedule
+#[instrument(skip_all, fields(guild = schedule.guild_id, channel = schedule.channel_id))]
+async fn post_qotd(bot: &Bot, http: &Http, schedule: &QotdSchedule, today: &str) -> Result<()> {
+    let guild_id = GuildId::new(schedule.guild_id as u64);
+    let language = bot.get_language(Some(guild_id), "").await?;
+
+    // Mark the day as posted first so a failure doesn't retry every tick
+    bot.set_qotd_last_posted(guild_id, today).await?;
+
+    let Some(question) = draw_question(bot, schedule, guild_id, language).await? else {
+        return Ok(());
+    };
+
+    if let Err(e) = bot.add_qotd_history(guild_id, &question.uid).await {
+        warn!(error = %e, "failed to record question of the day");
+    }
+
+    let row = CreateActionRow::Buttons(vec![truth_button(language), dare_button(language)]);
+
+    let builder = CreateMessage::new()
+        .content(t(language, "qotd.header"))
+        .embed(question_embed(schedule.question_type, &question, language))
+        .components(vec![row]);
+
+    ChannelId::new(schedule.channel_id as u64)
+        .send_message(http, builder)
+        .await?;
+
+    info!(question_uid = question.uid.as_str(), "posted question of the day");
+
+    Ok(())
+}
+
+/// Draws a question for the schedule, skipping recently posted questions when possible
+async fn draw_question(
+    bot: &Bot,
+    schedule: &QotdSchedule,
+    guild_id: GuildId,
+    language: &str,
+) -> Result<Option<Question>> {
+    let recent = bot.get_recent_qotd(guild_id).await?;
+
+    // Schedules with both ratings try the other one before allowing a recent question again
+    let rating = resolve_rating(&schedule.rating);
+    let mut ratings = vec![rating];
+    if schedule.rating == "ALL" {
+        ratings.push(if rating == "PG" { "PG-13" } else { "PG" });
+    }
+
+    for excluded in [recent.as_slice(), &[]] {
+        for rating in &ratings {
+            let question = bot
+                .get_random_question(schedule.question_type, rating, Some(guild_id), language, excluded)
+                .await?;
+
+            if question.is_some() {
+                return Ok(question);
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+impl Bot {
+    pub async fn get_qotd_schedules(&self) -> Result<Vec<QotdSchedule>> {
+        let schedules = sqlx::query_as::<_, QotdSchedule>(
+            r#"
+            SELECT q.*, COALESCE(g.timezone, 'UTC') AS timezone FROM qotd_schedules q
+            LEFT JOIN guild_settings g ON g.guild_id = q.guild_id
+            "#,
+        )
+        .fetch_all(&self.database)
+        .await?;
+
+        // Runs every tick of the scheduler, so a quiet bot still reports its database working
+        self.health.database_succeeded();
+
+        Ok(schedules)
+    }
+
+    pub async fn get_qotd_schedule(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Option<QotdSchedule>> {
+        let schedule = sqlx::query_as::<_, QotdSchedule>(
+            r#"
+            SELECT q.*, COALESCE(g.timezone, 'UTC') AS timezone FROM qotd_schedules q
+            LEFT JOIN guild_settings g ON g.guild_id = q.guild_id
+            WHERE q.guild_id = ?
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .fetch_optional(&self.database)
+        .await?;
+
+        Ok(schedule)
+    }
+
+    pub async fn set_qotd_schedule(&self, schedule: &QotdSchedule) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO qotd_schedules (guild_id, channel_id, post_time, question_type, rating, last_posted)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(guild_id) DO UPDATE SET
+                channel_id = excluded.channel_id,
+                post_time = excluded.post_time,
+                question_type = excluded.question_type,
+                rating = excluded.rating,
+                last_posted = excluded.last_posted
+            "#,
+        )
+        .bind(schedule.guild_id)
+        .bind(schedule.channel_id)
+        .bind(&schedule.post_time)
+        .bind(schedule.question_type.to_string())
+        .bind(&schedule.rating)
+        .bind(&schedule.last_posted)
+        .execute(&self.database)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_qotd_schedule(&self, guild_id: GuildId) -> Result<()> {
+        sqlx::query("DELETE FROM qotd_schedules WHERE guild_id = ?")
+            .bind(guild_id.get() as i64)
+            .execute(&self.database)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_qotd_last_posted(
+        &self,
+        guild_id: GuildId,
+        date: &str,
+    ) -> Result<()> {
+        sqlx::query("UPDATE qotd_schedules SET last_posted = ? WHERE guild_id = ?")
+            .bind(date)
+            .bind(guild_id.get() as i64)
+            .execute(&self.database)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Gets the uids of the most recently posted questions of the day for a guild
+    pub async fn get_recent_qotd(&self, guild_id: GuildId) -> Result<Vec<String>> {
+        let uids = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT question_uid FROM qotd_history
+            WHERE guild_id = ?
+            ORDER BY id DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(RECENT_WINDOW)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(uids)
+    }
+
+    pub async fn add_qotd_history(
+        &self,
+        guild_id: GuildId,
+        question_uid: &str,
+    ) -> Result<()> {
+        sqlx::query("INSERT INTO qotd_history (guild_id, question_uid) VALUES (?, ?)")
+            .bind(guild_id.get() as i64)
+            .bind(question_uid)
+            .execute(&self.database)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn schedule(post_time: &str, timezone: &str, last_posted: Option<&str>) -> QotdSchedule {
+        QotdSchedule {
+            guild_id: 1,
+            channel_id: 2,
+            post_time: post_time.to_string(),
+            question_type: QuestionType::TRUTH,
+            rating: "PG".to_string(),
+            timezone: timezone.to_string(),
+            last_posted: last_posted.map(str::to_string),
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 14, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn due_from_post_time() {
+        let schedule = schedule("09:30", "UTC", Some("2026-03-13"));
+
+        assert_eq!(schedule.due(at(9, 29)), None);
+        assert_eq!(schedule.due(at(9, 30)).as_deref(), Some("2026-03-14"));
+        assert_eq!(schedule.due(at(23, 59)).as_deref(), Some("2026-03-14"));
+    }
+
+    #[test]
+    fn posted_once_a_day() {
+        let schedule = schedule("09:30", "UTC", Some("2026-03-14"));
+
+        assert_eq!(schedule.due(at(12, 0)), None);
+        assert_eq!(
+            schedule.due(at(12, 0) + chrono::Duration::days(1)).as_deref(),
+            Some("2026-03-15")
+        );
+    }
+
+    #[test]
+    fn local_day_rolls_over_in_the_timezone() {
+        // 23:30 UTC is already 08:30 of the next day in Tokyo
+        let tokyo = schedule("08:00", "Asia/Tokyo", Some("2026-03-14"));
+        assert_eq!(tokyo.due(at(23, 30)).as_deref(), Some("2026-03-15"));
+        assert_eq!(tokyo.due(at(22, 30)), None);
+
+        // 02:00 UTC is still the evening before in New York
+        let new_york = schedule("20:00", "America/New_York", Some("2026-03-12"));
+        assert_eq!(new_york.due(at(2, 0)).as_deref(), Some("2026-03-13"));
+        assert_eq!(new_york.due(at(23, 30)), None);
+    }
+
+    #[test]
+    fn bad_settings() {
+        // Unknown timezones fall back to UTC
+        assert_eq!(schedule("09:30", "Mars/Olympus", None).due(at(9, 30)).as_deref(), Some("2026-03-14"));
+        assert_eq!(schedule("9.30", "UTC", None).due(at(23, 0)), None);
+    }
+}
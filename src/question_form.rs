@@ -0,0 +1,476 @@
+//! Question form module for the bot
+//! `/question_form` opens a Discord form to add a question, or to edit one when a uid is given, which has room
+//! for longer prompts than a command option. What was written goes through the same checks as `/add_question`
+//! and `/edit_question`, then is shown the way it will be drawn, and is only saved once the preview is confirmed.
+//! Text commands can't open forms, so they answer with a button that opens it instead.
+
+use std::str::FromStr;
+
+use serenity::all::{
+    ActionRowComponent, ButtonStyle, CreateActionRow, CreateButton, CreateCommand, CreateInputText,
+    CreateInteractionResponse, CreateModal, GuildId, InputTextStyle, ModalInteractionData, UserId,
+};
+use uuid::Uuid;
+
+use crate::{
+    autocomplete::question_uid_option,
+    bot::Bot,
+    commands::{check_new_question, check_question_edit, save_new_question, save_question_edit, NewQuestion, QuestionEdit},
+    content_policy::{APPROVED, PENDING},
+    embed::question_embed,
+    error::{Error, Result},
+    i18n::{t, tf, CommandLocalizer},
+    invocation::{Invocation, Reply},
+    other_impl::MessageMaker,
+    permissions::{Capability, Member},
+    questions::{normalize_tag, Question, QuestionType, MAX_TAG_LENGTH},
+};
+
+/// Prefix of the ids of the form, its open button and the buttons of its preview, like `form.save.12`
+pub const FORM_PREFIX: &str = "form.";
+
+/// Minutes a preview can be saved for
+const CONFIRM_MINUTES: i64 = 15;
+
+/// A question written in the form, waiting for its preview to be saved
+#[derive(sqlx::FromRow, Debug, Clone)]
+pub struct QuestionDraft {
+    pub id: i64,
+    pub guild_id: i64,
+    /// Member who wrote it, the only one who can save it
+    pub user_id: i64,
+    /// Uid the new question gets, or the uid of the question being edited
+    pub question_uid: String,
+    pub editing: bool,
+    pub prompt: String,
+    pub question_type: QuestionType,
+    pub rating: String,
+    pub language: String,
+    pub tag: Option<String>,
+}
+
+impl QuestionDraft {
+    fn new_question(&self) -> NewQuestion {
+        NewQuestion {
+            uid: self.question_uid.clone(),
+            prompt: self.prompt.clone(),
+            question_type: self.question_type,
+            rating: self.rating.clone(),
+            language: self.language.clone(),
+            tag: self.tag.clone(),
+        }
+    }
+}
+
+/// Every field of the form is sent, so an edit sets all of them and an empty tag removes it
+fn full_edit(question: &NewQuestion) -> QuestionEdit {
+    QuestionEdit {
+        prompt: Some(question.prompt.clone()),
+        question_type: Some(question.question_type),
+        rating: Some(question.rating.clone()),
+        tag: Some(question.tag.clone()),
+    }
+}
+
+impl Bot {
+    /// Saves a question written in the form until its preview is saved, dropping the ones that weren't in time
+    ///
+    /// # Returns
+    /// * `i64` - The id of the draft, used in the ids of the preview's buttons
+    pub async fn save_question_draft(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        editing: bool,
+        question: &NewQuestion,
+    ) -> Result<i64> {
+        sqlx::query("DELETE FROM question_drafts WHERE created_at < datetime('now', ?)")
+            .bind(format!("-{CONFIRM_MINUTES} minutes"))
+            .execute(&self.database)
+            .await?;
+
+        let id = sqlx::query_scalar::<_, i64>(
+            r#"
+            INSERT INTO question_drafts (guild_id, user_id, question_uid, editing, prompt, question_type, rating, language, tag)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING id
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .bind(&question.uid)
+        .bind(editing)
+        .bind(&question.prompt)
+        .bind(question.question_type.to_string())
+        .bind(&question.rating)
+        .bind(&question.language)
+        .bind(&question.tag)
+        .fetch_one(&self.database)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Gets a draft of a guild that can still be saved
+    pub async fn get_question_draft(&self, guild_id: GuildId, id: i64) -> Result<Option<QuestionDraft>> {
+        let draft = sqlx::query_as::<_, QuestionDraft>(
+            r#"
+            SELECT * FROM question_drafts
+            WHERE id = ? AND guild_id = ? AND created_at >= datetime('now', ?)
+            "#,
+        )
+        .bind(id)
+        .bind(guild_id.get() as i64)
+        .bind(format!("-{CONFIRM_MINUTES} minutes"))
+        .fetch_optional(&self.database)
+        .await?;
+
+        Ok(draft)
+    }
+
+    /// Puts back a draft that couldn't be saved, under its old id so the preview's buttons still work
+    /// It gets a new `CONFIRM_MINUTES` to be saved in
+    pub async fn restore_question_draft(&self, draft: &QuestionDraft) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO question_drafts (id, guild_id, user_id, question_uid, editing, prompt, question_type, rating, language, tag)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .bind(draft.id)
+        .bind(draft.guild_id)
+        .bind(draft.user_id)
+        .bind(&draft.question_uid)
+        .bind(draft.editing)
+        .bind(&draft.prompt)
+        .bind(draft.question_type.to_string())
+        .bind(&draft.rating)
+        .bind(&draft.language)
+        .bind(&draft.tag)
+        .execute(&self.database)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Drops a draft, returning false if it was already gone so it can't be saved twice
+    pub async fn delete_question_draft(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM question_drafts WHERE id = ?")
+            .bind(id)
+            .execute(&self.database)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Checks a member can use the form, so they find out before writing anything
+///
+/// # Parameters
+/// * `question_uid: Option<&str>` - The question being edited, `None` when adding one
+///
+/// # Returns
+/// * `Option<Question>` - The question being edited
+async fn check_member(
+    bot: &Bot,
+    member: Member<'_>,
+    question_uid: Option<&str>,
+    language: &str,
+) -> Result<Option<Question>> {
+    let guild_id = member.guild_id.ok_or(Error::GuildOnly)?;
+
+    let Some(question_uid) = question_uid else {
+        bot.require(member, Capability::Submit).await?;
+        bot.check_submitter(member, 1, language).await?;
+
+        return Ok(None);
+    };
+
+    let Some(question) = bot.get_guild_question(guild_id, question_uid).await? else {
+        return Err(Error::QuestionNotFound);
+    };
+
+    bot.require_for_question(member, Capability::Edit, &question).await?;
+
+    if question.author() == Some(member.user_id) {
+        bot.require_not_banned(guild_id, member.user_id, language).await?;
+    }
+
+    Ok(Some(question))
+}
+
+/// Makes the form, filled in with the question being edited if there is one
+fn form_modal(bot: &Bot, question: Option<&Question>, language: &str) -> CreateModal {
+    let policy = &bot.config.content_policy;
+
+    let mut prompt = CreateInputText::new(InputTextStyle::Paragraph, t(language, "form.prompt"), "prompt")
+        .min_length(policy.min_length as u16)
+        .max_length(policy.max_length as u16);
+    let mut question_type = CreateInputText::new(InputTextStyle::Short, t(language, "form.question_type"), "question_type")
+        .placeholder(t(language, "form.question_type_placeholder"))
+        .max_length(20);
+    let mut rating = CreateInputText::new(InputTextStyle::Short, t(language, "form.rating"), "rating")
+        .placeholder(t(language, "form.rating_placeholder"))
+        .max_length(5);
+    let mut tag = CreateInputText::new(InputTextStyle::Short, t(language, "form.tag"), "tag")
+        .placeholder(t(language, "form.tag_placeholder"))
+        .max_length(MAX_TAG_LENGTH)
+        .required(false);
+
+    let (id, title) = match question {
+        Some(question) => {
+            prompt = prompt.value(&question.prompt);
+            question_type = question_type.value(type_name(question.question_type, language));
+            rating = rating.value(&question.rating);
+
+            if let Some(value) = &question.tag {
+                tag = tag.value(value);
+            }
+
+            (format!("{FORM_PREFIX}edit.{}", question.uid), t(language, "form.edit_title"))
+        }
+        None => (format!("{FORM_PREFIX}add"), t(language, "form.add_title")),
+    };
+
+    CreateModal::new(id, title).components(
+        [prompt, question_type, rating, tag]
+            .into_iter()
+            .map(CreateActionRow::InputText)
+            .collect(),
+    )
+}
+
+/// Name of a question type as the form shows it
+fn type_name(question_type: QuestionType, language: &str) -> String {
+    match question_type {
+        QuestionType::DARE => t(language, "question.dare"),
+        _ => t(language, "question.truth"),
+    }
+}
+
+/// Reads the type written in the form, in English or in the language the form was shown in, ignoring case
+fn parse_type(text: &str, language: &str) -> Result<QuestionType> {
+    let text = text.trim();
+
+    [QuestionType::TRUTH, QuestionType::DARE]
+        .into_iter()
+        .find(|question_type| {
+            text.eq_ignore_ascii_case(&question_type.to_string())
+                || text.to_lowercase() == type_name(*question_type, language).to_lowercase()
+        })
+        .ok_or_else(|| Error::InvalidInput(t(language, "form.invalid_type")))
+}
+
+/// Reads the rating written in the form, ignoring case
+fn parse_rating(text: &str, language: &str) -> Result<String> {
+    let rating = text.trim().to_uppercase();
+
+    match rating.as_str() {
+        "PG" | "PG-13" => Ok(rating),
+        _ => Err(Error::InvalidInput(t(language, "form.invalid_rating"))),
+    }
+}
+
+/// Gets what was written in a field of the form, empty if it was left out
+fn field<'a>(data: &'a ModalInteractionData, custom_id: &str) -> &'a str {
+    data.components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) if input.custom_id == custom_id => input.value.as_deref(),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Command to add or edit a question with a form
+pub fn question_form_command() -> CreateCommand {
+    CreateCommand::new("question_form")
+        .localized("question_form")
+        .add_option(question_uid_option("The question to edit, leave empty to add a new one").required(false))
+}
+
+pub async fn question_form(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let question_uid = invocation.str_option("question_uid");
+    let question = check_member(bot, Member::from(invocation), question_uid, language).await?;
+
+    let button_id = match question_uid {
+        Some(question_uid) => format!("{FORM_PREFIX}open.{question_uid}"),
+        None => format!("{FORM_PREFIX}open"),
+    };
+
+    Ok(Reply::text(t(language, "form.open"))
+        .components(vec![CreateActionRow::Buttons(vec![CreateButton::new(button_id)
+            .label(t(language, "button.open_form"))
+            .style(ButtonStyle::Primary)])])
+        .modal(form_modal(bot, question.as_ref(), language)))
+}
+
+/// Checks what was written in the form and previews it, saving it as a draft until the preview is saved
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to access the database
+/// * `data: &ModalInteractionData` - The submitted form, its id is `form.add` or `form.edit.<uid>`
+/// * `member: Member<'_>` - Member who filled in the form
+/// * `language: &str` - Language to answer in
+pub async fn submit_form(
+    bot: &Bot,
+    data: &ModalInteractionData,
+    member: Member<'_>,
+    language: &str,
+) -> Result<CreateInteractionResponse> {
+    let guild_id = member.guild_id.ok_or(Error::GuildOnly)?;
+    let editing = data.custom_id.strip_prefix(FORM_PREFIX).and_then(|rest| rest.strip_prefix("edit."));
+
+    let written = NewQuestion {
+        uid: editing.map(str::to_string).unwrap_or_else(|| Uuid::new_v4().to_string()),
+        prompt: field(data, "prompt").to_string(),
+        question_type: parse_type(field(data, "question_type"), language)?,
+        rating: parse_rating(field(data, "rating"), language)?,
+        language: language.to_string(),
+        tag: normalize_tag(field(data, "tag")),
+    };
+
+    // Checked like the commands would, so the preview never shows a question that can't be saved
+    let (preview, submission) = match editing {
+        Some(question_uid) => {
+            let (_, edited, submission) = check_question_edit(bot, member, question_uid, &full_edit(&written), language).await?;
+
+            (edited, submission)
+        }
+        None => {
+            bot.require(member, Capability::Submit).await?;
+
+            let submission = check_new_question(bot, member, &written, language).await?;
+            let preview = Question {
+                status: if submission.needs_review { PENDING } else { APPROVED }.to_string(),
+                tag: written.tag.clone(),
+                ..Question::new(
+                    guild_id.get() as i64,
+                    submission.prompt.clone(),
+                    written.question_type,
+                    written.rating.clone(),
+                    written.uid.clone(),
+                    written.language.clone(),
+                )
+            };
+
+            (preview, submission)
+        }
+    };
+
+    // Edited questions keep the language they were written in
+    let written = NewQuestion { language: preview.language.clone(), ..written };
+    let id = bot.save_question_draft(guild_id, member.user_id, editing.is_some(), &written).await?;
+
+    let mut content = tf(language, "form.preview", &[("minutes", &CONFIRM_MINUTES)]);
+
+    if preview.status == PENDING {
+        content = format!("{content}\n{}", t(language, "form.pending"));
+    }
+    if let Some(warning) = submission.warning(language) {
+        content = format!("{content}\n{warning}");
+    }
+
+    let mut embed = question_embed(preview.question_type, &preview, language);
+
+    if let Some(tag) = &preview.tag {
+        embed = embed.field(t(language, "form.tag"), format!("#{tag}"), true);
+    }
+
+    Ok(Reply {
+        content: Some(content),
+        ..Reply::embed(embed)
+    }
+    .components(vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{FORM_PREFIX}save.{id}"))
+            .label(t(language, "button.save"))
+            .style(ButtonStyle::Success),
+        CreateButton::new(format!("{FORM_PREFIX}cancel.{id}"))
+            .label(t(language, "button.cancel"))
+            .style(ButtonStyle::Secondary),
+    ])])
+    .ephemeral(true)
+    .to_interaction_message())
+}
+
+/// Checks if a component or form id belongs to the question form
+pub fn is_form_id(id: &str) -> bool {
+    id.starts_with(FORM_PREFIX)
+}
+
+/// Opens the form from its button, or saves or drops the draft a preview button points to
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to access the database
+/// * `interaction: &str` - The id of the button, like `form.open.<uid>` or `form.save.12`
+/// * `member: Member<'_>` - Member who pressed the button
+/// * `language: &str` - Language to answer in
+pub async fn form_button(
+    bot: &Bot,
+    interaction: &str,
+    member: Member<'_>,
+    language: &str,
+) -> Result<CreateInteractionResponse> {
+    let guild_id = member.guild_id.ok_or(Error::GuildOnly)?;
+    let rest = interaction.strip_prefix(FORM_PREFIX).unwrap_or_default();
+
+    if rest == "open" || rest.starts_with("open.") {
+        let question_uid = rest.strip_prefix("open.");
+        let question = check_member(bot, member, question_uid, language).await?;
+
+        return Ok(CreateInteractionResponse::Modal(form_modal(bot, question.as_ref(), language)));
+    }
+
+    let Some((button, id)) = rest
+        .split_once('.')
+        .and_then(|(button, id)| Some((button, i64::from_str(id).ok()?)))
+    else {
+        return Err(Error::InvalidInput(t(language, "common.unknown")));
+    };
+
+    let Some(draft) = bot.get_question_draft(guild_id, id).await? else {
+        return Ok(Reply::text(t(language, "form.expired")).to_update());
+    };
+
+    if draft.user_id != member.user_id.get() as i64 {
+        return Err(Error::InvalidInput(t(language, "form.not_yours")));
+    }
+
+    if button == "cancel" {
+        bot.delete_question_draft(id).await?;
+
+        return Ok(Reply::text(t(language, "form.cancelled")).to_update());
+    }
+
+    // Taken while it is saved, so pressing Save twice can't add the question twice
+    if !bot.delete_question_draft(id).await? {
+        return Ok(Reply::text(t(language, "form.expired")).to_update());
+    }
+
+    // Everything is checked again, since roles, bans and the question itself may have changed since the preview
+    let reply = async {
+        match draft.editing {
+            true => save_question_edit(bot, member, &draft.question_uid, &full_edit(&draft.new_question()), language).await,
+            false => {
+                bot.require(member, Capability::Submit).await?;
+                save_new_question(bot, member, &draft.new_question(), language).await
+            }
+        }
+    }
+    .await;
+
+    match reply {
+        Ok(reply) => Ok(Reply::text(reply).to_update()),
+        // Nothing was saved, so what the member wrote is kept and Save can be pressed again
+        Err(e) => {
+            bot.restore_question_draft(&draft).await?;
+            Err(e)
+        }
+    }
+}
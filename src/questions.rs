@@ -1,12 +1,17 @@
-/// Questions module for the bot
-/// Has the Question struct and QuestionType enum
+//! Questions module for the bot
+//! Has the Question struct and QuestionType enum
 
 use std::fmt;
 use std::str::FromStr;
 
+use chrono::NaiveDateTime;
+use serenity::all::UserId;
 use sqlx::{Decode, Sqlite, Type};
 use sqlx::sqlite::SqliteValueRef;
 
+/// Longest tag a question can have
+pub const MAX_TAG_LENGTH: u16 = 32;
+
 /// Enum with implementations for the question type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum QuestionType {
@@ -22,19 +27,79 @@ pub struct Question {
     pub prompt: String,
     pub question_type: QuestionType,
     pub rating: String,
-    pub uid: String
+    pub uid: String,
+    pub language: String,
+    /// Review status, `approved` or `pending`
+    pub status: String,
+    /// Tag the guild grouped the question under, like `party`
+    pub tag: Option<String>,
+    /// Member who added the question, `None` for default questions and ones added before authors were recorded
+    pub author_id: Option<i64>,
+    /// When the question was added, like `2024-05-01 12:00:00` in UTC
+    pub created_at: Option<String>,
+    /// When the question was moved to the trash, `None` if it wasn't
+    pub deleted_at: Option<String>,
+    /// Member who moved the question to the trash
+    pub deleted_by: Option<i64>,
 }
 
 impl Question {
-    pub fn new(guild_id: i64, prompt: String, question_type: QuestionType, rating: String, uid: String) -> Question {
+    pub fn new(guild_id: i64, prompt: String, question_type: QuestionType, rating: String, uid: String, language: String) -> Question {
         Question {
             guild_id,
             prompt,
             question_type,
             rating,
             uid,
+            language,
+            status: "approved".to_string(),
+            tag: None,
+            author_id: None,
+            created_at: None,
+            deleted_at: None,
+            deleted_by: None,
         }
     }
+
+    /// Gets the member who added the question
+    pub fn author(&self) -> Option<UserId> {
+        self.author_id.map(|author_id| UserId::new(author_id as u64))
+    }
+
+    /// Gets when the question was added as a unix timestamp
+    pub fn created_timestamp(&self) -> Option<i64> {
+        timestamp(self.created_at.as_deref()?)
+    }
+
+    /// Gets when the question was moved to the trash as a unix timestamp
+    pub fn deleted_timestamp(&self) -> Option<i64> {
+        timestamp(self.deleted_at.as_deref()?)
+    }
+}
+
+/// Turns a tag as it was typed into the one that is saved, so `#Party Games` and `party-games` are the same
+/// Returns `None` for a tag with nothing left, like `-`, which is how a tag is removed
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag: String = tag
+        .trim()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+        .take(MAX_TAG_LENGTH as usize)
+        .collect();
+    let tag = tag.trim_matches(|c| c == '-' || c == '_');
+
+    (!tag.is_empty()).then(|| tag.to_string())
+}
+
+/// Reads a time stored by SQLite, like `2024-05-01 12:00:00` in UTC, as a unix timestamp
+fn timestamp(time: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|time| time.and_utc().timestamp())
 }
 
 /// Get the question type as printable text
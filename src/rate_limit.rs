@@ -0,0 +1,566 @@
+//! Rate limit module for the bot
+//! Keeps members from flooding a channel with commands and buttons, counting recent uses
+//! per user, per channel and per guild. Guilds can change their limits with `/set_rate_limit`.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use serenity::all::{
+    ChannelId, CommandOptionType, CreateCommand, CreateCommandOption, GuildId, UserId,
+};
+use tracing::warn;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    bot::Bot,
+    error::{Error, Result},
+    i18n::{t, tf, CommandLocalizer},
+    invocation::{Invocation, Reply},
+    metrics::METRICS,
+};
+
+/// Most uses a limit can allow in its window when set with `/set_rate_limit`
+const MAX_REQUESTS: u64 = 1000;
+
+/// Longest window a limit can have when set with `/set_rate_limit`
+const MAX_SECONDS: u64 = 3600;
+
+/// Uses between cleanups of buckets that haven't been used in a while
+const CLEANUP_EVERY: u64 = 1024;
+
+/// What a limit is counted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitScope {
+    User,
+    Channel,
+    Guild,
+}
+
+impl LimitScope {
+    pub const ALL: [LimitScope; 3] = [LimitScope::User, LimitScope::Channel, LimitScope::Guild];
+
+    /// Name used in commands and metric labels
+    pub fn name(&self) -> &'static str {
+        match self {
+            LimitScope::User => "user",
+            LimitScope::Channel => "channel",
+            LimitScope::Guild => "guild",
+        }
+    }
+}
+
+impl FromStr for LimitScope {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        LimitScope::ALL.into_iter().find(|scope| scope.name() == s).ok_or(())
+    }
+}
+
+/// How many uses are allowed in a window, written as `requests/seconds` like `5/10`
+/// A limit of 0 requests turns the limit off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Limit {
+    pub requests: u32,
+    pub seconds: u64,
+}
+
+impl Limit {
+    pub fn new(requests: u32, seconds: u64) -> Limit {
+        Limit { requests, seconds }
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.seconds)
+    }
+
+    pub fn is_off(&self) -> bool {
+        self.requests == 0
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.requests, self.seconds)
+    }
+}
+
+impl FromStr for Limit {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (requests, seconds) = s
+            .split_once('/')
+            .ok_or_else(|| format!("{s:?} must look like requests/seconds, e.g. 5/10"))?;
+        let requests = requests
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("{requests:?} isn't a number of requests"))?;
+        let seconds = seconds
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|seconds| *seconds > 0)
+            .ok_or_else(|| format!("{seconds:?} isn't a number of seconds above 0"))?;
+
+        Ok(Limit { requests, seconds })
+    }
+}
+
+impl TryFrom<String> for Limit {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Limits for every scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    /// Turns rate limiting off everywhere when false
+    pub enabled: bool,
+    pub user: Limit,
+    pub channel: Limit,
+    pub guild: Limit,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            enabled: true,
+            user: Limit::new(5, 10),
+            channel: Limit::new(15, 10),
+            guild: Limit::new(60, 10),
+        }
+    }
+}
+
+impl RateLimits {
+    pub fn get(&self, scope: LimitScope) -> Limit {
+        match scope {
+            LimitScope::User => self.user,
+            LimitScope::Channel => self.channel,
+            LimitScope::Guild => self.guild,
+        }
+    }
+
+    pub fn set(&mut self, scope: LimitScope, limit: Limit) {
+        match scope {
+            LimitScope::User => self.user = limit,
+            LimitScope::Channel => self.channel = limit,
+            LimitScope::Guild => self.guild = limit,
+        }
+    }
+}
+
+/// A use that was turned away
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimited {
+    /// The limit that was hit
+    pub scope: LimitScope,
+    /// How long until another use is allowed
+    pub retry_after: Duration,
+    /// Whether this is the first use turned away since the limit was hit,
+    /// so text commands only answer once instead of adding to the flood
+    pub first: bool,
+}
+
+impl RateLimited {
+    /// Makes the reply telling the member to slow down
+    pub fn reply(&self, language: &str) -> Reply {
+        let seconds = self.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        let key = format!("rate_limit.{}", self.scope.name());
+
+        Reply::text(tf(language, &key, &[("seconds", &seconds)])).ephemeral(true)
+    }
+}
+
+/// Recent uses of one user, channel or guild
+#[derive(Debug, Default)]
+struct Bucket {
+    uses: VecDeque<Instant>,
+    /// Window of the limit the bucket was last checked against
+    window: Duration,
+    /// Set once a use is turned away, cleared when one is allowed again
+    warned: bool,
+}
+
+impl Bucket {
+    /// Forgets uses that are outside of the window
+    fn trim(&mut self, now: Instant) {
+        while self.uses.front().is_some_and(|used| now.duration_since(*used) >= self.window) {
+            self.uses.pop_front();
+        }
+    }
+}
+
+/// Counts uses in a sliding window for every user, channel and guild
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<(LimitScope, u64), Bucket>>,
+    /// Limits of guilds, loaded from the database the first time they are needed
+    guild_limits: Mutex<HashMap<GuildId, RateLimits>>,
+    checks: AtomicU64,
+}
+
+impl RateLimiter {
+    /// Records a use if every limit allows it
+    /// Nothing is recorded when a use is turned away, so spamming doesn't push the wait back
+    ///
+    /// # Parameters
+    /// * `limits: &RateLimits` - Limits to check against
+    /// * `user_id: UserId` - Who used the command or button
+    /// * `channel_id: ChannelId` - Where it was used
+    /// * `guild_id: Option<GuildId>` - Guild it was used in, if any
+    pub fn check(
+        &self,
+        limits: &RateLimits,
+        user_id: UserId,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+    ) -> std::result::Result<(), RateLimited> {
+        self.check_at(limits, user_id, channel_id, guild_id, Instant::now())
+    }
+
+    /// Same as `check`, with the time of the use passed in so tests can move the clock
+    fn check_at(
+        &self,
+        limits: &RateLimits,
+        user_id: UserId,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+        now: Instant,
+    ) -> std::result::Result<(), RateLimited> {
+        if !limits.enabled {
+            return Ok(());
+        }
+
+        let keys = [
+            Some((LimitScope::User, user_id.get())),
+            Some((LimitScope::Channel, channel_id.get())),
+            guild_id.map(|id| (LimitScope::Guild, id.get())),
+        ];
+
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return Ok(());
+        };
+
+        if self.checks.fetch_add(1, Ordering::Relaxed).is_multiple_of(CLEANUP_EVERY) {
+            buckets.retain(|_, bucket| {
+                bucket.trim(now);
+                !bucket.uses.is_empty()
+            });
+        }
+
+        for (scope, id) in keys.iter().flatten() {
+            let limit = limits.get(*scope);
+            if limit.is_off() {
+                continue;
+            }
+
+            let bucket = buckets.entry((*scope, *id)).or_default();
+            bucket.window = limit.window();
+            bucket.trim(now);
+
+            if bucket.uses.len() >= limit.requests as usize {
+                let oldest = bucket.uses.front().copied().unwrap_or(now);
+                let first = !bucket.warned;
+                bucket.warned = true;
+                METRICS.rate_limited.with_label_values(&[scope.name()]).inc();
+
+                return Err(RateLimited {
+                    scope: *scope,
+                    retry_after: limit.window().saturating_sub(now.duration_since(oldest)),
+                    first,
+                });
+            }
+        }
+
+        for (scope, id) in keys.iter().flatten() {
+            if limits.get(*scope).is_off() {
+                continue;
+            }
+
+            let bucket = buckets.entry((*scope, *id)).or_default();
+            bucket.uses.push_back(now);
+            bucket.warned = false;
+        }
+
+        Ok(())
+    }
+
+    fn cached_limits(&self, guild_id: GuildId) -> Option<RateLimits> {
+        self.guild_limits.lock().ok()?.get(&guild_id).copied()
+    }
+
+    fn cache_limits(&self, guild_id: GuildId, limits: RateLimits) {
+        if let Ok(mut guild_limits) = self.guild_limits.lock() {
+            guild_limits.insert(guild_id, limits);
+        }
+    }
+}
+
+impl Bot {
+    /// Checks a use against the rate limits of the guild and records it if allowed
+    /// If the limits can't be loaded the configured ones are used, so a database
+    /// problem doesn't stop every command
+    ///
+    /// # Returns
+    /// * `Option<RateLimited>` - Why the use was turned away, `None` if it is allowed
+    pub async fn rate_limit(
+        &self,
+        user_id: UserId,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+    ) -> Option<RateLimited> {
+        let limits = match self.get_rate_limits(guild_id).await {
+            Ok(limits) => limits,
+            Err(e) => {
+                warn!(error = %e, "failed to load rate limits, using the defaults");
+                self.config.rate_limits
+            }
+        };
+
+        self.rate_limiter.check(&limits, user_id, channel_id, guild_id).err()
+    }
+
+    /// Gets the rate limits of a guild, which are the configured ones with the guild's overrides
+    pub async fn get_rate_limits(&self, guild_id: Option<GuildId>) -> Result<RateLimits> {
+        let Some(guild_id) = guild_id else {
+            return Ok(self.config.rate_limits);
+        };
+
+        if let Some(limits) = self.rate_limiter.cached_limits(guild_id) {
+            return Ok(limits);
+        }
+
+        let settings = self.get_guild_settings(guild_id).await?;
+        let mut limits = self.config.rate_limits;
+
+        for scope in [LimitScope::User, LimitScope::Channel, LimitScope::Guild] {
+            if let Some(limit) = settings.rate_limit(scope) {
+                limits.set(scope, limit);
+            }
+        }
+
+        self.rate_limiter.cache_limits(guild_id, limits);
+
+        Ok(limits)
+    }
+
+    /// Sets the rate limit of a guild for a scope, `None` goes back to the configured limit
+    pub async fn set_guild_rate_limit(
+        &self,
+        guild_id: GuildId,
+        scope: LimitScope,
+        limit: Option<Limit>,
+    ) -> Result<()> {
+        self.update_guild_settings(guild_id, |settings| settings.set_rate_limit(scope, limit)).await?;
+
+        // Loaded again the next time it is needed
+        if let Ok(mut guild_limits) = self.rate_limiter.guild_limits.lock() {
+            guild_limits.remove(&guild_id);
+        }
+
+        Ok(())
+    }
+}
+
+/// Command to change the rate limits of a guild
+pub fn set_rate_limit_command() -> CreateCommand {
+    CreateCommand::new("set_rate_limit")
+        .localized("set_rate_limit")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "scope", "What the limit counts")
+                .required(true)
+                .add_string_choice("Each member", "user")
+                .add_string_choice("Each channel", "channel")
+                .add_string_choice("The whole server", "guild"),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "requests",
+                "Uses allowed in the window, 0 turns the limit off, leave empty to use the default",
+            )
+            .min_int_value(0)
+            .max_int_value(MAX_REQUESTS),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "seconds", "Length of the window in seconds")
+                .min_int_value(1)
+                .max_int_value(MAX_SECONDS),
+        )
+}
+
+pub async fn set_rate_limit(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+
+    let scope = invocation
+        .str_option("scope")
+        .and_then(|scope| LimitScope::from_str(scope).ok())
+        .ok_or_else(|| Error::InvalidInput(t(language, "common.unknown")))?;
+
+    // Without a number of uses the limit goes back to the default, and without
+    // a window the current one is kept
+    let limit = match invocation.int_option("requests") {
+        Some(requests) => {
+            let current = bot.get_rate_limits(Some(guild_id)).await?.get(scope);
+            let seconds = invocation.int_option("seconds").unwrap_or(current.seconds as i64);
+
+            if !(0..=MAX_REQUESTS as i64).contains(&requests) || !(1..=MAX_SECONDS as i64).contains(&seconds) {
+                return Err(Error::InvalidInput(tf(
+                    language,
+                    "rate_limit.invalid",
+                    &[("requests", &MAX_REQUESTS), ("seconds", &MAX_SECONDS)],
+                )));
+            }
+
+            Some(Limit::new(requests as u32, seconds as u64))
+        }
+        None => None,
+    };
+
+    let before = bot.get_rate_limits(Some(guild_id)).await?.get(scope);
+    bot.set_guild_rate_limit(guild_id, scope, limit).await?;
+
+    let limits = bot.get_rate_limits(Some(guild_id)).await?;
+
+    if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::SetRateLimit) {
+        bot.record_audit(entry.target(scope.name()).before(Some(before)).after(Some(limits.get(scope)))).await;
+    }
+    let describe = |limit: Limit| match limit.is_off() {
+        true => t(language, "rate_limit.off"),
+        false => tf(
+            language,
+            "rate_limit.limit",
+            &[("requests", &limit.requests), ("seconds", &limit.seconds)],
+        ),
+    };
+
+    Ok(Reply::text(tf(
+        language,
+        "rate_limit.set",
+        &[
+            ("user", &describe(limits.user)),
+            ("channel", &describe(limits.channel)),
+            ("guild", &describe(limits.guild)),
+        ],
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: UserId = UserId::new(1);
+    const OTHER_USER: UserId = UserId::new(2);
+    const CHANNEL: ChannelId = ChannelId::new(10);
+    const OTHER_CHANNEL: ChannelId = ChannelId::new(11);
+    const GUILD: Option<GuildId> = Some(GuildId::new(100));
+
+    fn limits(user: &str, channel: &str, guild: &str) -> RateLimits {
+        RateLimits {
+            enabled: true,
+            user: user.parse().unwrap(),
+            channel: channel.parse().unwrap(),
+            guild: guild.parse().unwrap(),
+        }
+    }
+
+    fn after(start: Instant, seconds: u64) -> Instant {
+        start + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn parsing_limits() {
+        assert_eq!("5/10".parse(), Ok(Limit::new(5, 10)));
+        assert_eq!(" 3 / 60 ".parse(), Ok(Limit::new(3, 60)));
+        assert_eq!(Limit::new(5, 10).to_string(), "5/10");
+
+        // 0 requests turns a limit off, a window of 0 seconds isn't allowed
+        assert!("0/10".parse::<Limit>().unwrap().is_off());
+        assert!("5/0".parse::<Limit>().is_err());
+
+        for malformed in ["", "5", "5/", "/10", "five/10", "5/ten", "-1/10", "5/-10", "5/10/15"] {
+            assert!(malformed.parse::<Limit>().is_err(), "{malformed:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn window_expires() {
+        let limiter = RateLimiter::default();
+        let limits = limits("2/10", "0/10", "0/10");
+        let start = Instant::now();
+
+        assert!(limiter.check_at(&limits, USER, CHANNEL, GUILD, start).is_ok());
+        assert!(limiter.check_at(&limits, USER, CHANNEL, GUILD, after(start, 4)).is_ok());
+
+        let limited = limiter.check_at(&limits, USER, CHANNEL, GUILD, after(start, 6)).unwrap_err();
+        assert_eq!(limited.scope, LimitScope::User);
+        assert_eq!(limited.retry_after, Duration::from_secs(4));
+        assert!(limited.first);
+
+        // Only the first use turned away is answered, and turned away uses don't push the wait back
+        let limited = limiter.check_at(&limits, USER, CHANNEL, GUILD, after(start, 9)).unwrap_err();
+        assert!(!limited.first);
+        assert_eq!(limited.retry_after, Duration::from_secs(1));
+
+        // The first use leaves the window, so one more is allowed
+        assert!(limiter.check_at(&limits, USER, CHANNEL, GUILD, after(start, 10)).is_ok());
+        assert!(limiter.check_at(&limits, USER, CHANNEL, GUILD, after(start, 11)).is_err());
+    }
+
+    #[test]
+    fn scopes_are_counted_apart() {
+        let limiter = RateLimiter::default();
+        let limits = limits("1/10", "2/10", "3/10");
+        let now = Instant::now();
+
+        assert!(limiter.check_at(&limits, USER, CHANNEL, GUILD, now).is_ok());
+        assert_eq!(limiter.check_at(&limits, USER, OTHER_CHANNEL, GUILD, now).unwrap_err().scope, LimitScope::User);
+
+        // Another member can still use the channel until the channel is full
+        assert!(limiter.check_at(&limits, OTHER_USER, CHANNEL, GUILD, now).is_ok());
+        assert_eq!(
+            limiter.check_at(&limits, UserId::new(3), CHANNEL, GUILD, now).unwrap_err().scope,
+            LimitScope::Channel
+        );
+
+        // Other channels count toward the guild
+        assert!(limiter.check_at(&limits, UserId::new(4), OTHER_CHANNEL, GUILD, now).is_ok());
+        assert_eq!(
+            limiter.check_at(&limits, UserId::new(5), ChannelId::new(12), GUILD, now).unwrap_err().scope,
+            LimitScope::Guild
+        );
+
+        // Direct messages have no guild to count toward
+        assert!(limiter.check_at(&limits, UserId::new(6), ChannelId::new(13), None, now).is_ok());
+    }
+
+    #[test]
+    fn limits_that_are_off() {
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+
+        let off = limits("0/10", "0/10", "0/10");
+        let disabled = RateLimits { enabled: false, ..limits("1/10", "1/10", "1/10") };
+
+        for _ in 0..5 {
+            assert!(limiter.check_at(&off, USER, CHANNEL, GUILD, now).is_ok());
+            assert!(limiter.check_at(&disabled, USER, CHANNEL, GUILD, now).is_ok());
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Registration module for the bot
+//! Keeps the slash commands registered with Discord in sync with `create_commands`,
+//! only sending requests for the commands that changed
+
+use serde_json::{Map, Value};
+use serenity::all::{Command, CommandId, CreateCommand, GuildId, Http};
+use tracing::{info, warn};
+
+use crate::error::Result;
+
+/// Fields of a registered command that are compared even if the new command leaves them out
+const COMPARED_FIELDS: &[&str] = &[
+    "name",
+    "description",
+    "options",
+    "default_member_permissions",
+    "name_localizations",
+    "description_localizations",
+];
+
+/// Where commands are registered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Every guild, updates can take a while to reach clients
+    Global,
+    /// A single guild, updates show up right away so it is used for development
+    Guild(GuildId),
+}
+
+/// Changes needed to make the registered commands match the wanted ones
+#[derive(Debug, Default)]
+pub struct RegistrationPlan {
+    /// Commands that aren't registered yet
+    pub create: Vec<(String, CreateCommand)>,
+    /// Registered commands that are different from the wanted ones
+    pub update: Vec<(String, CommandId, CreateCommand)>,
+    /// Registered commands that aren't wanted anymore
+    pub stale: Vec<(String, CommandId)>,
+    /// Names of commands that are already up to date
+    pub unchanged: Vec<String>,
+}
+
+impl RegistrationPlan {
+    /// Compares the wanted commands to the registered ones
+    ///
+    /// # Parameters
+    /// * `wanted: Vec<CreateCommand>` - Commands the bot should have
+    /// * `registered: &[Command]` - Commands Discord has for the bot
+    pub fn new(wanted: Vec<CreateCommand>, registered: &[Command]) -> RegistrationPlan {
+        let mut plan = RegistrationPlan::default();
+        let mut wanted_names = Vec::new();
+
+        for command in wanted {
+            let wanted_json = normalize(serde_json::to_value(&command).unwrap_or_default());
+            let name = wanted_json["name"].as_str().unwrap_or_default().to_string();
+
+            match registered.iter().find(|r| r.name == name) {
+                Some(existing) if same_command(&wanted_json, existing) => plan.unchanged.push(name.clone()),
+                Some(existing) => plan.update.push((name.clone(), existing.id, command)),
+                None => plan.create.push((name.clone(), command)),
+            }
+
+            wanted_names.push(name);
+        }
+
+        for existing in registered {
+            if !wanted_names.contains(&existing.name) {
+                plan.stale.push((existing.name.clone(), existing.id));
+            }
+        }
+
+        plan
+    }
+
+    /// Checks if nothing needs to be sent to Discord
+    pub fn is_empty(&self) -> bool {
+        self.create.is_empty() && self.update.is_empty() && self.stale.is_empty()
+    }
+}
+
+/// Brings the registered commands in line with the wanted ones
+///
+/// # Parameters
+/// * `http: &Http` - Http client used to talk to Discord
+/// * `scope: Scope` - Where to register the commands
+/// * `wanted: Vec<CreateCommand>` - Commands the bot should have
+/// * `purge_stale: bool` - Whether to delete registered commands that aren't wanted anymore
+pub async fn sync_commands(
+    http: &Http,
+    scope: Scope,
+    wanted: Vec<CreateCommand>,
+    purge_stale: bool,
+) -> Result<()> {
+    // Without the localizations every localized command would look changed
+    let registered = match scope {
+        Scope::Global => Command::get_global_commands_with_localizations(http).await?,
+        Scope::Guild(guild_id) => guild_id.get_commands_with_localizations(http).await?,
+    };
+
+    let plan = RegistrationPlan::new(wanted, &registered);
+
+    if plan.is_empty() {
+        info!(?scope, commands = plan.unchanged.len(), "commands are up to date");
+        return Ok(());
+    }
+
+    for (name, command) in plan.create {
+        match scope {
+            Scope::Global => Command::create_global_command(http, command).await?,
+            Scope::Guild(guild_id) => guild_id.create_command(http, command).await?,
+        };
+        info!(?scope, command = name, "created command");
+    }
+
+    for (name, id, command) in plan.update {
+        match scope {
+            Scope::Global => Command::edit_global_command(http, id, command).await?,
+            Scope::Guild(guild_id) => guild_id.edit_command(http, id, command).await?,
+        };
+        info!(?scope, command = name, "updated command");
+    }
+
+    for (name, id) in plan.stale {
+        if !purge_stale {
+            warn!(?scope, command = name, "command is registered but unused, start with --purge-stale-commands to delete it");
+            continue;
+        }
+
+        match scope {
+            Scope::Global => Command::delete_global_command(http, id).await?,
+            Scope::Guild(guild_id) => guild_id.delete_command(http, id).await?,
+        };
+        info!(?scope, command = name, "deleted stale command");
+    }
+
+    Ok(())
+}
+
+/// Checks if a registered command matches a wanted one
+fn same_command(wanted: &Value, registered: &Command) -> bool {
+    let registered = normalize(serde_json::to_value(registered).unwrap_or_default());
+
+    let (Some(wanted), Some(registered)) = (wanted.as_object(), registered.as_object()) else {
+        return false;
+    };
+
+    // Anything the wanted command sets is compared, along with the fields it may have dropped
+    wanted
+        .keys()
+        .map(String::as_str)
+        .chain(COMPARED_FIELDS.iter().copied())
+        .all(|key| wanted.get(key) == registered.get(key))
+}
+
+/// Removes values that mean the same as leaving the field out, so both sides look alike
+/// Nulls, `false`, and empty lists and maps are dropped
+fn normalize(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (key, normalize(value)))
+                .filter(|(_, value)| !is_empty_value(value))
+                .collect::<Map<String, Value>>(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(normalize).collect()),
+        value => value,
+    }
+}
+
+fn is_empty_value(value: &Value) -> bool {
+    match value {
+        Value::Null | Value::Bool(false) => true,
+        Value::Array(values) => values.is_empty(),
+        Value::Object(map) => map.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use serenity::all::{CommandOptionType, CreateCommandOption};
+
+    use super::*;
+    use crate::{commands::create_commands, config::Config};
+
+    /// Makes a command the way Discord sends it back once registered
+    fn registered(id: u64, command: Value) -> Command {
+        let mut command = command;
+        let fields = command.as_object_mut().unwrap();
+        fields.insert("id".to_string(), json!(id.to_string()));
+        fields.insert("application_id".to_string(), json!("1"));
+        fields.insert("version".to_string(), json!("1"));
+        fields.insert("type".to_string(), json!(1));
+        fields.entry("default_member_permissions").or_insert(Value::Null);
+        fields.entry("nsfw").or_insert(json!(false));
+
+        serde_json::from_value(command).unwrap()
+    }
+
+    fn registered_from(id: u64, command: &CreateCommand) -> Command {
+        registered(id, serde_json::to_value(command).unwrap())
+    }
+
+    fn truth() -> CreateCommand {
+        CreateCommand::new("truth").description("Get a truth question").add_option(
+            CreateCommandOption::new(CommandOptionType::String, "rating", "Rating of the question").required(false),
+        )
+    }
+
+    fn names(plan: &RegistrationPlan) -> (Vec<&str>, Vec<&str>, Vec<&str>, Vec<&str>) {
+        (
+            plan.create.iter().map(|(name, _)| name.as_str()).collect(),
+            plan.update.iter().map(|(name, _, _)| name.as_str()).collect(),
+            plan.stale.iter().map(|(name, _)| name.as_str()).collect(),
+            plan.unchanged.iter().map(String::as_str).collect(),
+        )
+    }
+
+    #[test]
+    fn unchanged_command() {
+        let plan = RegistrationPlan::new(vec![truth()], &[registered_from(10, &truth())]);
+
+        assert!(plan.is_empty());
+        assert_eq!(names(&plan), (vec![], vec![], vec![], vec!["truth"]));
+    }
+
+    #[test]
+    fn changed_description() {
+        let wanted = truth().description("Get a truth question to answer");
+        let plan = RegistrationPlan::new(vec![wanted], &[registered_from(10, &truth())]);
+
+        assert_eq!(names(&plan), (vec![], vec!["truth"], vec![], vec![]));
+        assert_eq!(plan.update[0].1, CommandId::new(10));
+    }
+
+    #[test]
+    fn new_command() {
+        let dare = CreateCommand::new("dare").description("Get a dare");
+        let plan = RegistrationPlan::new(vec![truth(), dare], &[registered_from(10, &truth())]);
+
+        assert_eq!(names(&plan), (vec!["dare"], vec![], vec![], vec!["truth"]));
+    }
+
+    #[test]
+    fn stale_command() {
+        let dare = CreateCommand::new("dare").description("Get a dare");
+        let plan = RegistrationPlan::new(vec![truth()], &[registered_from(10, &truth()), registered_from(11, &dare)]);
+
+        assert_eq!(names(&plan), (vec![], vec![], vec!["dare"], vec!["truth"]));
+        assert_eq!(plan.stale[0].1, CommandId::new(11));
+    }
+
+    #[test]
+    fn empty_fields_match_missing_ones() {
+        // Discord fills in fields the bot never set, with values that mean the same as leaving them out
+        let mut command = serde_json::to_value(truth()).unwrap();
+        command["options"][0]["required"] = json!(false);
+        command["options"][0]["choices"] = json!([]);
+        command["name_localizations"] = Value::Null;
+        command["description_localizations"] = json!({});
+        command["default_member_permissions"] = Value::Null;
+
+        let plan = RegistrationPlan::new(vec![truth()], &[registered(10, command)]);
+
+        assert!(plan.is_empty());
+    }
+
+    #[test]
+    fn dropped_field_is_an_update() {
+        // A registered field the wanted command no longer sets still has to be removed
+        let mut command = serde_json::to_value(truth()).unwrap();
+        command["default_member_permissions"] = json!("8");
+
+        let plan = RegistrationPlan::new(vec![truth()], &[registered(10, command)]);
+
+        assert_eq!(names(&plan), (vec![], vec!["truth"], vec![], vec![]));
+    }
+
+    /// Removes every localization map, the way Discord leaves them out unless they are asked for
+    fn strip_localizations(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                map.remove("name_localizations");
+                map.remove("description_localizations");
+                map.values_mut().for_each(strip_localizations);
+            }
+            Value::Array(values) => values.iter_mut().for_each(strip_localizations),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn localized_commands() {
+        let wanted = create_commands(&Config::default());
+        assert!(wanted.iter().all(|command| serde_json::to_value(command).unwrap()["name_localizations"].is_object()));
+
+        let fetched: Vec<Command> = (10..).zip(&wanted).map(|(id, command)| registered_from(id, command)).collect();
+        let plan = RegistrationPlan::new(wanted.clone(), &fetched);
+
+        assert!(plan.is_empty(), "changed: {:?}", names(&plan).1);
+
+        // Commands fetched without their localizations can't be compared
+        let fetched: Vec<Command> = (10..)
+            .zip(&wanted)
+            .map(|(id, command)| {
+                let mut command = serde_json::to_value(command).unwrap();
+                strip_localizations(&mut command);
+                registered(id, command)
+            })
+            .collect();
+        let plan = RegistrationPlan::new(wanted.clone(), &fetched);
+
+        assert_eq!(plan.update.len(), wanted.len());
+    }
+}
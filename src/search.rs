@@ -0,0 +1,63 @@
+//! Search module for the bot
+//! Full-text search over the question bank backed by the `questions_fts` index
+
+use serenity::all::{CommandOptionType, CreateCommand, CreateCommandOption};
+
+use crate::{
+    bot::Bot,
+    commands::{add_list_options, list_ephemeral, list_filter, list_sort},
+    embed::send_page,
+    error::Result,
+    i18n::CommandLocalizer,
+    invocation::{Invocation, Reply},
+    menu_type::MenuType,
+    pagination::{ListFilter, PageState, SortOrder},
+    permissions::{Capability, Member},
+};
+
+/// Longest query that still fits into a page button id
+pub const MAX_QUERY_LENGTH: u16 = 60;
+
+/// Command to search the questions
+pub fn search_questions_command() -> CreateCommand {
+    add_list_options(
+        CreateCommand::new("search_questions")
+            .localized("search_questions")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "query", "Words to search for")
+                    .required(true)
+                    .max_length(MAX_QUERY_LENGTH),
+            ),
+    )
+    .add_option(CreateCommandOption::new(
+        CommandOptionType::Boolean,
+        "custom_only",
+        "Only show questions added in this server",
+    ))
+}
+
+pub async fn search_questions(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let filter = ListFilter {
+        query: invocation.str_option("query").map(|s| s.to_string()),
+        custom_only: invocation.bool_option("custom_only").unwrap_or(false),
+        ..list_filter(invocation)
+    };
+
+    // Best matches come first unless another order was picked
+    let state = PageState::new(
+        MenuType::SEARCH,
+        filter,
+        list_sort(invocation).unwrap_or(SortOrder::Relevance),
+    );
+
+    // Moderators also see who added each question
+    let show_authors = bot.require(Member::from(invocation), Capability::Moderate).await.is_ok();
+
+    Ok(send_page(bot, invocation.guild_id, state, show_authors, language)
+        .await?
+        .ephemeral(list_ephemeral(invocation)))
+}
@@ -0,0 +1,54 @@
+//! Server module for the bot
+//! Serves the optional HTTP endpoints, endpoints given the same address share a listener
+
+use std::net::SocketAddr;
+
+use axum::Router;
+use tracing::{error, info};
+
+/// Serves every router on its address until the bot stops
+///
+/// # Parameters
+/// * `routers: Vec<(SocketAddr, Router)>` - Routers and the address to serve each on,
+///   keep them local unless they are firewalled
+pub async fn serve(routers: Vec<(SocketAddr, Router)>) {
+    // Merge routers that share an address
+    let mut servers: Vec<(SocketAddr, Router)> = Vec::new();
+
+    for (addr, router) in routers {
+        match servers.iter().position(|(a, _)| *a == addr) {
+            Some(i) => {
+                let (_, existing) = servers.remove(i);
+                servers.push((addr, existing.merge(router)));
+            }
+            None => servers.push((addr, router)),
+        }
+    }
+
+    let tasks: Vec<_> = servers
+        .into_iter()
+        .map(|(addr, router)| tokio::spawn(serve_router(addr, router)))
+        .collect();
+
+    for task in tasks {
+        if let Err(e) = task.await {
+            error!(error = %e, "http server task failed");
+        }
+    }
+}
+
+async fn serve_router(addr: SocketAddr, router: Router) {
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(%addr, error = %e, "failed to bind http server");
+            return;
+        }
+    };
+
+    info!(%addr, "serving http endpoints");
+
+    if let Err(e) = axum::serve(listener, router).await {
+        error!(%addr, error = %e, "http server stopped");
+    }
+}
@@ -0,0 +1,97 @@
+//! Signing module for the bot
+//! Signs data that round-trips through Discord (like button ids) so it can be trusted when it comes back
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+/// Bytes of the HMAC kept in a signature, ids have a length limit so it is truncated
+const SIGNATURE_BYTES: usize = 6;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Gets the signing key from the configured secret, or derives one from the bot token
+/// so signatures stay valid across restarts
+///
+/// # Parameters
+/// * `secret: Option<&str>` - The `signing_secret` setting
+/// * `token: &str` - Discord token of the bot
+pub fn signing_key(secret: Option<&str>, token: &str) -> Vec<u8> {
+    match secret {
+        Some(secret) => secret.as_bytes().to_vec(),
+        None => Sha256::new()
+            .chain_update(b"truth_or_dare_bot signing key:")
+            .chain_update(token.as_bytes())
+            .finalize()
+            .to_vec(),
+    }
+}
+
+fn mac(key: &[u8], message: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    mac
+}
+
+/// Signs a message
+///
+/// # Returns
+/// * `String` - The truncated signature as lowercase hex
+pub fn sign(key: &[u8], message: &str) -> String {
+    mac(key, message).finalize().into_bytes()[..SIGNATURE_BYTES]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Checks a signature made by `sign` in constant time
+pub fn verify(key: &[u8], message: &str, signature: &str) -> bool {
+    if signature.len() != SIGNATURE_BYTES * 2 || !signature.is_ascii() {
+        return false;
+    }
+
+    let bytes: Option<Vec<u8>> = (0..signature.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&signature[i..i + 2], 16).ok())
+        .collect();
+
+    match bytes {
+        Some(bytes) => mac(key, message).verify_truncated_left(&bytes).is_ok(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let signature = sign(b"key", "message");
+
+        assert_eq!(signature.len(), SIGNATURE_BYTES * 2);
+        assert!(verify(b"key", "message", &signature));
+    }
+
+    #[test]
+    fn tampered() {
+        let signature = sign(b"key", "message");
+
+        assert!(!verify(b"key", "massage", &signature));
+        assert!(!verify(b"other key", "message", &signature));
+
+        let mut flipped = signature.clone().into_bytes();
+        flipped[0] = if flipped[0] == b'0' { b'1' } else { b'0' };
+        assert!(!verify(b"key", "message", &String::from_utf8(flipped).unwrap()));
+    }
+
+    #[test]
+    fn malformed() {
+        let signature = sign(b"key", "message");
+
+        assert!(!verify(b"key", "message", ""));
+        assert!(!verify(b"key", "message", &signature[..10]));
+        assert!(!verify(b"key", "message", &format!("{signature}00")));
+        assert!(!verify(b"key", "message", "zzzzzzzzzzzz"));
+        assert!(!verify(b"key", "message", "ééééé¢"));
+    }
+}
@@ -0,0 +1,215 @@
+//! Submission module for the bot
+//! Checks questions before they are saved: the content policy runs first, then the guild's
+//! pool is checked for copies of the same prompt that only differ in case, spacing or punctuation
+
+use serenity::all::GuildId;
+
+use crate::{
+    bot::Bot,
+    content_policy::check_content,
+    error::{Error, Result},
+    i18n::{t, tf},
+    questions::Question,
+};
+
+/// A question that passed the checks
+#[derive(Debug, Clone)]
+pub struct Submission {
+    /// The prompt to save, with mentions and links removed
+    pub prompt: String,
+    /// Whether a moderator has to approve the question before it is drawn
+    pub needs_review: bool,
+    /// A question that is close to the prompt without being the same, worth a warning
+    pub similar: Option<Question>,
+}
+
+impl Submission {
+    /// Gets the warning to show about a similar question, if there is one
+    pub fn warning(&self, language: &str) -> Option<String> {
+        self.similar.as_ref().map(|question| match question.guild_id {
+            0 => tf(language, "duplicate.similar_default", &[("question", &question.prompt)]),
+            _ => tf(
+                language,
+                "duplicate.similar_custom",
+                &[("question", &question.prompt), ("uid", &question.uid)],
+            ),
+        })
+    }
+}
+
+/// Checks a question before it is added to a guild
+/// The content policy is checked first, then a question that is the same as an existing one once normalized is rejected,
+/// and the closest one above `duplicate_threshold` is kept to warn about
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to access the database
+/// * `guild_id: GuildId` - Guild the question is added to, its questions and the defaults are checked
+/// * `prompt: &str` - The question as it was submitted
+/// * `rating: &str` - Rating the question was submitted with
+/// * `ignore_uid: Option<&str>` - Question being edited, which shouldn't match itself
+/// * `language: &str` - Language to write errors in
+pub async fn validate_submission(
+    bot: &Bot,
+    guild_id: GuildId,
+    prompt: &str,
+    rating: &str,
+    ignore_uid: Option<&str>,
+    language: &str,
+) -> Result<Submission> {
+    let content = check_content(bot, guild_id, prompt, rating, language).await?;
+    let normalized = normalize(&content.prompt);
+
+    // A prompt of only punctuation is as good as empty
+    if normalized.is_empty() {
+        return Err(Error::InvalidInput(t(language, "add_question.empty")));
+    }
+
+    let mut similar: Option<(f64, Question)> = None;
+
+    for question in bot.get_questions(Some(guild_id)).await? {
+        if Some(question.uid.as_str()) == ignore_uid {
+            continue;
+        }
+
+        let existing = normalize(&question.prompt);
+
+        if existing == normalized {
+            return Err(Error::InvalidInput(match question.guild_id {
+                0 => t(language, "duplicate.exact_default"),
+                _ => tf(language, "duplicate.exact_custom", &[("uid", &question.uid)]),
+            }));
+        }
+
+        let score = similarity(&existing, &normalized, bot.config.duplicate_threshold);
+
+        if score >= bot.config.duplicate_threshold
+            && similar.as_ref().is_none_or(|(best, _)| score > *best)
+        {
+            similar = Some((score, question));
+        }
+    }
+
+    Ok(Submission {
+        prompt: content.prompt,
+        needs_review: content.needs_review,
+        similar: similar.map(|(_, question)| question),
+    })
+}
+
+/// Lowercases a prompt and drops punctuation, so `Who's your crush?` and `whos your crush`
+/// are the same
+pub fn normalize(prompt: &str) -> String {
+    prompt
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Scores how alike two normalized prompts are from 0 to 1, using the edit distance
+/// Pairs too different in length to reach `threshold` are scored 0 without comparing them
+pub fn similarity(a: &str, b: &str, threshold: f64) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+
+    if longest == 0 {
+        return 1.0;
+    }
+
+    // The distance is at least the difference in length
+    if 1.0 - (a.len().abs_diff(b.len()) as f64 / longest as f64) < threshold {
+        return 0.0;
+    }
+
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+/// Counts the single character edits needed to turn one text into the other
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn threshold() -> f64 {
+        Config::default().duplicate_threshold
+    }
+
+    fn score(a: &str, b: &str) -> f64 {
+        similarity(&normalize(a), &normalize(b), threshold())
+    }
+
+    #[test]
+    fn normalize_ignores_case_spacing_and_punctuation() {
+        assert_eq!(normalize("  Have you EVER   lied?!  "), "have you ever lied");
+        assert_eq!(normalize("What's your\tfavourite\nfood…"), "whats your favourite food");
+        assert_eq!(normalize("¿Qué harías?"), "qué harías");
+        assert_eq!(normalize("?!..."), "");
+    }
+
+    #[test]
+    fn exact_duplicates_after_normalizing() {
+        assert_eq!(score("Have you ever lied to a friend?", "have you ever lied to a friend"), 1.0);
+        assert_eq!(similarity("", "", threshold()), 1.0);
+    }
+
+    #[test]
+    fn near_duplicates() {
+        for (a, b) in [
+            ("Have you ever lied to your best friend?", "Have you ever lied to your best friends?"),
+            ("What is your biggest fear?", "What's your biggest fear?"),
+            ("Sing a song for the group", "Sing a song to the group"),
+        ] {
+            let score = score(a, b);
+            assert!(score >= threshold() && score < 1.0, "{a:?} and {b:?} scored {score}");
+        }
+    }
+
+    #[test]
+    fn distinct_prompts() {
+        for (a, b) in [
+            ("Have you ever lied to your best friend?", "Have you ever cheated on a test?"),
+            ("What is your biggest fear?", "What is your favourite food?"),
+            ("Do ten push ups", "Do your best impression of someone here"),
+        ] {
+            let score = score(a, b);
+            assert!(score < threshold(), "{a:?} and {b:?} scored {score}");
+        }
+    }
+
+    #[test]
+    fn very_different_lengths_are_not_compared() {
+        assert_eq!(similarity("sing", "sing a song for everyone in the room", threshold()), 0.0);
+    }
+
+    #[test]
+    fn edit_distance() {
+        let chars = |text: &str| text.chars().collect::<Vec<_>>();
+
+        assert_eq!(levenshtein(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(levenshtein(&chars(""), &chars("abc")), 3);
+        assert_eq!(levenshtein(&chars("café"), &chars("cafe")), 1);
+        assert_eq!(levenshtein(&chars("same"), &chars("same")), 0);
+    }
+}
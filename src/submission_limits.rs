@@ -0,0 +1,406 @@
+//! Submission limits module for the bot
+//! Lets moderators ban members from adding questions in a guild, for good or for some days, and
+//! caps how many questions each member can add in 24 hours. Every way of adding questions goes
+//! through `Bot::check_submitter`, moderators are trusted with as many questions as they want.
+
+use serenity::all::{CommandOptionType, CreateCommand, CreateCommandOption, GuildId, UserId};
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    bot::Bot,
+    embed::{fit_lines, MAX_MESSAGE_LENGTH},
+    error::{Error, Result},
+    i18n::{t, tf, CommandLocalizer},
+    invocation::{Invocation, Reply},
+    permissions::{Capability, Member},
+};
+
+/// Longest reason a ban can be given
+const MAX_REASON_LENGTH: u16 = 200;
+
+/// Longest a ban with an end can last, longer bans should have no end
+const MAX_BAN_DAYS: i64 = 365;
+
+/// Highest quota a guild can set
+const MAX_QUOTA: i64 = 1000;
+
+/// Most bans listed at once, the lines are also cut when the list is too long for a message
+const MAX_LISTED_BANS: usize = 15;
+
+/// A member who can't add questions in a guild
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubmissionBan {
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    /// Moderator who banned the member
+    pub moderator_id: UserId,
+    pub reason: Option<String>,
+    /// Unix timestamp of the ban
+    pub created_at: i64,
+    /// Unix timestamp the ban ends at, `None` if it lasts until it is lifted
+    pub expires_at: Option<i64>,
+}
+
+impl SubmissionBan {
+    /// Describes the ban on one line for the list of bans
+    pub fn describe(&self, language: &str) -> String {
+        let mut line = tf(
+            language,
+            "submissions.ban_line",
+            &[
+                ("user", &self.user_id),
+                ("moderator", &self.moderator_id),
+                ("created", &self.created_at),
+            ],
+        );
+
+        if let Some(expires_at) = self.expires_at {
+            line.push_str(&tf(language, "submissions.until", &[("expires", &expires_at)]));
+        }
+        if let Some(reason) = &self.reason {
+            line.push_str(&tf(language, "submissions.ban_reason", &[("reason", reason)]));
+        }
+
+        line
+    }
+
+    /// Gets the message shown to the banned member when they try to add a question
+    pub fn message(&self, language: &str) -> String {
+        let mut message = t(language, "submissions.banned");
+
+        if let Some(expires_at) = self.expires_at {
+            message.push_str(&tf(language, "submissions.banned_until", &[("expires", &expires_at)]));
+        }
+        if let Some(reason) = &self.reason {
+            message.push_str(&tf(language, "submissions.reason", &[("reason", reason)]));
+        }
+
+        message
+    }
+}
+
+impl FromRow<'_, SqliteRow> for SubmissionBan {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        Ok(SubmissionBan {
+            guild_id: GuildId::new(row.try_get::<i64, _>("guild_id")? as u64),
+            user_id: UserId::new(row.try_get::<i64, _>("user_id")? as u64),
+            moderator_id: UserId::new(row.try_get::<i64, _>("moderator_id")? as u64),
+            reason: row.try_get("reason")?,
+            created_at: row.try_get("created_at")?,
+            expires_at: row.try_get("expires_at")?,
+        })
+    }
+}
+
+/// Columns of a ban, with the timestamps as unix seconds
+const BAN_COLUMNS: &str = r#"
+    guild_id, user_id, moderator_id, reason,
+    CAST(strftime('%s', created_at) AS INTEGER) AS created_at,
+    CAST(strftime('%s', expires_at) AS INTEGER) AS expires_at
+"#;
+
+/// Bans that haven't ended
+const ACTIVE_BAN: &str = "(expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)";
+
+impl Bot {
+    /// Checks a member can add questions: they aren't banned, and adding `count` more stays within the quota
+    /// Meant for every way of adding questions, so a batch of questions counts as many as it has
+    ///
+    /// # Parameters
+    /// * `member: Member<'_>` - The member adding the questions
+    /// * `count: u32` - How many questions they are adding
+    /// * `language: &str` - Language to write errors in
+    pub async fn check_submitter(&self, member: Member<'_>, count: u32, language: &str) -> Result<()> {
+        let guild_id = member.guild_id.ok_or(Error::GuildOnly)?;
+
+        self.require_not_banned(guild_id, member.user_id, language).await?;
+
+        // Moderators are trusted with as many questions as they want
+        if self.require(member, Capability::Moderate).await.is_ok() {
+            return Ok(());
+        }
+
+        let quota = self.get_submission_quota(guild_id).await?;
+
+        if quota == 0 {
+            return Ok(());
+        }
+
+        let (recent, oldest) = self.get_recent_submissions(guild_id, member.user_id).await?;
+
+        if recent + i64::from(count) <= i64::from(quota) {
+            return Ok(());
+        }
+
+        let mut message = tf(language, "submissions.quota_reached", &[("quota", &quota)]);
+
+        // The oldest question leaving the 24 hours is when the member can add one again
+        if let Some(oldest) = oldest {
+            message.push_str(&tf(language, "submissions.retry", &[("retry", &(oldest + 86_400))]));
+        }
+
+        Err(Error::InvalidInput(message))
+    }
+
+    /// Fails with a message for the member if they are banned from adding questions
+    pub async fn require_not_banned(&self, guild_id: GuildId, user_id: UserId, language: &str) -> Result<()> {
+        match self.get_submission_ban(guild_id, user_id).await? {
+            Some(ban) => Err(Error::InvalidInput(ban.message(language))),
+            None => Ok(()),
+        }
+    }
+
+    /// Counts the questions a member added in a guild in the last 24 hours
+    /// Questions in the trash still count, so removing questions doesn't free up the quota
+    ///
+    /// # Returns
+    /// * `(i64, Option<i64>)` - How many were added, and the unix timestamp of the oldest one
+    pub async fn get_recent_submissions(&self, guild_id: GuildId, user_id: UserId) -> Result<(i64, Option<i64>)> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) AS recent, CAST(strftime('%s', MIN(created_at)) AS INTEGER) AS oldest
+            FROM questions
+            WHERE guild_id = ? AND author_id = ? AND created_at > datetime('now', '-1 day')
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .fetch_one(&self.database)
+        .await?;
+
+        Ok((row.try_get("recent")?, row.try_get("oldest")?))
+    }
+
+    /// Gets the ban of a member, `None` if they aren't banned or their ban ended
+    pub async fn get_submission_ban(&self, guild_id: GuildId, user_id: UserId) -> Result<Option<SubmissionBan>> {
+        let ban = sqlx::query_as::<_, SubmissionBan>(&format!(
+            r#"
+            SELECT {BAN_COLUMNS} FROM submission_bans
+            WHERE guild_id = ? AND user_id = ? AND {ACTIVE_BAN}
+            "#
+        ))
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .fetch_optional(&self.database)
+        .await?;
+
+        Ok(ban)
+    }
+
+    /// Gets the bans of a guild that haven't ended, newest first
+    pub async fn get_submission_bans(&self, guild_id: GuildId) -> Result<Vec<SubmissionBan>> {
+        let bans = sqlx::query_as::<_, SubmissionBan>(&format!(
+            r#"
+            SELECT {BAN_COLUMNS} FROM submission_bans
+            WHERE guild_id = ? AND {ACTIVE_BAN}
+            ORDER BY created_at DESC
+            "#
+        ))
+        .bind(guild_id.get() as i64)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(bans)
+    }
+
+    /// Bans a member from adding questions, replacing any ban they already had
+    ///
+    /// # Parameters
+    /// * `guild_id: GuildId` - The guild to ban the member in
+    /// * `user_id: UserId` - The member to ban
+    /// * `moderator_id: UserId` - The moderator banning them
+    /// * `reason: Option<&str>` - Why they were banned, shown to them
+    /// * `days: Option<i64>` - How many days the ban lasts, `None` until it is lifted
+    pub async fn ban_submitter(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        moderator_id: UserId,
+        reason: Option<&str>,
+        days: Option<i64>,
+    ) -> Result<SubmissionBan> {
+        let ban = sqlx::query_as::<_, SubmissionBan>(&format!(
+            r#"
+            INSERT OR REPLACE INTO submission_bans (guild_id, user_id, moderator_id, reason, created_at, expires_at)
+            VALUES (?, ?, ?, ?, CURRENT_TIMESTAMP, datetime('now', ?))
+            RETURNING {BAN_COLUMNS}
+            "#
+        ))
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .bind(moderator_id.get() as i64)
+        .bind(reason)
+        // datetime gives NULL for a NULL modifier, which is a ban without an end
+        .bind(days.map(|days| format!("+{days} days")))
+        .fetch_one(&self.database)
+        .await?;
+
+        Ok(ban)
+    }
+
+    /// Lifts the ban of a member, returning false if they weren't banned
+    pub async fn unban_submitter(&self, guild_id: GuildId, user_id: UserId) -> Result<bool> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM submission_bans WHERE guild_id = ? AND user_id = ? AND {ACTIVE_BAN}"
+        ))
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .execute(&self.database)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Command to ban members from adding questions and set the daily quota
+pub fn submissions_command() -> CreateCommand {
+    let user_option = |description| CreateCommandOption::new(CommandOptionType::User, "user", description).required(true);
+
+    CreateCommand::new("submissions")
+        .localized("submissions")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "ban", "Stop a member from adding questions")
+                .add_sub_option(user_option("The member to ban"))
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "reason", "Why, shown to the member")
+                        .max_length(MAX_REASON_LENGTH),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "days",
+                        "How many days the ban lasts, leave empty until it is lifted",
+                    )
+                    .min_int_value(1)
+                    .max_int_value(MAX_BAN_DAYS as u64),
+                ),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "unban", "Let a member add questions again")
+                .add_sub_option(user_option("The member to unban")),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "bans",
+            "Show the members who can't add questions",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "quota",
+                "Set how many questions each member can add in 24 hours",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "per_day",
+                    "Questions per member, 0 for no limit, leave empty for the default",
+                )
+                .min_int_value(0)
+                .max_int_value(MAX_QUOTA as u64),
+            ),
+        )
+}
+
+pub async fn submissions(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+    let user_id = invocation.user_option("user");
+
+    match (invocation.subcommand.as_deref(), user_id) {
+        (Some("ban"), Some(user_id)) => {
+            if user_id == invocation.user_id {
+                return Err(Error::InvalidInput(t(language, "submissions.ban_self")));
+            }
+
+            let reason = invocation
+                .str_option("reason")
+                .map(str::trim)
+                .filter(|reason| !reason.is_empty());
+            let days = invocation.int_option("days").map(|days| days.clamp(1, MAX_BAN_DAYS));
+
+            let ban = bot.ban_submitter(guild_id, user_id, invocation.user_id, reason, days).await?;
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::BanSubmitter) {
+                let banned = match reason {
+                    Some(reason) => format!("<@{user_id}> ({reason})"),
+                    None => format!("<@{user_id}>"),
+                };
+                bot.record_audit(entry.after(Some(banned))).await;
+            }
+
+            let mut reply = tf(language, "submissions.ban", &[("user", &user_id)]);
+
+            if let Some(expires_at) = ban.expires_at {
+                reply.push_str(&tf(language, "submissions.banned_until", &[("expires", &expires_at)]));
+            }
+
+            Ok(Reply::text(reply).ephemeral(true))
+        }
+        (Some("unban"), Some(user_id)) => {
+            if !bot.unban_submitter(guild_id, user_id).await? {
+                return Err(Error::InvalidInput(tf(language, "submissions.not_banned", &[("user", &user_id)])));
+            }
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::UnbanSubmitter) {
+                bot.record_audit(entry.after(Some(format!("<@{user_id}>")))).await;
+            }
+
+            Ok(Reply::text(tf(language, "submissions.unban", &[("user", &user_id)])).ephemeral(true))
+        }
+        (Some("bans"), _) => {
+            let bans = bot.get_submission_bans(guild_id).await?;
+
+            if bans.is_empty() {
+                return Ok(Reply::text(t(language, "submissions.no_bans")).ephemeral(true));
+            }
+
+            let lines: Vec<String> = bans.iter().take(MAX_LISTED_BANS).map(|ban| ban.describe(language)).collect();
+            let more = (bans.len() > MAX_LISTED_BANS)
+                .then(|| tf(language, "submissions.more_bans", &[("count", &(bans.len() - MAX_LISTED_BANS))]));
+
+            // Reasons come last, so long lines are cut at the end to fit the list in a message
+            let header = tf(language, "submissions.bans", &[("bans", &"")]).chars().count();
+            let footer = more.as_ref().map_or(0, |more| more.chars().count() + 1);
+            let mut list = fit_lines(
+                &lines,
+                MAX_MESSAGE_LENGTH.saturating_sub(header + footer),
+                |line| line.as_str(),
+                |_, line| line.to_string(),
+            );
+
+            if let Some(more) = more {
+                list.push('\n');
+                list.push_str(&more);
+            }
+
+            Ok(Reply::text(tf(language, "submissions.bans", &[("bans", &list)])).ephemeral(true))
+        }
+        (Some("quota"), _) => {
+            // Handling bans is moderation, changing the quota is a setting
+            bot.require(Member::from(invocation), Capability::Settings).await?;
+
+            let quota = invocation
+                .int_option("per_day")
+                .map(|quota| quota.clamp(0, MAX_QUOTA) as u32);
+            let before = bot.get_submission_quota(guild_id).await?;
+
+            bot.set_submission_quota(guild_id, quota).await?;
+            let after = bot.get_submission_quota(guild_id).await?;
+
+            if let Some(entry) = AuditEntry::from_invocation(invocation, AuditAction::SetSubmissionQuota) {
+                bot.record_audit(entry.before(Some(before)).after(Some(after))).await;
+            }
+
+            Ok(Reply::text(match after {
+                0 => t(language, "submissions.quota_off"),
+                _ => tf(language, "submissions.quota_set", &[("quota", &after)]),
+            }))
+        }
+        _ => Err(Error::InvalidInput(t(language, "common.unknown"))),
+    }
+}
@@ -0,0 +1,576 @@
+//! Text commands module for the bot
+//! Parses prefixed messages like `!add_question "Do you like cats?" truth PG` into the same
+//! options a slash command has, using the slash command definitions as the grammar
+//!
+//! Arguments fill the options in order, or can be named like `rating:PG-13`.
+//! Text with spaces goes in quotes, except for the last option which takes the rest of the message.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+use serenity::all::{ChannelId, CommandDataOptionValue, CommandOptionType, CreateCommand, Permissions, RoleId, UserId};
+
+use crate::error::{Error, Result};
+use crate::i18n::{t, tf};
+
+/// Longest text Discord allows in a string option
+const MAX_STRING_LENGTH: usize = 6000;
+
+/// Largest number Discord allows in an integer option
+const MAX_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// Definition of a slash command, read back from the `CreateCommand` that registers it
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandSpec {
+    pub name: String,
+    #[serde(default)]
+    name_localizations: HashMap<String, String>,
+    #[serde(default)]
+    options: Vec<OptionSpec>,
+    /// Permission bits a member needs, as a string like Discord sends it
+    default_member_permissions: Option<String>,
+}
+
+/// Definition of a command option
+#[derive(Debug, Clone, Deserialize)]
+struct OptionSpec {
+    #[serde(rename = "type")]
+    kind: CommandOptionType,
+    name: String,
+    #[serde(default)]
+    required: bool,
+    #[serde(default)]
+    choices: Vec<ChoiceSpec>,
+    /// Options of a subcommand
+    #[serde(default)]
+    options: Vec<OptionSpec>,
+    /// Shortest and longest text a string option takes
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    /// Smallest and largest number an integer option takes
+    min_value: Option<f64>,
+    max_value: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChoiceSpec {
+    name: String,
+    value: Value,
+}
+
+/// Options parsed from the arguments of a text command
+#[derive(Debug, Clone, Default)]
+pub struct Arguments {
+    pub subcommand: Option<String>,
+    pub options: Vec<(String, CommandDataOptionValue)>,
+}
+
+/// A word of the arguments, or a quoted piece of text
+struct Token {
+    text: String,
+    /// Started with a quote, so it is never read as `name:value`
+    quoted: bool,
+}
+
+/// Reads the definitions of the commands, done once at startup since every prefixed message looks them up
+///
+/// # Parameters
+/// * `commands: &[CreateCommand]` - Commands the bot has
+pub fn command_specs(commands: &[CreateCommand]) -> Vec<CommandSpec> {
+    commands
+        .iter()
+        .filter_map(|command| {
+            serde_json::to_value(command)
+                .and_then(serde_json::from_value::<CommandSpec>)
+                .ok()
+        })
+        .collect()
+}
+
+/// Finds the command a message is running
+/// Names are matched ignoring case, and translated names work too
+///
+/// # Parameters
+/// * `text: &str` - The message without its prefix
+/// * `specs: &[CommandSpec]` - Definitions of the commands the bot has, from `command_specs`
+///
+/// # Returns
+/// * `Option<(&CommandSpec, &str)>` - The command and the text of its arguments, `None` if no command matches
+pub fn find_command<'s, 'a>(text: &'a str, specs: &'s [CommandSpec]) -> Option<(&'s CommandSpec, &'a str)> {
+    let text = text.trim_start();
+    let (name, arguments) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+
+    if name.is_empty() {
+        return None;
+    }
+
+    specs
+        .iter()
+        .find(|spec| {
+            spec.name.eq_ignore_ascii_case(name)
+                || spec
+                    .name_localizations
+                    .values()
+                    .any(|localized| localized.to_lowercase() == name.to_lowercase())
+        })
+        .map(|spec| (spec, arguments))
+}
+
+impl CommandSpec {
+    /// Gets the permissions a member needs to run the command, if it is limited
+    pub fn required_permissions(&self) -> Option<Permissions> {
+        self.default_member_permissions
+            .as_deref()
+            .and_then(|bits| bits.parse::<u64>().ok())
+            .map(Permissions::from_bits_truncate)
+    }
+
+    /// Parses the arguments of the command into its options
+    ///
+    /// # Parameters
+    /// * `arguments: &str` - Text after the command name
+    /// * `prefix: &str` - Prefix of the guild, used in the usage shown on errors
+    /// * `language: &str` - Language to write errors in
+    pub fn parse(&self, arguments: &str, prefix: &str, language: &str) -> Result<Arguments> {
+        let mut tokens = tokenize(arguments);
+
+        // Commands with subcommands take the subcommand as their first word
+        if self.options.iter().any(|option| option.kind == CommandOptionType::SubCommand) {
+            let subcommands: Vec<&str> = self.options.iter().map(|option| option.name.as_str()).collect();
+            let subcommand = (!tokens.is_empty())
+                .then(|| tokens.remove(0).text)
+                .and_then(|name| self.options.iter().find(|option| option.name.eq_ignore_ascii_case(&name)));
+
+            let Some(subcommand) = subcommand else {
+                return Err(self.usage_error(
+                    tf(language, "text_commands.subcommand", &[("subcommands", &subcommands.join(", "))]),
+                    None,
+                    prefix,
+                    language,
+                ));
+            };
+
+            return Ok(Arguments {
+                subcommand: Some(subcommand.name.clone()),
+                options: self.parse_options(&subcommand.options, tokens, Some(subcommand), prefix, language)?,
+            });
+        }
+
+        Ok(Arguments {
+            subcommand: None,
+            options: self.parse_options(&self.options, tokens, None, prefix, language)?,
+        })
+    }
+
+    /// Fills options from named arguments first, then the rest in order
+    fn parse_options(
+        &self,
+        specs: &[OptionSpec],
+        tokens: Vec<Token>,
+        subcommand: Option<&OptionSpec>,
+        prefix: &str,
+        language: &str,
+    ) -> Result<Vec<(String, CommandDataOptionValue)>> {
+        let mut options: Vec<(String, CommandDataOptionValue)> = Vec::new();
+        let mut positional = Vec::new();
+
+        for token in tokens {
+            let named = (!token.quoted)
+                .then(|| token.text.split_once(':'))
+                .flatten()
+                .and_then(|(name, value)| {
+                    specs
+                        .iter()
+                        .find(|spec| spec.name.eq_ignore_ascii_case(name))
+                        .map(|spec| (spec, value.to_string()))
+                });
+
+            match named {
+                Some((spec, value)) => {
+                    let value = self.convert(spec, &value, subcommand, prefix, language)?;
+                    options.retain(|(name, _)| *name != spec.name);
+                    options.push((spec.name.clone(), value));
+                }
+                None => positional.push(token.text),
+            }
+        }
+
+        // Options that weren't named are filled in the order the command lists them
+        let free: Vec<&OptionSpec> = specs
+            .iter()
+            .filter(|spec| !options.iter().any(|(name, _)| *name == spec.name))
+            .collect();
+        let mut free = free.into_iter();
+        let mut last: Option<(&OptionSpec, String)> = None;
+
+        for text in positional {
+            match free.next() {
+                Some(spec) => {
+                    if let Some((previous, value)) = last.take() {
+                        options.push((previous.name.clone(), self.convert(previous, &value, subcommand, prefix, language)?));
+                    }
+                    last = Some((spec, text));
+                }
+                // Extra words are part of the last option when it is free text
+                None => match &mut last {
+                    Some((spec, value)) if spec.kind == CommandOptionType::String && spec.choices.is_empty() => {
+                        value.push(' ');
+                        value.push_str(&text);
+                    }
+                    _ => {
+                        return Err(self.usage_error(t(language, "text_commands.extra"), subcommand, prefix, language));
+                    }
+                },
+            }
+        }
+
+        if let Some((spec, value)) = last {
+            options.push((spec.name.clone(), self.convert(spec, &value, subcommand, prefix, language)?));
+        }
+
+        if let Some(missing) = specs
+            .iter()
+            .find(|spec| spec.required && !options.iter().any(|(name, _)| *name == spec.name))
+        {
+            return Err(self.usage_error(
+                tf(language, "text_commands.missing", &[("option", &missing.name)]),
+                subcommand,
+                prefix,
+                language,
+            ));
+        }
+
+        Ok(options)
+    }
+
+    /// Converts an argument into the value of an option
+    /// Choices match their value or name ignoring case, so `truth` and `PG-13` both work
+    fn convert(
+        &self,
+        spec: &OptionSpec,
+        text: &str,
+        subcommand: Option<&OptionSpec>,
+        prefix: &str,
+        language: &str,
+    ) -> Result<CommandDataOptionValue> {
+        let value = if !spec.choices.is_empty() {
+            spec.choices
+                .iter()
+                .find(|choice| {
+                    choice.value.as_str().is_some_and(|value| value.eq_ignore_ascii_case(text))
+                        || choice.name.eq_ignore_ascii_case(text)
+                })
+                .and_then(|choice| choice.value.as_str())
+                .map(|value| CommandDataOptionValue::String(value.to_string()))
+        } else {
+            match spec.kind {
+                CommandOptionType::String if !text.is_empty() => {
+                    let (min, max) = (spec.min_length.unwrap_or(1), spec.max_length.unwrap_or(MAX_STRING_LENGTH));
+
+                    // Slash commands can't send text outside the limits, so text commands can't either
+                    if !(min..=max).contains(&text.chars().count()) {
+                        return Err(self.usage_error(
+                            tf(language, "text_commands.length", &[("option", &spec.name), ("min", &min), ("max", &max)]),
+                            subcommand,
+                            prefix,
+                            language,
+                        ));
+                    }
+
+                    Some(CommandDataOptionValue::String(text.to_string()))
+                }
+                CommandOptionType::Integer => match text.parse::<i64>() {
+                    Ok(number) => {
+                        let (min, max) = (spec.min_value.unwrap_or(-MAX_INTEGER), spec.max_value.unwrap_or(MAX_INTEGER));
+
+                        if !(min..=max).contains(&(number as f64)) {
+                            return Err(self.usage_error(
+                                tf(language, "text_commands.range", &[("option", &spec.name), ("min", &min), ("max", &max)]),
+                                subcommand,
+                                prefix,
+                                language,
+                            ));
+                        }
+
+                        Some(CommandDataOptionValue::Integer(number))
+                    }
+                    Err(_) => None,
+                },
+                CommandOptionType::Boolean => parse_bool(text).map(CommandDataOptionValue::Boolean),
+                CommandOptionType::Channel => parse_channel(text).map(CommandDataOptionValue::Channel),
+                CommandOptionType::Role => parse_role(text).map(CommandDataOptionValue::Role),
+                CommandOptionType::User => parse_user(text).map(CommandDataOptionValue::User),
+                _ => None,
+            }
+        };
+
+        value.ok_or_else(|| {
+            self.usage_error(
+                tf(language, "text_commands.invalid", &[("value", &text), ("option", &spec.name)]),
+                subcommand,
+                prefix,
+                language,
+            )
+        })
+    }
+
+    /// Shows how to run the command, e.g. `!add_question <question> <question_type: TRUTH|DARE> ...`
+    pub fn usage(&self, subcommand: Option<&str>, prefix: &str) -> String {
+        let subcommand = subcommand.and_then(|name| self.options.iter().find(|option| option.name == name));
+
+        let arguments: Vec<String> = match subcommand {
+            Some(subcommand) => std::iter::once(subcommand.name.clone())
+                .chain(subcommand.options.iter().map(OptionSpec::usage))
+                .collect(),
+            None if self.options.iter().any(|option| option.kind == CommandOptionType::SubCommand) => {
+                let names: Vec<&str> = self.options.iter().map(|option| option.name.as_str()).collect();
+                vec![format!("<{}>", names.join("|"))]
+            }
+            None => self.options.iter().map(OptionSpec::usage).collect(),
+        };
+
+        std::iter::once(format!("{prefix}{}", self.name))
+            .chain(arguments)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Makes an error that also shows how to run the command
+    fn usage_error(&self, message: String, subcommand: Option<&OptionSpec>, prefix: &str, language: &str) -> Error {
+        let usage = self.usage(subcommand.map(|option| option.name.as_str()), prefix);
+
+        Error::InvalidInput(format!(
+            "{message}\n{}",
+            tf(language, "text_commands.usage", &[("usage", &usage)])
+        ))
+    }
+}
+
+impl OptionSpec {
+    /// Shows an option as `<name>` when it is required or `[name]` when it isn't
+    fn usage(&self) -> String {
+        let choices: Vec<&str> = self.choices.iter().filter_map(|choice| choice.value.as_str()).collect();
+        let text = match choices.is_empty() {
+            true => self.name.clone(),
+            false => format!("{}: {}", self.name, choices.join("|")),
+        };
+
+        match self.required {
+            true => format!("<{text}>"),
+            false => format!("[{text}]"),
+        }
+    }
+}
+
+/// Splits arguments on spaces, keeping quoted text together
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut in_quotes = false;
+
+    for c in text.chars() {
+        match c {
+            // Phones often type curly quotes
+            '"' | '“' | '”' => {
+                in_quotes = !in_quotes;
+                current.get_or_insert_with(|| Token { text: String::new(), quoted: true });
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if let Some(token) = current.take() {
+                    tokens.push(token);
+                }
+            }
+            c => current
+                .get_or_insert_with(|| Token { text: String::new(), quoted: false })
+                .text
+                .push(c),
+        }
+    }
+
+    tokens.extend(current);
+    tokens
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Reads a channel mention like `<#123>` or a bare channel id
+fn parse_channel(text: &str) -> Option<ChannelId> {
+    let id = text
+        .strip_prefix("<#")
+        .and_then(|text| text.strip_suffix('>'))
+        .unwrap_or(text);
+
+    id.parse::<u64>().ok().filter(|id| *id != 0).map(ChannelId::new)
+}
+
+/// Reads a user mention like `<@123>` or `<@!123>`, or a bare user id
+fn parse_user(text: &str) -> Option<UserId> {
+    let id = text
+        .strip_prefix("<@")
+        .and_then(|text| text.strip_suffix('>'))
+        .map(|text| text.strip_prefix('!').unwrap_or(text))
+        .unwrap_or(text);
+
+    id.parse::<u64>().ok().filter(|id| *id != 0).map(UserId::new)
+}
+
+/// Reads a role mention like `<@&123>` or a bare role id
+fn parse_role(text: &str) -> Option<RoleId> {
+    let id = text
+        .strip_prefix("<@&")
+        .and_then(|text| text.strip_suffix('>'))
+        .unwrap_or(text);
+
+    id.parse::<u64>().ok().filter(|id| *id != 0).map(RoleId::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::CreateCommandOption;
+
+    use super::*;
+    use crate::{commands::create_commands, config::Config};
+
+    fn specs() -> Vec<CommandSpec> {
+        command_specs(&[
+            CreateCommand::new("add_question")
+                .description("Add a question")
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "question", "Question").required(true))
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "question_type", "Type")
+                        .required(true)
+                        .add_string_choice("Truth", "TRUTH")
+                        .add_string_choice("Dare", "DARE"),
+                )
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "tag", "Tag").max_length(10)),
+            CreateCommand::new("set_rate_limit")
+                .description("Set a rate limit")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "requests", "Requests")
+                        .required(true)
+                        .min_int_value(0)
+                        .max_int_value(100),
+                )
+                .add_option(CreateCommandOption::new(CommandOptionType::Boolean, "enabled", "Enabled")),
+            CreateCommand::new("qotd")
+                .description("Question of the day")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::SubCommand, "set", "Set it").add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::Channel, "channel", "Channel").required(true),
+                    ),
+                )
+                .add_option(CreateCommandOption::new(CommandOptionType::SubCommand, "show", "Show it")),
+        ])
+    }
+
+    fn parse(text: &str) -> Result<Arguments> {
+        let specs = specs();
+        let (spec, arguments) = find_command(text, &specs).expect("command exists");
+        spec.parse(arguments, "!", "en")
+    }
+
+    fn string(arguments: &Arguments, name: &str) -> Option<String> {
+        arguments.options.iter().find(|(option, _)| option == name).and_then(|(_, value)| match value {
+            CommandDataOptionValue::String(value) => Some(value.clone()),
+            _ => None,
+        })
+    }
+
+    fn invalid_input(result: Result<Arguments>) -> String {
+        match result {
+            Err(Error::InvalidInput(message)) => message,
+            other => panic!("expected invalid input, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn quoting() {
+        let tokens = tokenize(r#"one "two three" “four five” "" six"#);
+        let texts: Vec<(&str, bool)> = tokens.iter().map(|token| (token.text.as_str(), token.quoted)).collect();
+
+        assert_eq!(
+            texts,
+            vec![("one", false), ("two three", true), ("four five", true), ("", true), ("six", false)]
+        );
+
+        let arguments = parse(r#"add_question "Do you like cats?" truth party"#).unwrap();
+        assert_eq!(string(&arguments, "question").as_deref(), Some("Do you like cats?"));
+        assert_eq!(string(&arguments, "question_type").as_deref(), Some("TRUTH"));
+        assert_eq!(string(&arguments, "tag").as_deref(), Some("party"));
+
+        // Quoted text is never read as a named argument
+        let arguments = parse(r#"add_question "tag:odd" dare"#).unwrap();
+        assert_eq!(string(&arguments, "question").as_deref(), Some("tag:odd"));
+        assert_eq!(string(&arguments, "tag"), None);
+    }
+
+    #[test]
+    fn named_and_trailing_arguments() {
+        let arguments = parse("add_question question_type:Dare tag:party Sing a song").unwrap();
+
+        assert_eq!(string(&arguments, "question_type").as_deref(), Some("DARE"));
+        assert_eq!(string(&arguments, "question").as_deref(), Some("Sing a song"));
+    }
+
+    #[test]
+    fn missing_required_arguments() {
+        assert!(invalid_input(parse(r#"add_question "Do you like cats?""#)).contains("`question_type` is missing"));
+        assert!(invalid_input(parse("set_rate_limit")).contains("`requests` is missing"));
+        assert!(invalid_input(parse("qotd set")).contains("`channel` is missing"));
+        assert!(invalid_input(parse("qotd")).contains("Pick one of: set, show"));
+    }
+
+    #[test]
+    fn wrong_types() {
+        assert!(invalid_input(parse("set_rate_limit five")).contains("`five` isn't a valid `requests`"));
+        assert!(invalid_input(parse("set_rate_limit 5 maybe")).contains("`maybe` isn't a valid `enabled`"));
+        assert!(invalid_input(parse(r#"add_question "Cats?" both"#)).contains("`both` isn't a valid `question_type`"));
+        assert!(invalid_input(parse("qotd set #general")).contains("isn't a valid `channel`"));
+        assert!(invalid_input(parse("set_rate_limit 5 yes extra")).contains("Too many arguments"));
+
+        let arguments = parse("set_rate_limit 5 yes").unwrap();
+        assert!(matches!(arguments.options[0], (_, CommandDataOptionValue::Integer(5))));
+        assert!(matches!(arguments.options[1], (_, CommandDataOptionValue::Boolean(true))));
+
+        let arguments = parse("qotd set <#123>").unwrap();
+        assert_eq!(arguments.subcommand.as_deref(), Some("set"));
+        assert!(matches!(arguments.options[0], (_, CommandDataOptionValue::Channel(id)) if id.get() == 123));
+    }
+
+    #[test]
+    fn unknown_commands() {
+        let specs = specs();
+
+        assert!(find_command("truth", &specs).is_none());
+        assert!(find_command("", &specs).is_none());
+        assert!(find_command("  ", &specs).is_none());
+        assert!(find_command("add_questions x", &specs).is_none());
+        let found = find_command("ADD_QUESTION x y", &specs).map(|(spec, rest)| (spec.name.as_str(), rest));
+        assert_eq!(found, Some(("add_question", "x y")));
+    }
+
+    #[test]
+    fn option_limits() {
+        assert!(invalid_input(parse(r#"add_question "Cats?" truth abcdefghijk"#)).contains("`tag` must be 1 to 10 characters"));
+        assert!(invalid_input(parse("set_rate_limit 101")).contains("`requests` must be from 0 to 100"));
+        assert!(invalid_input(parse("set_rate_limit -1")).contains("`requests` must be from 0 to 100"));
+
+        assert!(parse(r#"add_question "Cats?" truth abcdefghij"#).is_ok());
+        assert!(parse("set_rate_limit 0").is_ok());
+        assert!(parse("set_rate_limit 100").is_ok());
+    }
+
+    #[test]
+    fn every_command_has_a_spec() {
+        let commands = create_commands(&Config::default());
+
+        assert_eq!(command_specs(&commands).len(), commands.len());
+    }
+}
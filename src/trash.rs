@@ -0,0 +1,277 @@
+//! Trash module for the bot
+//! Removed questions are moved to a trash per guild instead of being deleted, so a mistyped uid can be
+//! undone with the button on the removal reply or with `/restore_question`. A background task deletes
+//! questions that were in the trash longer than `trash_retention_days` for good.
+
+use std::time::Duration;
+
+use serenity::all::{
+    ButtonStyle, CreateActionRow, CreateButton, CreateCommand, CreateInteractionResponse, GuildId, UserId,
+};
+use tracing::{error, info};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    autocomplete::question_uid_option,
+    bot::Bot,
+    content_policy::PENDING,
+    embed::{fit_lines, MAX_MESSAGE_LENGTH},
+    error::{Error, Result},
+    i18n::{t, tf, CommandLocalizer},
+    invocation::{Invocation, Reply},
+    permissions::{Capability, Member},
+    questions::Question,
+    submission::validate_submission,
+};
+
+/// Prefix of the ids of undo buttons, followed by the uid of the removed question
+pub const UNDO_PREFIX: &str = "trash.undo.";
+
+/// How often questions past the retention period are purged
+const PURGE_TICK: Duration = Duration::from_secs(60 * 60);
+
+/// Most questions `/trash` shows at once
+const TRASH_PAGE: i64 = 15;
+
+impl Bot {
+    /// Moves a question to the trash, returning false if the guild has no such question outside of it
+    ///
+    /// # Parameters
+    /// * `guild_id: GuildId` - Guild the question was added to
+    /// * `question_uid: &str` - The question to move
+    /// * `user_id: UserId` - Member who removed it
+    pub async fn trash_question(&self, guild_id: GuildId, question_uid: &str, user_id: UserId) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE questions SET deleted_at = CURRENT_TIMESTAMP, deleted_by = ?
+            WHERE guild_id = ? AND uid = ? AND deleted_at IS NULL
+            "#,
+        )
+        .bind(user_id.get() as i64)
+        .bind(guild_id.get() as i64)
+        .bind(question_uid)
+        .execute(&self.database)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Takes a question out of the trash, returning false if it isn't in the guild's trash
+    pub async fn restore_trashed_question(&self, guild_id: GuildId, question_uid: &str) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE questions SET deleted_at = NULL, deleted_by = NULL
+            WHERE guild_id = ? AND uid = ? AND deleted_at IS NOT NULL
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(question_uid)
+        .execute(&self.database)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Gets a question in the trash of a guild by its uid
+    pub async fn get_trashed_question(&self, guild_id: GuildId, question_uid: &str) -> Result<Option<Question>> {
+        let question = sqlx::query_as::<_, Question>(
+            r#"
+            SELECT * FROM questions
+            WHERE guild_id = ? AND uid = ? AND deleted_at IS NOT NULL
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(question_uid)
+        .fetch_optional(&self.database)
+        .await?;
+
+        Ok(question)
+    }
+
+    /// Gets the questions most recently moved to the trash of a guild
+    ///
+    /// # Returns
+    /// * `(Vec<Question>, i64)` - Up to `TRASH_PAGE` questions, and how many are in the trash in total
+    pub async fn get_trash(&self, guild_id: GuildId) -> Result<(Vec<Question>, i64)> {
+        let questions = sqlx::query_as::<_, Question>(
+            r#"
+            SELECT * FROM questions
+            WHERE guild_id = ? AND deleted_at IS NOT NULL
+            ORDER BY deleted_at DESC, id DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(guild_id.get() as i64)
+        .bind(TRASH_PAGE)
+        .fetch_all(&self.database)
+        .await?;
+
+        let total = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM questions WHERE guild_id = ? AND deleted_at IS NOT NULL")
+            .bind(guild_id.get() as i64)
+            .fetch_one(&self.database)
+            .await?;
+
+        Ok((questions, total))
+    }
+
+    /// Deletes the questions that were in the trash longer than the retention period, returning how many
+    pub async fn purge_trash(&self) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM questions WHERE deleted_at < datetime('now', ?)")
+            .bind(format!("-{} days", self.config.trash_retention_days))
+            .execute(&self.database)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Runs forever, deleting questions that were in the trash longer than the retention period
+///
+/// # Parameters
+/// * `bot: Bot` - Bot instance for database interaction
+pub async fn run_trash_purge(bot: Bot) {
+    let mut interval = tokio::time::interval(PURGE_TICK);
+
+    loop {
+        interval.tick().await;
+
+        match bot.purge_trash().await {
+            Ok(0) => {}
+            Ok(purged) => info!(purged, "purged questions from the trash"),
+            Err(e) => error!(error = %e, "failed to purge the trash"),
+        }
+    }
+}
+
+/// Makes the button that undoes removing a question
+pub fn undo_button(question_uid: &str, language: &str) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![CreateButton::new(format!("{UNDO_PREFIX}{question_uid}"))
+        .label(t(language, "button.undo"))
+        .style(ButtonStyle::Secondary)])
+}
+
+/// Checks if a component id is an undo button
+pub fn is_undo_id(id: &str) -> bool {
+    id.starts_with(UNDO_PREFIX)
+}
+
+/// Takes a question out of the trash for a member, who needs to be its author or able to remove questions
+///
+/// # Returns
+/// * `String` - The message telling the member the question is back
+async fn restore(bot: &Bot, member: Member<'_>, question_uid: &str, language: &str) -> Result<String> {
+    let guild_id = member.guild_id.ok_or(Error::GuildOnly)?;
+
+    let Some(question) = bot.get_trashed_question(guild_id, question_uid).await? else {
+        return Err(Error::InvalidInput(tf(language, "trash.not_found", &[("uid", &question_uid)])));
+    };
+
+    // Whoever could remove the question can bring it back, rejected questions go back to the review queue
+    let capability = match question.status.as_str() {
+        PENDING => Capability::Moderate,
+        _ => Capability::Remove,
+    };
+    bot.require_for_question(member, capability, &question).await?;
+
+    // The same question may have been added again while this one was in the trash
+    validate_submission(bot, guild_id, &question.prompt, &question.rating, Some(question_uid), language).await?;
+
+    if !bot.restore_trashed_question(guild_id, question_uid).await? {
+        return Err(Error::InvalidInput(tf(language, "trash.not_found", &[("uid", &question_uid)])));
+    }
+
+    bot.record_audit(
+        AuditEntry::new(guild_id, member.user_id, AuditAction::RestoreQuestion)
+            .target(question_uid)
+            .after(Some(&question.prompt)),
+    )
+    .await;
+
+    Ok(tf(language, "trash.restored", &[("uid", &question_uid), ("question", &question.prompt)]))
+}
+
+/// Command to take a question out of the trash
+pub fn restore_question_command() -> CreateCommand {
+    CreateCommand::new("restore_question")
+        .localized("restore_question")
+        .add_option(question_uid_option("The question to restore"))
+}
+
+pub async fn restore_question(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let question_uid = invocation.str_option("question_uid").unwrap_or("");
+
+    Ok(Reply::text(restore(bot, Member::from(invocation), question_uid, language).await?))
+}
+
+/// Command to see the questions in the trash
+pub fn trash_command() -> CreateCommand {
+    CreateCommand::new("trash").localized("trash")
+}
+
+pub async fn trash(
+    bot: &Bot,
+    invocation: &Invocation,
+    language: &str,
+) -> Result<Reply> {
+    let guild_id = invocation.guild_id.ok_or(Error::GuildOnly)?;
+    let (questions, total) = bot.get_trash(guild_id).await?;
+
+    if questions.is_empty() {
+        return Ok(Reply::text(t(language, "trash.empty")).ephemeral(true));
+    }
+
+    let retention = i64::from(bot.config.trash_retention_days) * 86_400;
+    let line = |question: &Question, prompt: &str| {
+        let deleted_at = question.deleted_timestamp().unwrap_or_default();
+
+        tf(
+            language,
+            "trash.line",
+            &[
+                ("uid", &question.uid),
+                ("question", &prompt),
+                ("user", &question.deleted_by.unwrap_or_default()),
+                ("removed", &deleted_at),
+                ("purged", &(deleted_at + retention)),
+            ],
+        )
+    };
+
+    // Long questions are shortened so the list fits in a message
+    let header = tf(language, "trash.list", &[("shown", &questions.len()), ("total", &total), ("questions", &"")]);
+    let lines = fit_lines(
+        &questions,
+        MAX_MESSAGE_LENGTH.saturating_sub(header.chars().count()),
+        |question| question.prompt.as_str(),
+        line,
+    );
+
+    Ok(Reply::text(tf(
+        language,
+        "trash.list",
+        &[("shown", &questions.len()), ("total", &total), ("questions", &lines)],
+    ))
+    .ephemeral(true))
+}
+
+/// Restores the question an undo button points to, and takes the button off the removal reply
+///
+/// # Parameters
+/// * `bot: &Bot` - Used to access the database
+/// * `interaction: &str` - The id of the button, like `trash.undo.<uid>`
+/// * `member: Member<'_>` - Member who pressed the button
+/// * `language: &str` - Language to answer in
+pub async fn undo_removal(
+    bot: &Bot,
+    interaction: &str,
+    member: Member<'_>,
+    language: &str,
+) -> Result<CreateInteractionResponse> {
+    let question_uid = interaction.strip_prefix(UNDO_PREFIX).unwrap_or_default();
+
+    Ok(Reply::text(restore(bot, member, question_uid, language).await?).to_update())
+}
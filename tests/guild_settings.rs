@@ -0,0 +1,246 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serenity::all::{ChannelId, GuildId, Permissions, UserId};
+use sqlx::sqlite::SqliteConnectOptions;
+use truth_or_dare_bot::{
+    bot::Bot,
+    guild_settings::GuildSettings,
+    health::MIGRATOR,
+    invocation::{Invocation, Reply},
+    permissions::{permissions, Capability, Member},
+    rate_limit::{Limit, LimitScope},
+};
+
+const GUILD: GuildId = GuildId::new(123_456_789);
+
+async fn bot() -> Bot {
+    // A single connection, every connection to an in-memory database gets its own database
+    let database = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+
+    MIGRATOR.run(&database).await.unwrap();
+
+    Bot {
+        database,
+        signing_key: b"test".to_vec(),
+        config: Arc::default(),
+        health: Arc::default(),
+        rate_limiter: Arc::default(),
+        command_specs: Arc::default(),
+    }
+}
+
+/// Database file deleted when the test ends, even if it fails
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", self.0.display()));
+        }
+    }
+}
+
+/// Settings where nothing is the default
+fn changed_settings() -> GuildSettings {
+    GuildSettings {
+        guild_id: GUILD,
+        rating: "PG-13".to_string(),
+        admin: true,
+        timezone: "Europe/Paris".to_string(),
+        language: Some("fr".to_string()),
+        prefix: Some("?".to_string()),
+        rate_limit_user: Some(Limit::new(2, 30)),
+        rate_limit_channel: Some(Limit::new(8, 20)),
+        rate_limit_guild: Some(Limit::new(100, 60)),
+        audit_channel: Some(ChannelId::new(987_654_321)),
+        submission_quota: Some(3),
+    }
+}
+
+#[tokio::test]
+async fn guild_without_settings_uses_defaults() {
+    let bot = bot().await;
+
+    let settings = bot.get_guild_settings(GUILD).await.unwrap();
+
+    assert_eq!(settings, GuildSettings::new(GUILD, &bot.config));
+    assert!(!bot.get_guild_question_permissions(Some(GUILD)).await.unwrap());
+}
+
+#[tokio::test]
+async fn every_setting_survives_a_round_trip() {
+    let bot = bot().await;
+    let settings = changed_settings();
+
+    bot.save_guild_settings(&settings).await.unwrap();
+    assert_eq!(bot.get_guild_settings(GUILD).await.unwrap(), settings);
+
+    // Saving again updates the row instead of adding one
+    let settings = GuildSettings::new(GUILD, &bot.config);
+    bot.save_guild_settings(&settings).await.unwrap();
+    assert_eq!(bot.get_guild_settings(GUILD).await.unwrap(), settings);
+}
+
+#[tokio::test]
+async fn admin_only_setting_is_saved() {
+    let bot = bot().await;
+
+    bot.set_guild_question_permissions(GUILD, true).await.unwrap();
+    assert!(bot.get_guild_question_permissions(Some(GUILD)).await.unwrap());
+
+    bot.set_guild_question_permissions(GUILD, false).await.unwrap();
+    assert!(!bot.get_guild_question_permissions(Some(GUILD)).await.unwrap());
+}
+
+#[tokio::test]
+async fn admin_only_setting_limits_who_can_submit() {
+    let bot = bot().await;
+    let show = Invocation {
+        name: "permissions".to_string(),
+        subcommand: Some("show".to_string()),
+        options: Vec::new(),
+        guild_id: Some(GUILD),
+        channel_id: ChannelId::new(1),
+        user_id: UserId::new(2),
+        locale: String::new(),
+        permissions: Some(Permissions::empty()),
+        roles: Vec::new(),
+    };
+    let shown = |reply: Reply| reply.content.unwrap_or_default();
+
+    assert!(shown(permissions(&bot, &show, "en").await.unwrap()).contains("**submit questions**: everyone"));
+    bot.require(Member::from(&show), Capability::Submit).await.unwrap();
+
+    // /permissions show has to tell the same as the check does
+    bot.set_guild_question_permissions(GUILD, true).await.unwrap();
+
+    assert!(shown(permissions(&bot, &show, "en").await.unwrap()).contains("**submit questions**: admins only"));
+    assert!(bot.require(Member::from(&show), Capability::Submit).await.is_err());
+    // Playing isn't affected by the setting
+    assert!(shown(permissions(&bot, &show, "en").await.unwrap()).contains("**play**: everyone"));
+}
+
+#[tokio::test]
+async fn each_setter_only_changes_its_setting() {
+    let bot = bot().await;
+    let mut expected = changed_settings();
+    bot.save_guild_settings(&expected).await.unwrap();
+
+    bot.set_guild_rating(GUILD, "ALL").await.unwrap();
+    expected.rating = "ALL".to_string();
+    assert_eq!(bot.get_guild_settings(GUILD).await.unwrap(), expected);
+
+    bot.set_guild_question_permissions(GUILD, false).await.unwrap();
+    expected.admin = false;
+    assert_eq!(bot.get_guild_settings(GUILD).await.unwrap(), expected);
+
+    bot.set_guild_timezone(GUILD, "Asia/Tokyo").await.unwrap();
+    expected.timezone = "Asia/Tokyo".to_string();
+    assert_eq!(bot.get_guild_settings(GUILD).await.unwrap(), expected);
+
+    bot.set_guild_language(GUILD, None).await.unwrap();
+    expected.language = None;
+    assert_eq!(bot.get_guild_settings(GUILD).await.unwrap(), expected);
+
+    bot.set_guild_prefix(GUILD, Some("tod!")).await.unwrap();
+    expected.prefix = Some("tod!".to_string());
+    assert_eq!(bot.get_guild_settings(GUILD).await.unwrap(), expected);
+
+    bot.set_guild_rate_limit(GUILD, LimitScope::Channel, None).await.unwrap();
+    expected.rate_limit_channel = None;
+    assert_eq!(bot.get_guild_settings(GUILD).await.unwrap(), expected);
+
+    bot.set_audit_channel(GUILD, None).await.unwrap();
+    expected.audit_channel = None;
+    assert_eq!(bot.get_guild_settings(GUILD).await.unwrap(), expected);
+
+    bot.set_submission_quota(GUILD, Some(0)).await.unwrap();
+    expected.submission_quota = Some(0);
+    assert_eq!(bot.get_guild_settings(GUILD).await.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn setters_on_a_new_guild_keep_the_defaults() {
+    let bot = bot().await;
+
+    bot.set_guild_question_permissions(GUILD, true).await.unwrap();
+
+    let settings = bot.get_guild_settings(GUILD).await.unwrap();
+    assert!(settings.admin);
+    assert_eq!(settings.rating, bot.config.default_rating);
+    assert_eq!(settings.timezone, "UTC");
+    assert_eq!(bot.get_guild_prefix(Some(GUILD)).await.unwrap(), bot.config.prefix);
+}
+
+#[tokio::test]
+async fn getters_read_the_saved_settings() {
+    let bot = bot().await;
+    bot.save_guild_settings(&changed_settings()).await.unwrap();
+
+    assert_eq!(bot.get_guild_rating(Some(GUILD)).await.unwrap(), "PG-13");
+    assert_eq!(bot.get_guild_timezone(Some(GUILD)).await.unwrap(), "Europe/Paris");
+    assert_eq!(bot.get_language(Some(GUILD), "en-US").await.unwrap(), "fr");
+    assert_eq!(bot.get_guild_prefix(Some(GUILD)).await.unwrap(), "?");
+    assert_eq!(bot.get_submission_quota(GUILD).await.unwrap(), 3);
+
+    let limits = bot.get_rate_limits(Some(GUILD)).await.unwrap();
+    assert_eq!(limits.user, Limit::new(2, 30));
+    assert_eq!(limits.channel, Limit::new(8, 20));
+    assert_eq!(limits.guild, Limit::new(100, 60));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn setters_running_at_once_keep_both_changes() {
+    // Lost updates need setters on different connections, which an in-memory database can't share
+    let file = TempFile(std::env::temp_dir().join(format!("guild_settings_{}.sqlite", uuid::Uuid::new_v4())));
+    let options = SqliteConnectOptions::new().filename(&file.0).create_if_missing(true);
+    let database = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(4)
+        .connect_with(options)
+        .await
+        .unwrap();
+    MIGRATOR.run(&database).await.unwrap();
+
+    let bot = Bot { database, ..bot().await };
+
+    for guild in 1..=20 {
+        let guild_id = GuildId::new(guild);
+        let spawn_bot = || bot.clone();
+
+        let (prefix, audit_channel, rating, quota) = tokio::join!(
+            tokio::spawn({
+                let bot = spawn_bot();
+                async move { bot.set_guild_prefix(guild_id, Some("?")).await }
+            }),
+            tokio::spawn({
+                let bot = spawn_bot();
+                async move { bot.set_audit_channel(guild_id, Some(ChannelId::new(987_654_321))).await }
+            }),
+            tokio::spawn({
+                let bot = spawn_bot();
+                async move { bot.set_guild_rating(guild_id, "PG-13").await }
+            }),
+            tokio::spawn({
+                let bot = spawn_bot();
+                async move { bot.set_submission_quota(guild_id, Some(3)).await }
+            }),
+        );
+        prefix.unwrap().unwrap();
+        audit_channel.unwrap().unwrap();
+        rating.unwrap().unwrap();
+        quota.unwrap().unwrap();
+
+        let settings = bot.get_guild_settings(guild_id).await.unwrap();
+        assert_eq!(settings.prefix.as_deref(), Some("?"));
+        assert_eq!(settings.audit_channel, Some(ChannelId::new(987_654_321)));
+        assert_eq!(settings.rating, "PG-13");
+        assert_eq!(settings.submission_quota, Some(3));
+    }
+
+    bot.database.close().await;
+}
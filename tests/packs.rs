@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use serenity::all::{GuildId, UserId};
+use truth_or_dare_bot::{bot::Bot, health::MIGRATOR, questions::QuestionType};
+
+const PUBLISHER: GuildId = GuildId::new(111_111_111);
+const SUBSCRIBER: GuildId = GuildId::new(222_222_222);
+const USER: UserId = UserId::new(333_333_333);
+
+async fn bot() -> Bot {
+    // A single connection, every connection to an in-memory database gets its own database
+    let database = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+
+    MIGRATOR.run(&database).await.unwrap();
+
+    // Only the questions a test adds can be drawn
+    sqlx::query("DELETE FROM questions").execute(&database).await.unwrap();
+
+    Bot {
+        database,
+        signing_key: b"test".to_vec(),
+        config: Arc::default(),
+        health: Arc::default(),
+        rate_limiter: Arc::default(),
+        command_specs: Arc::default(),
+    }
+}
+
+/// Adds an approved PG truth with a tag to the publishing guild
+async fn add_question(bot: &Bot, prompt: &str, tag: &str) {
+    sqlx::query(
+        r#"
+        INSERT INTO questions (prompt, question_type, rating, guild_id, uid, tag)
+        VALUES (?, 'TRUTH', 'PG', ?, lower(hex(randomblob(16))), ?)
+        "#,
+    )
+    .bind(prompt)
+    .bind(PUBLISHER.get() as i64)
+    .bind(tag)
+    .execute(&bot.database)
+    .await
+    .unwrap();
+}
+
+async fn draw(bot: &Bot, guild_id: GuildId) -> Option<String> {
+    bot.get_random_question(QuestionType::TRUTH, "PG", Some(guild_id), "en", &[])
+        .await
+        .unwrap()
+        .map(|question| question.prompt)
+}
+
+#[tokio::test]
+async fn publishing_a_tag_twice_keeps_the_first_pack() {
+    let bot = bot().await;
+    add_question(&bot, "What is your favorite color?", "colors").await;
+
+    let pack = bot.publish_pack(PUBLISHER, "colors", USER).await.unwrap().unwrap();
+
+    assert_eq!(pack.guild_id, PUBLISHER);
+    assert_eq!(pack.tag, "colors");
+    assert_eq!(pack.share_code.len(), 8);
+    assert_eq!(pack.share_code, pack.share_code.to_uppercase());
+    assert_eq!(pack.questions, 1);
+    assert_eq!(pack.subscribers, 0);
+    assert!(bot.publish_pack(PUBLISHER, "colors", USER).await.unwrap().is_none());
+
+    let found = bot
+        .get_pack(&format!(" {} ", pack.share_code.to_lowercase()))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, pack.id);
+}
+
+#[tokio::test]
+async fn subscribed_packs_are_drawn_until_unsubscribing() {
+    let bot = bot().await;
+    add_question(&bot, "What is your favorite color?", "colors").await;
+    let pack = bot.publish_pack(PUBLISHER, "colors", USER).await.unwrap().unwrap();
+
+    assert_eq!(draw(&bot, SUBSCRIBER).await, None);
+
+    assert!(bot.subscribe_pack(SUBSCRIBER, pack.id, USER).await.unwrap());
+    assert!(!bot.subscribe_pack(SUBSCRIBER, pack.id, USER).await.unwrap());
+    assert_eq!(draw(&bot, SUBSCRIBER).await.as_deref(), Some("What is your favorite color?"));
+
+    let subscribed = bot.get_subscribed_packs(SUBSCRIBER).await.unwrap();
+    assert_eq!(subscribed.len(), 1);
+    assert_eq!(subscribed[0].subscribers, 1);
+
+    assert!(bot.unsubscribe_pack(SUBSCRIBER, pack.id).await.unwrap());
+    assert!(!bot.unsubscribe_pack(SUBSCRIBER, pack.id).await.unwrap());
+    assert_eq!(draw(&bot, SUBSCRIBER).await, None);
+    assert!(bot.get_subscribed_packs(SUBSCRIBER).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn unpublishing_ends_subscriptions() {
+    let bot = bot().await;
+    add_question(&bot, "What is your favorite color?", "colors").await;
+    let pack = bot.publish_pack(PUBLISHER, "colors", USER).await.unwrap().unwrap();
+    bot.subscribe_pack(SUBSCRIBER, pack.id, USER).await.unwrap();
+
+    bot.unpublish_pack(pack.id).await.unwrap();
+
+    assert!(bot.get_pack(&pack.share_code).await.unwrap().is_none());
+    assert!(bot.get_subscribed_packs(SUBSCRIBER).await.unwrap().is_empty());
+    assert_eq!(draw(&bot, SUBSCRIBER).await, None);
+    assert!(bot.publish_pack(PUBLISHER, "colors", USER).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn subscriber_blocklist_leaves_pack_questions_out() {
+    let bot = bot().await;
+    add_question(&bot, "Have you ever eaten a spider?", "food").await;
+    let pack = bot.publish_pack(PUBLISHER, "food", USER).await.unwrap().unwrap();
+    bot.subscribe_pack(SUBSCRIBER, pack.id, USER).await.unwrap();
+
+    bot.add_blocked_word(SUBSCRIBER, "spider").await.unwrap();
+
+    let blocked = bot
+        .get_blocked_pack_questions(SUBSCRIBER, QuestionType::TRUTH, "PG")
+        .await
+        .unwrap();
+    assert_eq!(blocked.len(), 1);
+    assert_eq!(draw(&bot, SUBSCRIBER).await, None);
+
+    // The blocklist is the subscriber's, the publisher still draws its own question
+    assert!(
+        bot.get_blocked_pack_questions(PUBLISHER, QuestionType::TRUTH, "PG")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(draw(&bot, PUBLISHER).await.as_deref(), Some("Have you ever eaten a spider?"));
+
+    bot.remove_blocked_word(SUBSCRIBER, "spider").await.unwrap();
+    assert_eq!(draw(&bot, SUBSCRIBER).await.as_deref(), Some("Have you ever eaten a spider?"));
+}